@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, anyhow};
+use clap::Parser;
+use shakmaty::fen::Fen;
+use shakmaty::{Chess, EnPassantMode, Position};
+
+use _core::canonicalize_fen_str;
+use _core::checkpoint::{Checkpoint, load_checkpoint, save_checkpoint};
+use _core::pgn::{PgnMove, format_game, parse_games};
+use _core::stockfish::{EnginePool, LineScore};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "annotate",
+    about = "Add engine evaluations to a repertoire PGN as [%eval] comments"
+)]
+struct Args {
+    /// PGN file containing the repertoire (use '-' for stdin)
+    pgn_file: String,
+
+    /// Path to the UCI engine binary
+    #[arg(long, default_value = "stockfish")]
+    engine: String,
+
+    /// Search depth for each position
+    #[arg(long, default_value_t = 16)]
+    depth: u32,
+
+    /// Lines searched per position; the best line's score is written
+    #[arg(long, default_value_t = 1)]
+    multipv: u32,
+
+    /// Destination file (use '-' for stdout)
+    #[arg(long, default_value = "-")]
+    output: String,
+
+    /// Save evaluations to this file as they come in and reuse them on the next
+    /// run, so an interrupted annotation resumes where it stopped
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// New evaluations between checkpoint saves
+    #[arg(long, default_value_t = 25)]
+    checkpoint_every: usize,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let pgn_text = if args.pgn_file == "-" {
+        std::io::read_to_string(std::io::stdin()).context("Failed to read PGN from stdin")?
+    } else {
+        fs::read_to_string(&args.pgn_file)
+            .with_context(|| format!("Failed to read PGN file: {}", args.pgn_file))?
+    };
+    let mut games = parse_games(&pgn_text).map_err(|err| anyhow!(err))?;
+
+    let engine = EnginePool::new(&args.engine, args.depth, args.multipv.max(1), 1)
+        .with_context(|| format!("Failed to start engine: {}", args.engine))?;
+    let progress = match &args.checkpoint {
+        Some(path) => load_checkpoint(path)
+            .with_context(|| format!("Failed to read checkpoint: {}", path.display()))?
+            .unwrap_or_default(),
+        None => Checkpoint::default(),
+    };
+    if progress.cursor > 0 || !progress.evaluated.is_empty() {
+        eprintln!(
+            "Resuming at game {} with {} positions already evaluated",
+            progress.cursor + 1,
+            progress.evaluated.len()
+        );
+    }
+    let mut evals = EvalMemo {
+        engine,
+        progress,
+        checkpoint: args.checkpoint.clone(),
+        checkpoint_every: args.checkpoint_every.max(1),
+        unsaved: 0,
+    };
+    let mut annotated = Vec::with_capacity(games.len());
+    for (index, game) in games.iter_mut().enumerate() {
+        evals.progress.cursor = index;
+        annotate_line(&mut game.moves, &Chess::new(), &mut evals)?;
+        annotated.push(format_game(game));
+    }
+    evals.progress.cursor = games.len();
+    evals.save()?;
+    write_output(&args.output, &annotated.join("\n"))
+}
+
+/// Engine evaluations keyed by canonical FEN, so transpositions are searched
+/// once. With a checkpoint file, they are saved every `checkpoint_every` new
+/// evaluations; games before the cursor are replayed from the saved comments.
+struct EvalMemo {
+    engine: EnginePool,
+    progress: Checkpoint<String>,
+    checkpoint: Option<PathBuf>,
+    checkpoint_every: usize,
+    unsaved: usize,
+}
+
+impl EvalMemo {
+    fn eval_comment(&mut self, position: &Chess) -> anyhow::Result<String> {
+        let fen = Fen::from_position(position.clone(), EnPassantMode::Legal).to_string();
+        let canonical = canonicalize_fen_str(&fen).map_err(|err| anyhow!(err))?;
+        if let Some(comment) = self.progress.evaluated.get(&canonical) {
+            return Ok(comment.clone());
+        }
+        let lines = self
+            .engine
+            .evaluate(&canonical)
+            .with_context(|| format!("Failed to evaluate {canonical}"))?;
+        let comment = match lines.first() {
+            Some(best) => format!("[%eval {}]", format_eval(best)),
+            // Mate or stalemate on the board: nothing for the engine to score.
+            None => String::new(),
+        };
+        self.progress.evaluated.insert(canonical, comment.clone());
+        self.unsaved += 1;
+        if self.unsaved >= self.checkpoint_every {
+            self.save()?;
+        }
+        Ok(comment)
+    }
+
+    fn save(&mut self) -> anyhow::Result<()> {
+        if let Some(path) = &self.checkpoint {
+            save_checkpoint(path, &self.progress)
+                .with_context(|| format!("Failed to write checkpoint: {}", path.display()))?;
+        }
+        self.unsaved = 0;
+        Ok(())
+    }
+}
+
+/// Lichess `%eval` syntax: pawns with two decimals, or `#N` / `#-N` for mates.
+fn format_eval(line: &LineScore) -> String {
+    match (line.mate, line.cp) {
+        (Some(mate), _) => format!("#{mate}"),
+        (None, Some(cp)) => format!("{:.2}", f64::from(cp) / 100.0),
+        (None, None) => "0.00".to_string(),
+    }
+}
+
+/// Add an eval comment after every move of `line`, which starts at
+/// `position`, and of the variations branching off it. Existing comments stay
+/// in front of it.
+fn annotate_line(
+    line: &mut [PgnMove],
+    position: &Chess,
+    evals: &mut EvalMemo,
+) -> anyhow::Result<()> {
+    let mut position = position.clone();
+    for pgn_move in line {
+        let before = position.clone();
+        let mv = pgn_move.san.san.to_move(&position)?;
+        position.play_unchecked(&mv);
+        let comment = evals.eval_comment(&position)?;
+        if !comment.is_empty() {
+            pgn_move.comments.push(comment);
+        }
+        for variation in &mut pgn_move.variations {
+            annotate_line(variation, &before, evals)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_output(output: &str, body: &str) -> anyhow::Result<()> {
+    if output == "-" {
+        print!("{body}");
+    } else {
+        fs::write(output, body)?;
+        println!("Wrote annotated PGN to {output}");
+    }
+    Ok(())
+}
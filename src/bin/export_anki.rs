@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fs;
+
+use anyhow::{Context, anyhow};
+use clap::{Parser, ValueEnum};
+use shakmaty::{Chess, Color, Position};
+
+use _core::frequency::canonical_fen;
+use _core::pgn::{PgnMove, parse_games};
+use _core::player_turn_flags;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "export_anki",
+    about = "Write one Anki card per player decision in a repertoire PGN"
+)]
+struct Args {
+    /// PGN file containing the repertoire (use '-' for stdin)
+    pgn_file: String,
+
+    /// Player side whose moves become the answers
+    #[arg(long, value_enum, default_value_t = Side::White)]
+    side: Side,
+
+    /// Destination of the tab-separated cards (use '-' for stdout)
+    #[arg(long, default_value = "-")]
+    output: String,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Side {
+    White,
+    Black,
+}
+
+/// A position reached in the repertoire, the moves played from it, and the
+/// first line that reached it.
+struct Node {
+    fen: String,
+    moves: Vec<String>,
+    line: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let pgn_text = if args.pgn_file == "-" {
+        std::io::read_to_string(std::io::stdin()).context("Failed to read PGN from stdin")?
+    } else {
+        fs::read_to_string(&args.pgn_file)
+            .with_context(|| format!("Failed to read PGN file: {}", args.pgn_file))?
+    };
+    let games = parse_games(&pgn_text).map_err(|err| anyhow!(err))?;
+
+    let mut nodes = Nodes::default();
+    for game in &games {
+        nodes.add_line(&game.moves, Chess::new(), &mut Vec::new())?;
+    }
+
+    let side = match args.side {
+        Side::White => Color::White,
+        Side::Black => Color::Black,
+    };
+    let fens: Vec<String> = nodes.list.iter().map(|node| node.fen.clone()).collect();
+    let mask = player_turn_flags(side, &fens).map_err(|err| anyhow!(err))?;
+
+    let mut cards = String::from("#separator:tab\n#html:false\n#columns:FEN\tMove\tComment\n");
+    let mut card_count = 0;
+    for (node, is_player) in nodes.list.iter().zip(mask) {
+        if is_player {
+            cards.push_str(&format!(
+                "{}\t{}\t{}\n",
+                node.fen,
+                node.moves.join(" / "),
+                node.line
+            ));
+            card_count += 1;
+        }
+    }
+
+    if args.output == "-" {
+        print!("{cards}");
+    } else {
+        fs::write(&args.output, cards)?;
+        println!("Wrote {card_count} cards to {}", args.output);
+    }
+    Ok(())
+}
+
+/// Positions in first-seen order, mainline moves before their variations.
+#[derive(Default)]
+struct Nodes {
+    list: Vec<Node>,
+    index: HashMap<String, usize>,
+}
+
+impl Nodes {
+    fn add_line(
+        &mut self,
+        line: &[PgnMove],
+        mut position: Chess,
+        path: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        let depth = path.len();
+        for pgn_move in line {
+            let mv = pgn_move
+                .san
+                .san
+                .to_move(&position)
+                .with_context(|| format!("Illegal move {} in PGN", pgn_move.san))?;
+            let fen = canonical_fen(&position).map_err(|err| anyhow!(err))?;
+            let index = *self.index.entry(fen.clone()).or_insert_with(|| {
+                self.list.push(Node {
+                    fen,
+                    moves: Vec::new(),
+                    line: path.join(" "),
+                });
+                self.list.len() - 1
+            });
+            let san = pgn_move.san.to_string();
+            if !self.list[index].moves.contains(&san) {
+                self.list[index].moves.push(san.clone());
+            }
+
+            for variation in &pgn_move.variations {
+                self.add_line(variation, position.clone(), path)?;
+            }
+            path.push(match position.turn() {
+                Color::White => format!("{}. {san}", position.fullmoves()),
+                Color::Black if path.is_empty() => format!("{}... {san}", position.fullmoves()),
+                Color::Black => san,
+            });
+            position.play_unchecked(&mv);
+        }
+        path.truncate(depth);
+        Ok(())
+    }
+}
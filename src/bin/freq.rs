@@ -1,221 +1,520 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::io::Read;
 
 use anyhow::{Context, anyhow};
 use chrono::Utc;
 use clap::{Parser, ValueEnum};
+use flate2::read::MultiGzDecoder;
 use serde::Serialize;
 use shakmaty::fen::Fen;
-use shakmaty::san::SanPlus;
-use shakmaty::uci::UciMove;
-use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Move, Position};
+use shakmaty::{CastlingMode, Chess, Color, Move, Position};
 
-use _core::canonicalize_fen_str;
+use _core::frequency::{
+    Fingerprint, FrequencyOptions, PlayedMove, ResultWeights, canonical_fen,
+    count_move_frequencies, move_location, move_ply,
+};
+use _core::pgn::{PgnGame, PgnMove, parse_games};
 
 #[derive(Parser, Debug)]
 #[command(name = "freq", about = "Compute move frequencies for a repertoire PGN")]
 struct Args {
-    /// PGN file containing the repertoire
+    /// PGN file containing the repertoire (use '-' for stdin; gzip files are decompressed)
     pgn_file: String,
 
-    /// Player side whose move frequencies should be analyzed
+    /// Player side whose move frequencies should be analyzed (`both` ranks each
+    /// side separately)
     #[arg(long, value_enum, default_value_t = Side::White)]
     side: Side,
 
-    /// Destination JSON file (use '-' for stdout)
+    /// Destination file (use '-' for stdout)
     #[arg(long, default_value = "-")]
     output: String,
 
-    /// Number of spaces to indent JSON (0 for compact)
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Number of spaces to indent JSON (0 for compact; ignored for CSV)
     #[arg(long, default_value_t = 2)]
     indent: u16,
+
+    /// Count each move at most once per game when a position repeats
+    #[arg(long)]
+    collapse_repetitions: bool,
+
+    /// Report how many legal moves each position has and how many are covered,
+    /// counted before `--top-n` and `--min-frequency` trim the rankings
+    #[arg(long)]
+    with_completeness: bool,
+
+    /// Move notation used as each ranked move's primary key
+    #[arg(long, value_enum, default_value_t = MoveKey::San)]
+    key: MoveKey,
+
+    /// Emit rankings as an array of `{fen, moves}` sorted by FEN
+    #[arg(long)]
+    sorted: bool,
+
+    /// Add a `generated_at` timestamp to the JSON output; without it, identical
+    /// inputs produce identical output
+    #[arg(long)]
+    timestamp: bool,
+
+    /// Only print the number of games, player decision points and moves
+    #[arg(long)]
+    count_only: bool,
+
+    /// Weight each counted move by the result of its game for the player
+    #[arg(long)]
+    weight_by_result: bool,
+
+    /// Win, draw and loss weights used by --weight-by-result
+    #[arg(long, value_parser = parse_result_weights, default_value = "3,1,0")]
+    result_weights: ResultWeights,
+
+    /// Keep at most this many moves per position, most frequent first (0 keeps all)
+    #[arg(long, default_value_t = 0)]
+    top_n: usize,
+
+    /// Drop moves seen fewer than this many times, then positions left with no moves
+    #[arg(long, default_value_t = 1)]
+    min_frequency: u32,
+
+    /// Ignore moves past this ply, and the positions they reach (0 for no limit)
+    #[arg(long, default_value_t = 0)]
+    max_ply: u32,
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 enum Side {
     White,
     Black,
+    Both,
+}
+
+impl Side {
+    fn colors(self) -> &'static [Color] {
+        match self {
+            Side::White => &[Color::White],
+            Side::Black => &[Color::Black],
+            Side::Both => &[Color::White, Color::Black],
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::White => "white",
+            Side::Black => "black",
+            Side::Both => "both",
+        }
+    }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
-struct Fingerprint {
-    piece: char,
-    from: String,
-    to: String,
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum MoveKey {
+    San,
+    Uci,
+}
+
+impl MoveKey {
+    fn as_str(self) -> &'static str {
+        match self {
+            MoveKey::San => "san",
+            MoveKey::Uci => "uci",
+        }
+    }
+}
+
+fn parse_result_weights(raw: &str) -> Result<ResultWeights, String> {
+    let weights = raw
+        .split(',')
+        .map(|part| part.trim().parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|err| format!("invalid weight: {err}"))?;
+    match weights[..] {
+        [win, draw, loss] => Ok(ResultWeights { win, draw, loss }),
+        _ => Err("expected three comma-separated weights: WIN,DRAW,LOSS".to_string()),
+    }
+}
+
+struct RankingOptions {
+    frequency: FrequencyOptions,
+    key: MoveKey,
+    top_n: usize,
+    min_frequency: u32,
+    with_completeness: bool,
 }
 
 #[derive(Serialize)]
 struct RankedMove {
+    key: String,
     uci: String,
     san: String,
     frequency: u32,
+    /// NAGs attached to this move anywhere in the input, sorted and deduplicated.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    nags: Vec<u8>,
+}
+
+/// A move recorded from a position, with the NAGs seen on it so far.
+struct Edge {
+    mv: Move,
+    uci: String,
+    san: String,
+    nags: Vec<u8>,
+}
+
+#[derive(Serialize)]
+struct RankedPosition {
+    fen: String,
+    moves: Vec<RankedMove>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Rankings {
+    ByFen(BTreeMap<String, Vec<RankedMove>>),
+    Sorted(Vec<RankedPosition>),
+}
+
+impl Rankings {
+    fn sorted(rankings: BTreeMap<String, Vec<RankedMove>>) -> Self {
+        Rankings::Sorted(sorted_positions(rankings))
+    }
+}
+
+fn sorted_positions(rankings: BTreeMap<String, Vec<RankedMove>>) -> Vec<RankedPosition> {
+    rankings
+        .into_iter()
+        .map(|(fen, moves)| RankedPosition { fen, moves })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct Counts {
+    games: usize,
+    player_nodes: usize,
+    total_moves: usize,
+}
+
+#[derive(Serialize)]
+struct Completeness {
+    legal_move_count: usize,
+    covered_count: usize,
 }
 
 #[derive(Serialize)]
 struct Payload {
-    generated_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generated_at: Option<String>,
     side: String,
+    key: String,
+    #[serde(flatten)]
+    sides: PayloadSides,
+}
+
+/// Rankings for one side at the top level, or for both nested under `white`
+/// and `black`.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum PayloadSides {
+    One(SideRankings),
+    Both {
+        white: SideRankings,
+        black: SideRankings,
+    },
+}
+
+#[derive(Serialize)]
+struct SideRankings {
     total_nodes: usize,
-    rankings: HashMap<String, Vec<RankedMove>>,
+    rankings: Rankings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completeness: Option<BTreeMap<String, Completeness>>,
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let pgn_text = fs::read_to_string(&args.pgn_file)
-        .with_context(|| format!("Failed to read PGN file: {}", args.pgn_file))?;
-    let mainline = parse_mainline_san(&pgn_text)?;
-
-    let side_color = match args.side {
-        Side::White => Color::White,
-        Side::Black => Color::Black,
+    let pgn_text = if args.pgn_file == "-" {
+        std::io::read_to_string(std::io::stdin()).context("Failed to read PGN from stdin")?
+    } else {
+        read_pgn_file(&args.pgn_file)
+            .with_context(|| format!("Failed to read PGN file: {}", args.pgn_file))?
     };
+    let games = parse_games(&pgn_text).map_err(|err| anyhow!(err))?;
+
+    let max_ply = (args.max_ply > 0).then_some(args.max_ply);
+    if args.count_only {
+        let counts = count_games(&games, args.side.colors(), max_ply)?;
+        return write_output(&args.output, &to_json(&counts, args.indent)?);
+    }
 
-    let (rankings, total_nodes) = build_rankings(&mainline, side_color)?;
+    let mut per_side = Vec::new();
+    for &color in args.side.colors() {
+        let options = RankingOptions {
+            frequency: FrequencyOptions {
+                player_side: color,
+                collapse_repetitions: args.collapse_repetitions,
+                result_weights: args.weight_by_result.then_some(args.result_weights),
+                max_ply,
+            },
+            key: args.key,
+            top_n: args.top_n,
+            min_frequency: args.min_frequency,
+            with_completeness: args.with_completeness,
+        };
+        per_side.push(build_rankings(&games, &options)?);
+    }
+
+    if let Format::Csv = args.format {
+        // Each FEN names its side to move, so the sides' rows never collide.
+        let mut rankings = BTreeMap::new();
+        for (side_rankings, _, _) in per_side {
+            rankings.extend(side_rankings);
+        }
+        return write_output(&args.output, &rankings_csv(rankings));
+    }
 
+    let mut sides = Vec::with_capacity(per_side.len());
+    for (rankings, total_nodes, completeness) in per_side {
+        let rankings = if args.sorted {
+            Rankings::sorted(rankings)
+        } else {
+            Rankings::ByFen(rankings)
+        };
+        sides.push(SideRankings {
+            total_nodes,
+            rankings,
+            completeness,
+        });
+    }
+    let mut sides = sides.into_iter();
+    let sides = match (sides.next(), sides.next()) {
+        (Some(white), Some(black)) => PayloadSides::Both { white, black },
+        (Some(side), None) => PayloadSides::One(side),
+        _ => unreachable!("every side option ranks at least one color"),
+    };
     let payload = Payload {
-        generated_at: Utc::now().to_rfc3339(),
-        side: match args.side {
-            Side::White => "white".to_string(),
-            Side::Black => "black".to_string(),
-        },
-        total_nodes,
-        rankings,
+        generated_at: args.timestamp.then(|| Utc::now().to_rfc3339()),
+        side: args.side.as_str().to_string(),
+        key: args.key.as_str().to_string(),
+        sides,
     };
 
-    let indent = args.indent as usize;
+    write_output(&args.output, &to_json(&payload, args.indent)?)
+}
+
+/// Read a PGN file, transparently decompressing it when the name ends in
+/// `.gz` or the contents start with the gzip magic bytes.
+fn read_pgn_file(path: &str) -> anyhow::Result<String> {
+    let bytes = fs::read(path)?;
+    if path.ends_with(".gz") || bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut text = String::new();
+        MultiGzDecoder::new(bytes.as_slice())
+            .read_to_string(&mut text)
+            .context("Failed to decompress gzip PGN")?;
+        return Ok(text);
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn to_json(value: &impl Serialize, indent: u16) -> anyhow::Result<String> {
     let json = if indent == 0 {
-        serde_json::to_string(&payload)?
+        serde_json::to_string(value)?
     } else {
-        serde_json::to_string_pretty(&payload)?
+        serde_json::to_string_pretty(value)?
     };
+    Ok(json + "\n")
+}
 
-    if args.output == "-" {
-        println!("{}", json);
+fn write_output(output: &str, body: &str) -> anyhow::Result<()> {
+    if output == "-" {
+        print!("{body}");
     } else {
-        std::fs::write(&args.output, json + "\n")?;
-        println!("Wrote frequency map to {}", args.output);
+        std::fs::write(output, body)?;
+        println!("Wrote frequency map to {output}");
     }
-
     Ok(())
 }
 
-fn parse_mainline_san(text: &str) -> anyhow::Result<Vec<SanPlus>> {
-    let mut sans: Vec<SanPlus> = Vec::new();
-    let mut variation_depth: i32 = 0;
-    for raw in text.split_whitespace() {
-        if raw.starts_with('[')
-            || raw.starts_with('{')
-            || raw.ends_with(']')
-            || raw.starts_with('"')
-        {
-            continue;
-        }
-
-        let open = raw.matches('(').count() as i32;
-        let close = raw.matches(')').count() as i32;
-        variation_depth += open;
-
-        if variation_depth > 0 {
-            variation_depth -= close;
-            continue;
-        }
-
-        variation_depth = (variation_depth - close).max(0);
-
-        let token = raw.trim_matches(|c| c == '(' || c == ')');
-        if token.is_empty() {
-            continue;
-        }
-        if token.contains('.') {
-            continue;
-        }
-        if matches!(token, "*" | "1-0" | "0-1" | "1/2-1/2") {
-            break;
+/// One `fen,uci,san,frequency` row per ranked move, ordered by FEN and then by
+/// rank within each position.
+fn rankings_csv(rankings: BTreeMap<String, Vec<RankedMove>>) -> String {
+    let mut csv = String::from("fen,uci,san,frequency\n");
+    for position in sorted_positions(rankings) {
+        for mv in position.moves {
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                position.fen, mv.uci, mv.san, mv.frequency
+            ));
         }
-        if token.starts_with('$') {
-            continue;
-        }
-        let san = SanPlus::from_ascii(token.as_bytes())
-            .with_context(|| format!("Invalid SAN token in PGN: {token}"))?;
-        sans.push(san);
     }
-    Ok(sans)
+    csv
 }
 
+/// Rank the moves at every position where `options.frequency.player_side` is
+/// to move. With `options.with_completeness`, also return the legal and
+/// prepared move counts of each ranked position, taken before `min_frequency`
+/// and `top_n` cut the move lists.
+#[allow(clippy::type_complexity)]
 fn build_rankings(
-    mainline: &[SanPlus],
-    player_side: Color,
-) -> anyhow::Result<(HashMap<String, Vec<RankedMove>>, usize)> {
-    let mut position = Chess::new();
-    let mut nodes: HashMap<String, Vec<(Move, String, String)>> = HashMap::new();
-    let mut frequencies: HashMap<Fingerprint, u32> = HashMap::new();
-
-    let root_fen = canonicalize_current_fen(&position)?;
-    nodes.entry(root_fen.clone()).or_default();
-
-    for san in mainline {
-        let mv = san.san.to_move(&position)?;
-        let parent_fen = canonicalize_current_fen(&position)?;
-        let san_str = san.to_string();
-        let uci = UciMove::from_move(&mv, CastlingMode::Standard).to_string();
-
-        if position.turn() == player_side {
-            let fp = Fingerprint::from_move(&mv)?;
-            *frequencies.entry(fp).or_insert(0) += 1;
-        }
-
-        position = position.play(&mv)?;
-        let child_fen = canonicalize_current_fen(&position)?;
-        nodes
-            .entry(parent_fen)
-            .or_default()
-            .push((mv.clone(), uci, san_str));
-        nodes.entry(child_fen).or_default();
-    }
-
-    let mut rankings: HashMap<String, Vec<RankedMove>> = HashMap::new();
+    games: &[PgnGame],
+    options: &RankingOptions,
+) -> anyhow::Result<(
+    BTreeMap<String, Vec<RankedMove>>,
+    usize,
+    Option<BTreeMap<String, Completeness>>,
+)> {
+    let mut nodes: HashMap<String, Vec<Edge>> = HashMap::new();
+    nodes
+        .entry(canonicalize_current_fen(&Chess::new())?)
+        .or_default();
+    let frequencies = count_move_frequencies(games, &options.frequency, |played| {
+        record_edge(&mut nodes, played)
+    })
+    .map_err(|err| anyhow!(err))?;
+
+    let mut rankings: BTreeMap<String, Vec<RankedMove>> = BTreeMap::new();
+    let mut completeness = options.with_completeness.then(BTreeMap::new);
     let mut total_nodes = 0usize;
     for (fen, moves) in nodes {
         let board: Chess =
             Fen::from_ascii(fen.as_bytes())?.into_position(CastlingMode::Standard)?;
-        if board.turn() != player_side {
+        if board.turn() != options.frequency.player_side {
             continue;
         }
-        total_nodes += 1;
         let mut ranked: Vec<RankedMove> = Vec::new();
-        for (mv, uci, san) in moves {
-            let fp = Fingerprint::from_move(&mv)?;
+        for Edge { mv, uci, san, nags } in moves {
+            let fp = Fingerprint::from_move(&mv).map_err(|err| anyhow!(err))?;
             let freq = *frequencies.get(&fp).unwrap_or(&0);
+            let key = match options.key {
+                MoveKey::San => san.clone(),
+                MoveKey::Uci => uci.clone(),
+            };
             ranked.push(RankedMove {
+                key,
                 uci,
                 san,
                 frequency: freq,
+                nags,
             });
         }
-        ranked.sort_by(|a, b| b.frequency.cmp(&a.frequency).then(a.san.cmp(&b.san)));
+        let entry = Completeness {
+            legal_move_count: board.legal_moves().len(),
+            covered_count: ranked.len(),
+        };
+        // The default of 1 keeps every position, including leaves and moves a
+        // zero result weight left at frequency 0.
+        if options.min_frequency > 1 {
+            ranked.retain(|mv| mv.frequency >= options.min_frequency);
+            if ranked.is_empty() {
+                continue;
+            }
+        }
+        total_nodes += 1;
+        ranked.sort_by(|a, b| b.frequency.cmp(&a.frequency).then(a.key.cmp(&b.key)));
+        if options.top_n > 0 {
+            ranked.truncate(options.top_n);
+        }
+        if let Some(completeness) = completeness.as_mut() {
+            completeness.insert(fen.clone(), entry);
+        }
         rankings.insert(fen, ranked);
     }
 
-    Ok((rankings, total_nodes))
+    Ok((rankings, total_nodes, completeness))
 }
 
-impl Fingerprint {
-    fn from_move(mv: &Move) -> anyhow::Result<Self> {
-        let role = mv.role();
-        let from_sq = mv
-            .from()
-            .ok_or_else(|| anyhow::anyhow!("Move lacks origin square"))?;
-        let to_sq = mv.to();
-        Ok(Fingerprint {
-            piece: role.char().to_ascii_uppercase(),
-            from: from_sq.to_string(),
-            to: to_sq.to_string(),
-        })
+/// Replay every game only to count moves and the distinct positions where one
+/// of `player_sides` is to move, skipping the fingerprint and ranking work.
+fn count_games(
+    games: &[PgnGame],
+    player_sides: &[Color],
+    max_ply: Option<u32>,
+) -> anyhow::Result<Counts> {
+    let mut positions = HashSet::new();
+    positions.insert(canonicalize_current_fen(&Chess::new())?);
+    let mut total_moves = 0usize;
+    for game in games {
+        count_line(
+            &game.moves,
+            Chess::new(),
+            max_ply,
+            &mut positions,
+            &mut total_moves,
+        )?;
     }
+    let mut player_nodes = 0usize;
+    for fen in positions {
+        let board: Chess =
+            Fen::from_ascii(fen.as_bytes())?.into_position(CastlingMode::Standard)?;
+        if player_sides.contains(&board.turn()) {
+            player_nodes += 1;
+        }
+    }
+    Ok(Counts {
+        games: games.len(),
+        player_nodes,
+        total_moves,
+    })
+}
+
+fn count_line(
+    line: &[PgnMove],
+    mut position: Chess,
+    max_ply: Option<u32>,
+    positions: &mut HashSet<String>,
+    total_moves: &mut usize,
+) -> anyhow::Result<()> {
+    for pgn_move in line {
+        if max_ply.is_some_and(|max_ply| move_ply(&position) > max_ply) {
+            break;
+        }
+        for variation in &pgn_move.variations {
+            count_line(variation, position.clone(), max_ply, positions, total_moves)?;
+        }
+        let mv = pgn_move.san.san.to_move(&position).with_context(|| {
+            format!(
+                "Illegal move {} in PGN",
+                move_location(&position, &pgn_move.san)
+            )
+        })?;
+        position = position.play(&mv)?;
+        positions.insert(canonicalize_current_fen(&position)?);
+        *total_moves += 1;
+    }
+    Ok(())
+}
+
+/// Add `played` to its parent's edges, merging NAGs into an edge already seen.
+fn record_edge(nodes: &mut HashMap<String, Vec<Edge>>, played: PlayedMove<'_>) {
+    let edges = nodes.entry(played.parent_fen).or_default();
+    let edge = match edges.iter().position(|edge| edge.uci == played.uci) {
+        Some(index) => &mut edges[index],
+        None => {
+            edges.push(Edge {
+                mv: played.mv,
+                uci: played.uci,
+                san: played.san,
+                nags: Vec::new(),
+            });
+            edges.last_mut().expect("edge just pushed")
+        }
+    };
+    edge.nags.extend(played.nags);
+    edge.nags.sort_unstable();
+    edge.nags.dedup();
+    nodes.entry(played.child_fen).or_default();
 }
 
 fn canonicalize_current_fen(board: &Chess) -> anyhow::Result<String> {
-    let fen = Fen::from_position(board.clone(), EnPassantMode::Legal).to_string();
-    canonicalize_fen_str(&fen).map_err(|err| anyhow!(err))
+    canonical_fen(board).map_err(|err| anyhow!(err))
 }
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs;
+
+use anyhow::{Context, anyhow};
+use clap::{Parser, ValueEnum};
+use shakmaty::{Chess, Color, Move, Position};
+
+use _core::frequency::{Fingerprint, FrequencyOptions, count_move_frequencies};
+use _core::pgn::{PgnGame, PgnMove, format_game, parse_games};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "prune",
+    about = "Drop rarely played player moves, and everything after them, from a repertoire PGN"
+)]
+struct Args {
+    /// PGN file containing the repertoire (use '-' for stdin)
+    pgn_file: String,
+
+    /// Player side whose moves are pruned; opponent moves are always kept
+    #[arg(long, value_enum, default_value_t = Side::White)]
+    side: Side,
+
+    /// Keep player moves played at least this many times across the repertoire
+    #[arg(long, default_value_t = 2)]
+    min_frequency: u32,
+
+    /// Destination file (use '-' for stdout)
+    #[arg(long, default_value = "-")]
+    output: String,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Side {
+    White,
+    Black,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let pgn_text = if args.pgn_file == "-" {
+        std::io::read_to_string(std::io::stdin()).context("Failed to read PGN from stdin")?
+    } else {
+        fs::read_to_string(&args.pgn_file)
+            .with_context(|| format!("Failed to read PGN file: {}", args.pgn_file))?
+    };
+    let games = parse_games(&pgn_text).map_err(|err| anyhow!(err))?;
+
+    let player_side = match args.side {
+        Side::White => Color::White,
+        Side::Black => Color::Black,
+    };
+    let options = FrequencyOptions {
+        player_side,
+        collapse_repetitions: false,
+        result_weights: None,
+        max_ply: None,
+    };
+    let pruner = Pruner {
+        frequencies: count_move_frequencies(&games, &options, |_| {})
+            .map_err(|err| anyhow!(err))?,
+        player_side,
+        min_frequency: args.min_frequency,
+    };
+
+    let mut pruned = Vec::with_capacity(games.len());
+    for game in &games {
+        let moves = pruner.prune_branch(vec![&game.moves], &Chess::new(), true)?;
+        pruned.push(format_game(&PgnGame {
+            headers: game.headers.clone(),
+            result: game.result.clone(),
+            moves,
+        }));
+    }
+    write_output(&args.output, &pruned.join("\n"))
+}
+
+struct Pruner {
+    frequencies: HashMap<Fingerprint, u32>,
+    player_side: Color,
+    min_frequency: u32,
+}
+
+impl Pruner {
+    /// Prune the lines that start at `position`: each of `lines` plus every
+    /// variation branching off its first move. A dropped move takes the rest of
+    /// its line with it. With `keep_one`, the most frequent move survives even
+    /// when all fall below the threshold, so a game is never pruned to nothing.
+    ///
+    /// Returns the first surviving line with the other survivors attached as
+    /// variations of its first move.
+    fn prune_branch(
+        &self,
+        lines: Vec<&[PgnMove]>,
+        position: &Chess,
+        keep_one: bool,
+    ) -> anyhow::Result<Vec<PgnMove>> {
+        let mut alternatives = Vec::new();
+        for line in lines {
+            collect_alternatives(line, &mut alternatives);
+        }
+
+        let mut scored = Vec::with_capacity(alternatives.len());
+        for line in alternatives {
+            let mv = line[0].san.san.to_move(position)?;
+            let frequency = self.frequency(position, &mv)?;
+            scored.push((line, mv, frequency));
+        }
+        let mut kept: Vec<usize> = (0..scored.len())
+            .filter(|&index| {
+                scored[index]
+                    .2
+                    .is_none_or(|freq| freq >= self.min_frequency)
+            })
+            .collect();
+        if kept.is_empty()
+            && keep_one
+            && let Some(best) =
+                (0..scored.len()).max_by_key(|&index| (scored[index].2, std::cmp::Reverse(index)))
+        {
+            kept.push(best);
+        }
+
+        let mut survivors = Vec::with_capacity(kept.len());
+        for index in kept {
+            let (line, mv, _) = &scored[index];
+            let mut child = position.clone();
+            child.play_unchecked(mv);
+            let mut survivor = vec![PgnMove {
+                san: line[0].san.clone(),
+                nags: line[0].nags.clone(),
+                comments: line[0].comments.clone(),
+                variations: Vec::new(),
+            }];
+            if line.len() > 1 {
+                survivor.extend(self.prune_branch(vec![&line[1..]], &child, false)?);
+            }
+            survivors.push(survivor);
+        }
+        let mut survivors = survivors.into_iter();
+        let Some(mut primary) = survivors.next() else {
+            return Ok(Vec::new());
+        };
+        primary[0].variations = survivors.collect();
+        Ok(primary)
+    }
+
+    /// How often a player move is played; `None` for opponent moves, which are
+    /// never pruned.
+    fn frequency(&self, position: &Chess, mv: &Move) -> anyhow::Result<Option<u32>> {
+        if position.turn() != self.player_side {
+            return Ok(None);
+        }
+        let fp = Fingerprint::from_move(mv).map_err(|err| anyhow!(err))?;
+        Ok(Some(self.frequencies.get(&fp).copied().unwrap_or(0)))
+    }
+}
+
+/// `line` and, recursively, every variation replacing its first move.
+fn collect_alternatives<'a>(line: &'a [PgnMove], out: &mut Vec<&'a [PgnMove]>) {
+    let Some(first) = line.first() else {
+        return;
+    };
+    out.push(line);
+    for variation in &first.variations {
+        collect_alternatives(variation, out);
+    }
+}
+
+fn write_output(output: &str, body: &str) -> anyhow::Result<()> {
+    if output == "-" {
+        print!("{body}");
+    } else {
+        fs::write(output, body)?;
+        println!("Wrote pruned PGN to {output}");
+    }
+    Ok(())
+}
@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, anyhow};
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Position};
+
+use _core::frequency::canonical_fen;
+use _core::pgn::{PgnGame, PgnMove, format_game_from, parse_games};
+use _core::{SplitChildInput, SplitEventPayload, SplitNodeInput, split_nodes};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "split",
+    about = "Split a repertoire into chapters that each hold a bounded number of moves"
+)]
+struct Args {
+    /// Repertoire PGN, or a JSON list of `{fen, children: [{uci, fen}]}` nodes
+    /// when the name ends in `.json` (use '-' for a PGN on stdin)
+    input: String,
+
+    /// Largest number of moves a chapter may hold before it is split further
+    #[arg(long, default_value_t = 1000)]
+    max_moves: u64,
+
+    /// Smallest subtree that is split off into its own chapter
+    #[arg(long, default_value_t = 0)]
+    min_moves: u64,
+
+    /// FEN of the position to split from (defaults to the standard start)
+    #[arg(long)]
+    root_fen: Option<String>,
+
+    /// Write one PGN file per chapter, or a single JSON manifest
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pgn)]
+    format: OutputFormat,
+
+    /// Directory receiving `chapter_NNN.pgn` files (pgn format)
+    #[arg(long, default_value = ".")]
+    output_dir: String,
+
+    /// Destination of the manifest (json format; use '-' for stdout)
+    #[arg(long, default_value = "-")]
+    output: String,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum OutputFormat {
+    Pgn,
+    Json,
+}
+
+#[derive(Serialize)]
+struct ChapterEntry {
+    fen: String,
+    prefix: Vec<String>,
+    move_count: u64,
+    pgn: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let nodes = if args.input.ends_with(".json") {
+        let text = fs::read_to_string(&args.input)
+            .with_context(|| format!("Failed to read node list: {}", args.input))?;
+        let nodes = serde_json::from_str::<Vec<SplitNodeInput>>(&text)
+            .with_context(|| format!("Invalid node list: {}", args.input))?;
+        canonicalize_nodes(nodes)?
+    } else {
+        let pgn_text = if args.input == "-" {
+            std::io::read_to_string(std::io::stdin()).context("Failed to read PGN from stdin")?
+        } else {
+            fs::read_to_string(&args.input)
+                .with_context(|| format!("Failed to read PGN file: {}", args.input))?
+        };
+        nodes_from_pgn(&pgn_text)?
+    };
+
+    let root_fen = match &args.root_fen {
+        Some(fen) => canonicalize_fen(fen, "root FEN")?,
+        None => canonical_fen(&Chess::new()).map_err(|err| anyhow!(err))?,
+    };
+    let tree = Tree::new(&nodes);
+    let events = split_nodes(
+        &root_fen,
+        nodes.clone(),
+        args.max_moves,
+        args.min_moves,
+        None,
+    )
+    .map_err(|err| anyhow!(err))?;
+    let chapters = tree.chapters(&root_fen, &events)?;
+
+    match args.format {
+        OutputFormat::Pgn => {
+            let dir = Path::new(&args.output_dir);
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", args.output_dir))?;
+            for (index, chapter) in chapters.iter().enumerate() {
+                let path = dir.join(format!("chapter_{:03}.pgn", index + 1));
+                fs::write(&path, &chapter.pgn)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+            println!("Wrote {} chapters to {}", chapters.len(), args.output_dir);
+        }
+        OutputFormat::Json => {
+            let manifest = serde_json::to_string_pretty(&chapters)?;
+            if args.output == "-" {
+                println!("{manifest}");
+            } else {
+                fs::write(&args.output, manifest)?;
+                println!("Wrote {} chapters to {}", chapters.len(), args.output);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Every position reached in the PGN, variations included, with the distinct
+/// moves played from it; a mainline move comes before its variations.
+fn nodes_from_pgn(pgn_text: &str) -> anyhow::Result<Vec<SplitNodeInput>> {
+    let games = parse_games(pgn_text).map_err(|err| anyhow!(err))?;
+    let mut nodes: BTreeMap<String, SplitNodeInput> = BTreeMap::new();
+    for game in &games {
+        add_line(&mut nodes, &game.moves, Chess::new())?;
+    }
+    Ok(nodes.into_values().collect())
+}
+
+fn add_line(
+    nodes: &mut BTreeMap<String, SplitNodeInput>,
+    line: &[PgnMove],
+    mut position: Chess,
+) -> anyhow::Result<()> {
+    for pgn_move in line {
+        let mv = pgn_move
+            .san
+            .san
+            .to_move(&position)
+            .with_context(|| format!("Illegal move {} in PGN", pgn_move.san))?;
+        let parent_fen = canonical_fen(&position).map_err(|err| anyhow!(err))?;
+        let mut child = position.clone();
+        child.play_unchecked(&mv);
+        let uci = UciMove::from_move(&mv, CastlingMode::Standard).to_string();
+        let node = nodes
+            .entry(parent_fen.clone())
+            .or_insert_with(|| SplitNodeInput {
+                fen: parent_fen,
+                children: Vec::new(),
+            });
+        if !node.children.iter().any(|existing| existing.uci == uci) {
+            node.children.push(SplitChildInput {
+                uci,
+                fen: canonical_fen(&child).map_err(|err| anyhow!(err))?,
+            });
+        }
+        for variation in &pgn_move.variations {
+            add_line(nodes, variation, position.clone())?;
+        }
+        position = child;
+    }
+    Ok(())
+}
+
+/// Rewrite every FEN in a node list to its canonical form, so lists written by
+/// other tools (with en passant squares or real move counters) match the
+/// positions the splitter looks up. Nodes that collapse onto one FEN are merged.
+fn canonicalize_nodes(nodes: Vec<SplitNodeInput>) -> anyhow::Result<Vec<SplitNodeInput>> {
+    let mut merged: BTreeMap<String, SplitNodeInput> = BTreeMap::new();
+    for node in nodes {
+        let fen = canonicalize_fen(&node.fen, "FEN in node list")?;
+        let entry = merged.entry(fen.clone()).or_insert_with(|| SplitNodeInput {
+            fen,
+            children: Vec::new(),
+        });
+        for child in node.children {
+            if !entry
+                .children
+                .iter()
+                .any(|existing| existing.uci == child.uci)
+            {
+                entry.children.push(SplitChildInput {
+                    fen: canonicalize_fen(&child.fen, "FEN in node list")?,
+                    uci: child.uci,
+                });
+            }
+        }
+    }
+    Ok(merged.into_values().collect())
+}
+
+fn canonicalize_fen(fen: &str, what: &str) -> anyhow::Result<String> {
+    let position: Chess = Fen::from_str(fen)
+        .with_context(|| format!("Invalid {what}: {fen}"))?
+        .into_position(CastlingMode::Standard)
+        .with_context(|| format!("Illegal position for {what}: {fen}"))?;
+    canonical_fen(&position).map_err(|err| anyhow!(err))
+}
+
+fn position_at(fen: &str) -> anyhow::Result<Chess> {
+    Fen::from_str(fen)
+        .with_context(|| format!("Invalid FEN in node list: {fen}"))?
+        .into_position(CastlingMode::Standard)
+        .with_context(|| format!("Illegal position in node list: {fen}"))
+}
+
+struct Tree<'a> {
+    nodes: BTreeMap<&'a str, &'a SplitNodeInput>,
+}
+
+impl<'a> Tree<'a> {
+    fn new(nodes: &'a [SplitNodeInput]) -> Self {
+        Self {
+            nodes: nodes.iter().map(|node| (node.fen.as_str(), node)).collect(),
+        }
+    }
+
+    /// One chapter per event: the moves from the root to the event's position,
+    /// followed by its subtree minus the branches split off into other
+    /// chapters.
+    fn chapters(
+        &self,
+        root_fen: &str,
+        events: &[SplitEventPayload],
+    ) -> anyhow::Result<Vec<ChapterEntry>> {
+        let mut split_paths: HashSet<&[String]> = HashSet::new();
+        for event in events {
+            for len in 1..=event.prefix.len() {
+                split_paths.insert(&event.prefix[..len]);
+            }
+        }
+        let root = position_at(root_fen)?;
+
+        let mut chapters = Vec::with_capacity(events.len());
+        for event in events {
+            let mut position = root.clone();
+            let mut moves = Vec::with_capacity(event.prefix.len());
+            let mut path_fens: HashSet<String> = HashSet::from([root_fen.to_string()]);
+            for uci in &event.prefix {
+                let (san, child) = play_uci(&position, uci)?;
+                moves.push(PgnMove {
+                    san,
+                    nags: Vec::new(),
+                    comments: Vec::new(),
+                    variations: Vec::new(),
+                });
+                position = child;
+                path_fens.insert(canonical_fen(&position).map_err(|err| anyhow!(err))?);
+            }
+            let mut path = event.prefix.clone();
+            moves.extend(self.subtree(
+                &event.fen,
+                &position,
+                &mut path,
+                &mut path_fens,
+                &split_paths,
+            )?);
+            chapters.push(ChapterEntry {
+                fen: event.fen.clone(),
+                prefix: event.prefix.clone(),
+                move_count: event.move_count,
+                pgn: format_game_from(
+                    &PgnGame {
+                        headers: Vec::new(),
+                        result: None,
+                        moves,
+                    },
+                    &root,
+                ),
+            });
+        }
+        Ok(chapters)
+    }
+
+    /// The moves below `fen` as a line whose first move carries the other
+    /// branches as variations. Children whose path starts another chapter, or
+    /// that return to a position already on the path, are left out.
+    fn subtree(
+        &self,
+        fen: &str,
+        position: &Chess,
+        path: &mut Vec<String>,
+        path_fens: &mut HashSet<String>,
+        split_paths: &HashSet<&[String]>,
+    ) -> anyhow::Result<Vec<PgnMove>> {
+        let Some(node) = self.nodes.get(fen) else {
+            return Ok(Vec::new());
+        };
+        let mut branches = Vec::new();
+        for child in &node.children {
+            path.push(child.uci.clone());
+            let skip = split_paths.contains(path.as_slice()) || path_fens.contains(&child.fen);
+            if !skip {
+                let (san, next) = play_uci(position, &child.uci)?;
+                path_fens.insert(child.fen.clone());
+                let mut line = vec![PgnMove {
+                    san,
+                    nags: Vec::new(),
+                    comments: Vec::new(),
+                    variations: Vec::new(),
+                }];
+                line.extend(self.subtree(&child.fen, &next, path, path_fens, split_paths)?);
+                path_fens.remove(&child.fen);
+                branches.push(line);
+            }
+            path.pop();
+        }
+        let mut branches = branches.into_iter();
+        let Some(mut primary) = branches.next() else {
+            return Ok(Vec::new());
+        };
+        primary[0].variations = branches.collect();
+        Ok(primary)
+    }
+}
+
+fn play_uci(position: &Chess, uci: &str) -> anyhow::Result<(SanPlus, Chess)> {
+    let mv = UciMove::from_str(uci)
+        .with_context(|| format!("Invalid UCI move: {uci}"))?
+        .to_move(position)
+        .with_context(|| format!("Illegal move {uci} in the node list"))?;
+    let san = SanPlus::from_move(position.clone(), &mv);
+    let mut child = position.clone();
+    child.play_unchecked(&mv);
+    Ok((san, child))
+}
@@ -0,0 +1,134 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Progress of a long analysis run: the results gathered so far, keyed by
+/// canonical FEN, and a cursor into the caller's work list marking where to
+/// resume.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint<T> {
+    pub cursor: usize,
+    pub evaluated: BTreeMap<String, T>,
+}
+
+impl<T> Default for Checkpoint<T> {
+    fn default() -> Self {
+        Self {
+            cursor: 0,
+            evaluated: BTreeMap::new(),
+        }
+    }
+}
+
+/// Write `checkpoint` as JSON, replacing any earlier one atomically.
+pub fn save_checkpoint<T: Serialize>(path: &Path, checkpoint: &Checkpoint<T>) -> io::Result<()> {
+    let text = serde_json::to_vec(checkpoint)?;
+    write_atomic(path, &text)
+}
+
+/// Read the checkpoint at `path`. A missing file, or one that is truncated or
+/// otherwise unreadable as a checkpoint, gives `None` so the run starts over;
+/// only I/O failures such as a permission error are reported.
+pub fn load_checkpoint<T: DeserializeOwned>(path: &Path) -> io::Result<Option<Checkpoint<T>>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    Ok(serde_json::from_slice(&bytes).ok())
+}
+
+/// Write `contents` to a temporary file next to `path`, flush it to disk and
+/// rename it over `path`, so a crash mid-write leaves the previous file intact.
+/// The temporary name appends `.tmp` to the whole file name, so it never
+/// collides with a sibling that differs only in extension.
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn sample() -> Checkpoint<Vec<i32>> {
+        let mut checkpoint = Checkpoint {
+            cursor: 7,
+            ..Checkpoint::default()
+        };
+        checkpoint.evaluated.insert(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".to_string(),
+            vec![35, 20],
+        );
+        checkpoint.evaluated.insert(
+            "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1".to_string(),
+            vec![30],
+        );
+        checkpoint
+    }
+
+    #[test]
+    fn checkpoint_round_trips_and_leaves_no_temp_file() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("annotate.checkpoint.json");
+        assert_eq!(load_checkpoint::<Vec<i32>>(&path).expect("load"), None);
+
+        save_checkpoint(&path, &sample()).expect("save");
+        let mut updated = sample();
+        updated.cursor = 9;
+        save_checkpoint(&path, &updated).expect("save again");
+
+        assert_eq!(load_checkpoint(&path).expect("load"), Some(updated));
+        let files: Vec<_> = fs::read_dir(dir.path()).expect("read dir").collect();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn checkpoints_differing_only_in_extension_stay_separate() {
+        let dir = tempdir().expect("tempdir");
+        let json = dir.path().join("run.json");
+        let ckpt = dir.path().join("run.ckpt");
+        let tmp = dir.path().join("run.tmp");
+        let mut updated = sample();
+        updated.cursor = 9;
+
+        save_checkpoint(&tmp, &sample()).expect("save tmp");
+        save_checkpoint(&json, &sample()).expect("save json");
+        save_checkpoint(&ckpt, &updated).expect("save ckpt");
+
+        assert_eq!(load_checkpoint(&tmp).expect("load tmp"), Some(sample()));
+        assert_eq!(load_checkpoint(&json).expect("load json"), Some(sample()));
+        assert_eq!(load_checkpoint(&ckpt).expect("load ckpt"), Some(updated));
+        let files: Vec<_> = fs::read_dir(dir.path()).expect("read dir").collect();
+        assert_eq!(files.len(), 3);
+    }
+
+    #[test]
+    fn truncated_or_corrupt_checkpoint_is_ignored() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("annotate.checkpoint.json");
+        save_checkpoint(&path, &sample()).expect("save");
+        let full = fs::read(&path).expect("read checkpoint");
+
+        fs::write(&path, &full[..full.len() / 2]).expect("truncate");
+        assert_eq!(load_checkpoint::<Vec<i32>>(&path).expect("load"), None);
+
+        fs::write(&path, [0xff, 0xfe, 0x00, 0x7b]).expect("corrupt");
+        assert_eq!(load_checkpoint::<Vec<i32>>(&path).expect("load"), None);
+
+        fs::write(&path, br#"{"cursor": "three", "evaluated": {}}"#).expect("wrong shape");
+        assert_eq!(load_checkpoint::<Vec<i32>>(&path).expect("load"), None);
+    }
+}
@@ -0,0 +1,180 @@
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Move, Position};
+
+use std::collections::{HashMap, HashSet};
+
+use crate::canonicalize_fen_str;
+use crate::pgn::{PgnGame, PgnMove};
+
+/// A move identified by piece, squares and promotion only, so the same move
+/// played from different positions is counted together.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Fingerprint {
+    piece: char,
+    from: String,
+    to: String,
+    promotion: Option<char>,
+}
+
+impl Fingerprint {
+    pub fn from_move(mv: &Move) -> Result<Self, String> {
+        let from_sq = mv
+            .from()
+            .ok_or_else(|| "Move lacks origin square".to_string())?;
+        Ok(Fingerprint {
+            piece: mv.role().char().to_ascii_uppercase(),
+            from: from_sq.to_string(),
+            to: mv.to().to_string(),
+            promotion: mv.promotion().map(|role| role.char().to_ascii_uppercase()),
+        })
+    }
+}
+
+/// How much a game's player moves count, by the game's result for the player.
+#[derive(Copy, Clone, Debug)]
+pub struct ResultWeights {
+    pub win: u32,
+    pub draw: u32,
+    pub loss: u32,
+}
+
+impl ResultWeights {
+    /// Weight of a game for `player`; unfinished or unknown results count as
+    /// draws.
+    pub fn for_result(self, result: Option<&str>, player: Color) -> u32 {
+        match (result, player) {
+            (Some("1-0"), Color::White) | (Some("0-1"), Color::Black) => self.win,
+            (Some("0-1"), Color::White) | (Some("1-0"), Color::Black) => self.loss,
+            _ => self.draw,
+        }
+    }
+}
+
+pub struct FrequencyOptions {
+    pub player_side: Color,
+    /// Count each move at most once per game when a position repeats.
+    pub collapse_repetitions: bool,
+    /// Weight moves by game result instead of counting each one as 1.
+    pub result_weights: Option<ResultWeights>,
+    /// Ignore moves, and the positions they reach, past this ply.
+    pub max_ply: Option<u32>,
+}
+
+/// A replayed move with the canonical FENs on either side of it.
+pub struct PlayedMove<'a> {
+    pub parent_fen: String,
+    pub child_fen: String,
+    pub mv: Move,
+    pub uci: String,
+    pub san: String,
+    pub nags: &'a [u8],
+}
+
+/// Replay every game, variations included, and count how often each player
+/// move is played. Every move of either side is also passed to `visit`.
+pub fn count_move_frequencies<F>(
+    games: &[PgnGame],
+    options: &FrequencyOptions,
+    mut visit: F,
+) -> Result<HashMap<Fingerprint, u32>, String>
+where
+    F: FnMut(PlayedMove<'_>),
+{
+    let mut counter = Counter {
+        options,
+        frequencies: HashMap::new(),
+        seen_in_game: HashSet::new(),
+        weight: 1,
+    };
+    for game in games {
+        counter.seen_in_game.clear();
+        counter.weight = options.result_weights.map_or(1, |weights| {
+            weights.for_result(game.result.as_deref(), options.player_side)
+        });
+        counter.count_line(&game.moves, Chess::new(), &mut visit)?;
+    }
+    Ok(counter.frequencies)
+}
+
+/// `san` with its ply, move number and the FEN it is played from, for errors
+/// that must point at one move in a large PGN.
+pub fn move_location(position: &Chess, san: &SanPlus) -> String {
+    let ply = move_ply(position);
+    let number = match position.turn() {
+        Color::White => format!("{}.", position.fullmoves()),
+        Color::Black => format!("{}...", position.fullmoves()),
+    };
+    let fen = Fen::from_position(position.clone(), EnPassantMode::Legal);
+    format!("{number} {san} at ply {ply} (position {fen})")
+}
+
+/// Ply of the next move played from `position`, counting the first move of the
+/// game as ply 1.
+pub fn move_ply(position: &Chess) -> u32 {
+    2 * (position.fullmoves().get() - 1) + u32::from(position.turn() == Color::Black) + 1
+}
+
+/// Canonical FEN of `position`, with move counters reset.
+pub fn canonical_fen(position: &Chess) -> Result<String, String> {
+    canonicalize_fen_str(&Fen::from_position(position.clone(), EnPassantMode::Legal).to_string())
+}
+
+struct Counter<'o> {
+    options: &'o FrequencyOptions,
+    frequencies: HashMap<Fingerprint, u32>,
+    seen_in_game: HashSet<(String, Fingerprint)>,
+    /// Contribution of each counted move in the current game.
+    weight: u32,
+}
+
+impl Counter<'_> {
+    fn count_line<F>(
+        &mut self,
+        line: &[PgnMove],
+        mut position: Chess,
+        visit: &mut F,
+    ) -> Result<(), String>
+    where
+        F: FnMut(PlayedMove<'_>),
+    {
+        for pgn_move in line {
+            if self
+                .options
+                .max_ply
+                .is_some_and(|max_ply| move_ply(&position) > max_ply)
+            {
+                break;
+            }
+            for variation in &pgn_move.variations {
+                self.count_line(variation, position.clone(), visit)?;
+            }
+            let mv = pgn_move.san.san.to_move(&position).map_err(|err| {
+                format!(
+                    "Illegal move {} in PGN: {err}",
+                    move_location(&position, &pgn_move.san)
+                )
+            })?;
+            let parent_fen = canonical_fen(&position)?;
+            let fp = Fingerprint::from_move(&mv)?;
+
+            let repeated = self.options.collapse_repetitions
+                && !self.seen_in_game.insert((parent_fen.clone(), fp.clone()));
+            if position.turn() == self.options.player_side && !repeated {
+                *self.frequencies.entry(fp).or_insert(0) += self.weight;
+            }
+
+            position.play_unchecked(&mv);
+            visit(PlayedMove {
+                parent_fen,
+                child_fen: canonical_fen(&position)?,
+                uci: UciMove::from_move(&mv, CastlingMode::Standard).to_string(),
+                san: pgn_move.san.to_string(),
+                mv,
+                nags: &pgn_move.nags,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1123 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Position};
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::{
+    Fingerprint, SplitChildInput, SplitNodeInput, canonicalize_fen_str, parse_position,
+    parse_uci_move,
+};
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Fraction of opponent-turn positions that have at least one prepared reply.
+///
+/// Child positions that never appear as a node are counted as uncovered, and a
+/// repertoire without any opponent-turn positions reports `0.0`.
+#[pyfunction]
+pub fn coverage(side_is_white: bool, nodes: Vec<SplitNodeInput>) -> PyResult<f64> {
+    let player = if side_is_white {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let mut has_children: HashMap<String, bool> = HashMap::new();
+    for node in &nodes {
+        let prepared = has_children.entry(node.fen.clone()).or_insert(false);
+        *prepared |= !node.children.is_empty();
+        for child in &node.children {
+            has_children.entry(child.fen.clone()).or_insert(false);
+        }
+    }
+
+    let mut opponent_nodes = 0u64;
+    let mut covered = 0u64;
+    for (fen, prepared) in &has_children {
+        if parse_position(fen)?.turn() == player {
+            continue;
+        }
+        opponent_nodes += 1;
+        if *prepared {
+            covered += 1;
+        }
+    }
+    if opponent_nodes == 0 {
+        return Ok(0.0);
+    }
+    Ok(covered as f64 / opponent_nodes as f64)
+}
+
+/// Group nodes by FEN; the same position may be listed more than once.
+fn nodes_by_fen(nodes: &[SplitNodeInput]) -> HashMap<&str, Vec<&SplitNodeInput>> {
+    let mut by_fen: HashMap<&str, Vec<&SplitNodeInput>> = HashMap::new();
+    for node in nodes {
+        by_fen.entry(node.fen.as_str()).or_default().push(node);
+    }
+    by_fen
+}
+
+/// FENs reachable from `root_fen` through edges whose move is legal and actually
+/// leads to the recorded child position.
+fn reachable_fens(root_fen: &str, nodes: &[SplitNodeInput]) -> HashSet<String> {
+    let by_fen = nodes_by_fen(nodes);
+    let mut reachable: HashSet<String> = HashSet::new();
+    let mut stack: Vec<&str> = vec![root_fen];
+    while let Some(fen) = stack.pop() {
+        if !reachable.insert(fen.to_string()) {
+            continue;
+        }
+        let (Some(entries), Ok(position)) = (by_fen.get(fen), parse_position(fen)) else {
+            continue;
+        };
+        for child in entries.iter().flat_map(|node| &node.children) {
+            if legal_edge(&position, fen, &child.uci, &child.fen) {
+                stack.push(child.fen.as_str());
+            }
+        }
+    }
+    reachable
+}
+
+fn legal_edge(position: &Chess, fen: &str, uci: &str, child_fen: &str) -> bool {
+    let Ok(next) = play_uci(position, fen, uci) else {
+        return false;
+    };
+    let played = Fen::from_position(next, EnPassantMode::Legal).to_string();
+    match (
+        canonicalize_fen_str(&played),
+        canonicalize_fen_str(child_fen),
+    ) {
+        (Ok(played), Ok(recorded)) => played == recorded,
+        _ => false,
+    }
+}
+
+/// Node FENs that cannot be reached from `root_fen` through legal edges, e.g.
+/// leftovers from an earlier version of the repertoire.
+#[pyfunction]
+pub fn orphan_positions(root_fen: String, nodes: Vec<SplitNodeInput>) -> PyResult<Vec<String>> {
+    for node in &nodes {
+        parse_position(&node.fen)?;
+    }
+    let reachable = reachable_fens(&root_fen, &nodes);
+    let mut orphans: Vec<String> = nodes
+        .iter()
+        .map(|node| node.fen.clone())
+        .filter(|fen| !reachable.contains(fen))
+        .collect();
+    orphans.sort();
+    orphans.dedup();
+    Ok(orphans)
+}
+
+/// Every `(parent_fen, uci)` edge whose child canonicalizes to `target_fen`,
+/// i.e. all the move orders that transpose into the target.
+#[pyfunction]
+pub fn transposing_moves(
+    nodes: Vec<SplitNodeInput>,
+    target_fen: String,
+) -> PyResult<Vec<(String, String)>> {
+    let target = canonical(&target_fen)?;
+    let mut edges: Vec<(String, String)> = Vec::new();
+    for node in &nodes {
+        for child in &node.children {
+            if canonical(&child.fen)? == target {
+                edges.push((node.fen.clone(), child.uci.clone()));
+            }
+        }
+    }
+    edges.sort();
+    edges.dedup();
+    Ok(edges)
+}
+
+/// Indices of `fens` grouped by canonical FEN, so each group is one position
+/// reached through different move orders. Groups are ordered by their first
+/// member.
+#[pyfunction]
+pub fn group_transpositions(fens: Vec<String>) -> PyResult<Vec<Vec<usize>>> {
+    let mut group_of: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (index, fen) in fens.iter().enumerate() {
+        let group = *group_of.entry(canonical(fen)?).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(index);
+    }
+    Ok(groups)
+}
+
+/// Union several node graphs into one, keyed by canonical parent FEN.
+///
+/// Edges are deduplicated by `(uci, child_fen)`; the same move leading to two
+/// different children from one parent is reported as an error.
+#[pyfunction]
+pub fn merge_node_graphs(graphs: Vec<Vec<SplitNodeInput>>) -> PyResult<Vec<SplitNodeInput>> {
+    let mut merged: BTreeMap<String, SplitNodeInput> = BTreeMap::new();
+    for node in graphs.into_iter().flatten() {
+        let fen = canonical(&node.fen)?;
+        let entry = merged.entry(fen.clone()).or_insert_with(|| SplitNodeInput {
+            fen: fen.clone(),
+            children: Vec::new(),
+        });
+        for child in node.children {
+            let child_fen = canonical(&child.fen)?;
+            match entry.children.iter().find(|known| known.uci == child.uci) {
+                Some(known) if known.fen == child_fen => {}
+                Some(known) => {
+                    return Err(PyValueError::new_err(format!(
+                        "Conflicting children for move {} from '{fen}': '{}' and '{child_fen}'",
+                        child.uci, known.fen
+                    )));
+                }
+                None => entry.children.push(SplitChildInput {
+                    uci: child.uci,
+                    fen: child_fen,
+                }),
+            }
+        }
+    }
+    Ok(merged.into_values().collect())
+}
+
+fn canonical(fen: &str) -> PyResult<String> {
+    canonicalize_fen_str(fen).map_err(|err| PyValueError::new_err(format!("{err} (fen='{fen}')")))
+}
+
+/// The repertoire's main line from `root_fen` as UCI moves.
+///
+/// At each position the child whose move fingerprint is most frequent among that
+/// side's moves is followed (player and opponent moves are counted separately),
+/// with ties broken by SAN, until a leaf or an already visited position.
+#[pyfunction]
+pub fn main_line(
+    root_fen: String,
+    nodes: Vec<SplitNodeInput>,
+    side_is_white: bool,
+) -> PyResult<Vec<String>> {
+    let player = if side_is_white {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let mut player_counts: HashMap<Fingerprint, u32> = HashMap::new();
+    let mut opponent_counts: HashMap<Fingerprint, u32> = HashMap::new();
+    for node in &nodes {
+        let position = parse_position(&node.fen)?;
+        let counts = if position.turn() == player {
+            &mut player_counts
+        } else {
+            &mut opponent_counts
+        };
+        for child in &node.children {
+            let mv = parse_uci_move(&position, &node.fen, &child.uci)?;
+            *counts.entry(Fingerprint::from_move(&mv)?).or_insert(0) += 1;
+        }
+    }
+
+    let by_fen = nodes_by_fen(&nodes);
+    let mut line: Vec<String> = Vec::new();
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut fen = root_fen.as_str();
+    while visited.insert(fen) {
+        let Some(entries) = by_fen.get(fen) else {
+            break;
+        };
+        let position = parse_position(fen)?;
+        let counts = if position.turn() == player {
+            &player_counts
+        } else {
+            &opponent_counts
+        };
+        let mut best: Option<(u32, String, &SplitChildInput)> = None;
+        for child in entries.iter().flat_map(|node| &node.children) {
+            let mv = parse_uci_move(&position, fen, &child.uci)?;
+            let frequency = counts
+                .get(&Fingerprint::from_move(&mv)?)
+                .copied()
+                .unwrap_or(0);
+            let san = SanPlus::from_move(position.clone(), &mv).to_string();
+            let better = best.as_ref().is_none_or(|(best_frequency, best_san, _)| {
+                frequency > *best_frequency || (frequency == *best_frequency && san < *best_san)
+            });
+            if better {
+                best = Some((frequency, san, child));
+            }
+        }
+        let Some((_, _, child)) = best else {
+            break;
+        };
+        line.push(child.uci.clone());
+        fen = child.fen.as_str();
+    }
+    Ok(line)
+}
+
+/// Player-turn FENs with at least one prepared move, sorted and deduplicated.
+fn decision_points(side_is_white: bool, nodes: &[SplitNodeInput]) -> PyResult<Vec<String>> {
+    let player = if side_is_white {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let mut points: Vec<String> = Vec::new();
+    for node in nodes {
+        if node.children.is_empty() || parse_position(&node.fen)?.turn() != player {
+            continue;
+        }
+        points.push(node.fen.clone());
+    }
+    points.sort();
+    points.dedup();
+    Ok(points)
+}
+
+/// Child FENs prepared through a single move order although another one exists.
+///
+/// A child reached only by `A -x-> B -y-> P -m-> child` is flagged when playing
+/// `m`, `y`, `x` from `A` is legal and leads to the same canonical position, i.e.
+/// the two moves by the same side can be swapped but only one order is prepared.
+#[pyfunction]
+pub fn order_sensitive_nodes(nodes: Vec<SplitNodeInput>) -> PyResult<Vec<String>> {
+    // Canonical child -> distinct (canonical parent, uci) edges leading into it.
+    let mut incoming: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    let mut recorded: HashMap<String, String> = HashMap::new();
+    for node in &nodes {
+        let parent = canonical(&node.fen)?;
+        for child in &node.children {
+            let key = canonical(&child.fen)?;
+            recorded
+                .entry(key.clone())
+                .or_insert_with(|| child.fen.clone());
+            let edges = incoming.entry(key).or_default();
+            let edge = (parent.clone(), child.uci.clone());
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+    }
+
+    let mut flagged: Vec<String> = Vec::new();
+    for (child, edges) in &incoming {
+        let [(parent, last)] = edges.as_slice() else {
+            continue;
+        };
+        let swappable = incoming
+            .get(parent)
+            .into_iter()
+            .flatten()
+            .any(|(before, middle)| {
+                incoming
+                    .get(before)
+                    .into_iter()
+                    .flatten()
+                    .any(|(origin, first)| {
+                        first != last && reaches(origin, &[last, middle, first], child)
+                    })
+            });
+        if swappable {
+            flagged.push(recorded[child].clone());
+        }
+    }
+    flagged.sort();
+    flagged.dedup();
+    Ok(flagged)
+}
+
+/// Whether playing `moves` from `fen` is legal and ends on the canonical `target`.
+fn reaches(fen: &str, moves: &[&String], target: &str) -> bool {
+    let Ok(mut position) = parse_position(fen) else {
+        return false;
+    };
+    for uci in moves {
+        match play_uci(&position, fen, uci) {
+            Ok(next) => position = next,
+            Err(_) => return false,
+        }
+    }
+    let played = Fen::from_position(position, EnPassantMode::Legal).to_string();
+    canonicalize_fen_str(&played).is_ok_and(|played| played == target)
+}
+
+/// Number of player-turn positions with a prepared move, counting positions that
+/// share a canonical FEN (transpositions) once.
+#[pyfunction]
+pub fn memorization_load(side_is_white: bool, nodes: Vec<SplitNodeInput>) -> PyResult<u64> {
+    let mut positions: HashSet<String> = HashSet::new();
+    for fen in decision_points(side_is_white, &nodes)? {
+        positions.insert(canonical(&fen)?);
+    }
+    Ok(positions.len() as u64)
+}
+
+/// Player-turn FENs whose best prepared move beats every other legal move by more
+/// than `threshold` centipawns: "only moves" that have to be remembered.
+///
+/// `eval_cache` maps a FEN to centipawn scores for the side to move, keyed by UCI.
+/// Moves without a cached score are ignored, so a position is only flagged when
+/// at least one alternative legal move has been evaluated.
+#[pyfunction]
+pub fn only_moves(
+    side_is_white: bool,
+    nodes: Vec<SplitNodeInput>,
+    eval_cache: HashMap<String, HashMap<String, i32>>,
+    threshold: i32,
+) -> PyResult<Vec<String>> {
+    let cache = canonical_cache(&eval_cache)?;
+    let by_fen = nodes_by_fen(&nodes);
+    let mut flagged: Vec<String> = Vec::new();
+    for fen in decision_points(side_is_white, &nodes)? {
+        let Some(scores) = cache.get(&canonical(&fen)?) else {
+            continue;
+        };
+        let best = by_fen
+            .get(fen.as_str())
+            .into_iter()
+            .flatten()
+            .flat_map(|node| &node.children)
+            .filter_map(|child| scores.get(&child.uci).map(|cp| (*cp, child.uci.as_str())))
+            .max();
+        let Some((best_cp, best_uci)) = best else {
+            continue;
+        };
+        let position = parse_position(&fen)?;
+        let runner_up = position
+            .legal_moves()
+            .iter()
+            .map(|mv| UciMove::from_move(mv, CastlingMode::Standard).to_string())
+            .filter(|uci| uci != best_uci)
+            .filter_map(|uci| scores.get(&uci).copied())
+            .max();
+        if runner_up.is_some_and(|cp| best_cp - cp > threshold) {
+            flagged.push(fen);
+        }
+    }
+    Ok(flagged)
+}
+
+/// Prepared player moves `(parent_fen, uci)` that score more than
+/// `loss_threshold` centipawns below the best evaluated legal move.
+///
+/// `eval_cache` uses the same layout as [`only_moves`]: scores for the side to
+/// move keyed by UCI, so the loss is measured from the mover's perspective.
+/// Prepared moves without a cached score are never recommended.
+#[pyfunction]
+pub fn prune_recommendations(
+    nodes: Vec<SplitNodeInput>,
+    eval_cache: HashMap<String, HashMap<String, i32>>,
+    side_is_white: bool,
+    loss_threshold: i32,
+) -> PyResult<Vec<(String, String)>> {
+    let cache = canonical_cache(&eval_cache)?;
+    let by_fen = nodes_by_fen(&nodes);
+    let mut edges: Vec<(String, String)> = Vec::new();
+    for fen in decision_points(side_is_white, &nodes)? {
+        let Some(scores) = cache.get(&canonical(&fen)?) else {
+            continue;
+        };
+        let position = parse_position(&fen)?;
+        let Some(best_cp) = position
+            .legal_moves()
+            .iter()
+            .map(|mv| UciMove::from_move(mv, CastlingMode::Standard).to_string())
+            .filter_map(|uci| scores.get(&uci).copied())
+            .max()
+        else {
+            continue;
+        };
+        for child in by_fen
+            .get(fen.as_str())
+            .into_iter()
+            .flatten()
+            .flat_map(|node| &node.children)
+        {
+            if scores
+                .get(&child.uci)
+                .is_some_and(|cp| best_cp - cp > loss_threshold)
+            {
+                edges.push((fen.clone(), child.uci.clone()));
+            }
+        }
+    }
+    edges.sort();
+    edges.dedup();
+    Ok(edges)
+}
+
+/// Re-key an eval cache by canonical FEN so lookups ignore move counters.
+fn canonical_cache(
+    eval_cache: &HashMap<String, HashMap<String, i32>>,
+) -> PyResult<HashMap<String, &HashMap<String, i32>>> {
+    let mut cache: HashMap<String, &HashMap<String, i32>> = HashMap::new();
+    for (fen, scores) in eval_cache {
+        cache.insert(canonical(fen)?, scores);
+    }
+    Ok(cache)
+}
+
+/// Ply distance of every node from the nearest root (a node with no parent).
+fn node_depths(nodes: &[SplitNodeInput]) -> HashMap<String, u64> {
+    let children: HashSet<&str> = nodes
+        .iter()
+        .flat_map(|node| node.children.iter().map(|child| child.fen.as_str()))
+        .collect();
+    let by_fen = nodes_by_fen(nodes);
+
+    let mut depths: HashMap<String, u64> = HashMap::new();
+    let mut queue: VecDeque<(&str, u64)> = VecDeque::new();
+    for node in nodes {
+        if !children.contains(node.fen.as_str()) {
+            queue.push_back((node.fen.as_str(), 0));
+        }
+    }
+    while let Some((fen, depth)) = queue.pop_front() {
+        if depths.contains_key(fen) {
+            continue;
+        }
+        depths.insert(fen.to_string(), depth);
+        for child in by_fen
+            .get(fen)
+            .into_iter()
+            .flatten()
+            .flat_map(|node| &node.children)
+        {
+            queue.push_back((child.fen.as_str(), depth + 1));
+        }
+    }
+    depths
+}
+
+/// Seeded shuffle of the player's decision points for drilling.
+///
+/// With `weighted`, positions deeper in the tree tend to come up earlier: each
+/// position is weighted by `1 + depth` and ordered by a weighted random key.
+#[pyfunction]
+#[pyo3(signature = (side_is_white, nodes, seed, weighted=false))]
+pub fn quiz_order(
+    side_is_white: bool,
+    nodes: Vec<SplitNodeInput>,
+    seed: u64,
+    weighted: bool,
+) -> PyResult<Vec<String>> {
+    let points = decision_points(side_is_white, &nodes)?;
+    let depths = if weighted {
+        node_depths(&nodes)
+    } else {
+        HashMap::new()
+    };
+    let mut rng = SplitMix64::new(seed);
+    let mut keyed: Vec<(f64, String)> = points
+        .into_iter()
+        .map(|fen| {
+            let weight = 1.0 + depths.get(&fen).copied().unwrap_or(0) as f64;
+            (rng.next_f64().powf(1.0 / weight), fen)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    Ok(keyed.into_iter().map(|(_, fen)| fen).collect())
+}
+
+/// Small deterministic PRNG so seeded orders are stable across platforms.
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `(0, 1]`.
+    fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EdgeChange {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+impl EdgeChange {
+    fn comment(self) -> Option<&'static str> {
+        match self {
+            EdgeChange::Unchanged => None,
+            EdgeChange::Added => Some("+added"),
+            EdgeChange::Removed => Some("-removed"),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct DiffEdge {
+    uci: String,
+    child: String,
+    change: EdgeChange,
+}
+
+/// Union of two repertoire graphs with each edge tagged by which side it came from.
+fn diff_repertoires(
+    old_nodes: &[SplitNodeInput],
+    new_nodes: &[SplitNodeInput],
+) -> BTreeMap<String, Vec<DiffEdge>> {
+    let edge_set = |nodes: &[SplitNodeInput]| -> HashSet<(String, String, String)> {
+        nodes
+            .iter()
+            .flat_map(|node| {
+                node.children
+                    .iter()
+                    .map(|child| (node.fen.clone(), child.uci.clone(), child.fen.clone()))
+            })
+            .collect()
+    };
+    let old_edges = edge_set(old_nodes);
+    let new_edges = edge_set(new_nodes);
+
+    let mut graph: BTreeMap<String, Vec<DiffEdge>> = BTreeMap::new();
+    for edge in old_edges.union(&new_edges) {
+        let change = match (old_edges.contains(edge), new_edges.contains(edge)) {
+            (true, true) => EdgeChange::Unchanged,
+            (false, true) => EdgeChange::Added,
+            _ => EdgeChange::Removed,
+        };
+        let (parent, uci, child) = edge.clone();
+        graph
+            .entry(parent)
+            .or_default()
+            .push(DiffEdge { uci, child, change });
+    }
+    for edges in graph.values_mut() {
+        edges.sort_by(|a, b| a.uci.cmp(&b.uci));
+    }
+    graph
+}
+
+/// Render the differences between two repertoires as PGN, one game per root.
+///
+/// Only lines leading to a change are written. The first move of an added line is
+/// commented `{+added}` and the first move of a removed line `{-removed}`.
+#[pyfunction]
+pub fn diff_repertoires_pgn(
+    old_nodes: Vec<SplitNodeInput>,
+    new_nodes: Vec<SplitNodeInput>,
+) -> PyResult<String> {
+    let graph = diff_repertoires(&old_nodes, &new_nodes);
+    let children: HashSet<&str> = graph
+        .values()
+        .flat_map(|edges| edges.iter().map(|edge| edge.child.as_str()))
+        .collect();
+
+    let mut changed: HashMap<String, bool> = HashMap::new();
+    let mut games: Vec<String> = Vec::new();
+    for root in graph.keys().filter(|fen| !children.contains(fen.as_str())) {
+        if !subtree_changed(root, &graph, &mut changed, &mut HashSet::new()) {
+            continue;
+        }
+        let position = parse_position(root)?;
+        let mut movetext = String::new();
+        let mut path: HashSet<String> = HashSet::from([root.clone()]);
+        write_diff_line(
+            &mut movetext,
+            root,
+            &position,
+            EdgeChange::Unchanged,
+            true,
+            &graph,
+            &changed,
+            &mut path,
+        )?;
+
+        let mut game = String::from("[Event \"Repertoire diff\"]\n[Result \"*\"]\n");
+        if root != START_FEN {
+            game.push_str(&format!("[SetUp \"1\"]\n[FEN \"{root}\"]\n"));
+        }
+        game.push('\n');
+        game.push_str(&movetext);
+        game.push_str(" *\n");
+        games.push(game);
+    }
+    Ok(games.join("\n"))
+}
+
+/// Lichess analysis board URL for `moves` (UCI) played from `start_fen`, in the
+/// `/analysis/pgn/<pgn>` form. The moves are replayed so illegal ones are
+/// rejected and the URL carries SAN.
+#[pyfunction]
+#[pyo3(signature = (start_fen, moves, base_url="https://lichess.org".to_string()))]
+pub fn analysis_url(
+    start_fen: Option<String>,
+    moves: Vec<String>,
+    base_url: String,
+) -> PyResult<String> {
+    let fen = start_fen.unwrap_or_else(|| START_FEN.to_string());
+    let mut position = parse_position(&fen)?;
+    let mut pgn = String::new();
+    if fen != START_FEN {
+        pgn.push_str(&format!("[SetUp \"1\"] [FEN \"{fen}\"] "));
+    }
+    for (index, uci) in moves.iter().enumerate() {
+        let current = Fen::from_position(position.clone(), EnPassantMode::Legal).to_string();
+        let mv = parse_uci_move(&position, &current, uci)?;
+        let san = SanPlus::from_move(position.clone(), &mv);
+        let number = position.fullmoves();
+        if index > 0 {
+            pgn.push(' ');
+        }
+        if position.turn() == Color::White {
+            pgn.push_str(&format!("{number}. {san}"));
+        } else if index == 0 {
+            pgn.push_str(&format!("{number}... {san}"));
+        } else {
+            pgn.push_str(&san.to_string());
+        }
+        position.play_unchecked(&mv);
+    }
+
+    let mut url = reqwest::Url::parse(&base_url)
+        .map_err(|err| PyValueError::new_err(format!("Invalid base URL '{base_url}': {err}")))?;
+    url.path_segments_mut()
+        .map_err(|_| PyValueError::new_err(format!("Invalid base URL '{base_url}'")))?
+        .pop_if_empty()
+        .extend(["analysis", "pgn", pgn.as_str()]);
+    Ok(url.to_string())
+}
+
+fn subtree_changed(
+    fen: &str,
+    graph: &BTreeMap<String, Vec<DiffEdge>>,
+    memo: &mut HashMap<String, bool>,
+    visiting: &mut HashSet<String>,
+) -> bool {
+    if let Some(value) = memo.get(fen) {
+        return *value;
+    }
+    if !visiting.insert(fen.to_string()) {
+        return false;
+    }
+    let mut result = false;
+    for edge in graph.get(fen).into_iter().flatten() {
+        let child_changed = subtree_changed(&edge.child, graph, memo, visiting);
+        result |= edge.change != EdgeChange::Unchanged || child_changed;
+    }
+    visiting.remove(fen);
+    memo.insert(fen.to_string(), result);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_diff_line(
+    out: &mut String,
+    fen: &str,
+    position: &Chess,
+    parent_change: EdgeChange,
+    force_number: bool,
+    graph: &BTreeMap<String, Vec<DiffEdge>>,
+    changed: &HashMap<String, bool>,
+    path: &mut HashSet<String>,
+) -> PyResult<()> {
+    let edges: Vec<&DiffEdge> = graph
+        .get(fen)
+        .into_iter()
+        .flatten()
+        .filter(|edge| {
+            !path.contains(&edge.child)
+                && (edge.change != EdgeChange::Unchanged
+                    || changed.get(&edge.child).copied().unwrap_or(false))
+        })
+        .collect();
+    let Some((main, alternatives)) = edges.split_first() else {
+        return Ok(());
+    };
+
+    let main_commented = write_diff_move(out, fen, position, main, parent_change, force_number)?;
+    for alternative in alternatives {
+        out.push_str(" (");
+        let commented = write_diff_move(out, fen, position, alternative, parent_change, true)?;
+        let next = play_uci(position, fen, &alternative.uci)?;
+        path.insert(alternative.child.clone());
+        write_diff_line(
+            out,
+            &alternative.child,
+            &next,
+            alternative.change,
+            commented,
+            graph,
+            changed,
+            path,
+        )?;
+        path.remove(&alternative.child);
+        out.push(')');
+    }
+
+    let next = play_uci(position, fen, &main.uci)?;
+    path.insert(main.child.clone());
+    write_diff_line(
+        out,
+        &main.child,
+        &next,
+        main.change,
+        main_commented || !alternatives.is_empty(),
+        graph,
+        changed,
+        path,
+    )?;
+    path.remove(&main.child);
+    Ok(())
+}
+
+/// Append one move, returning whether a comment followed it.
+fn write_diff_move(
+    out: &mut String,
+    fen: &str,
+    position: &Chess,
+    edge: &DiffEdge,
+    parent_change: EdgeChange,
+    force_number: bool,
+) -> PyResult<bool> {
+    let mv = parse_uci_move(position, fen, &edge.uci)?;
+    let san = SanPlus::from_move(position.clone(), &mv);
+    let number = position.fullmoves();
+    if !out.is_empty() && !out.ends_with('(') {
+        out.push(' ');
+    }
+    if position.turn() == Color::White {
+        out.push_str(&format!("{number}. {san}"));
+    } else if force_number {
+        out.push_str(&format!("{number}... {san}"));
+    } else {
+        out.push_str(&san.to_string());
+    }
+    match edge.change.comment() {
+        Some(comment) if edge.change != parent_change => {
+            out.push_str(&format!(" {{{comment}}}"));
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn play_uci(position: &Chess, fen: &str, uci: &str) -> PyResult<Chess> {
+    let mv = parse_uci_move(position, fen, uci)?;
+    let mut next = position.clone();
+    next.play_unchecked(&mv);
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use std::str::FromStr;
+
+    fn next_fen(start_fen: &str, moves: &[&str]) -> String {
+        let fen = Fen::from_str(start_fen).unwrap();
+        let mut position: Chess = fen.into_position(CastlingMode::Standard).unwrap();
+        for mv in moves {
+            let chess_move = UciMove::from_str(mv).unwrap().to_move(&position).unwrap();
+            position.play_unchecked(&chess_move);
+        }
+        Fen::from_position(position, EnPassantMode::Legal).to_string()
+    }
+
+    fn node(fen: &str, children: &[(&str, &str)]) -> SplitNodeInput {
+        SplitNodeInput {
+            fen: fen.to_string(),
+            children: children
+                .iter()
+                .map(|(uci, child_fen)| SplitChildInput {
+                    uci: uci.to_string(),
+                    fen: child_fen.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn coverage_counts_opponent_nodes_with_replies() {
+        let after_e4 = next_fen(START_FEN, &["e2e4"]);
+        let after_d4 = next_fen(START_FEN, &["d2d4"]);
+        let after_e4_e5 = next_fen(&after_e4, &["e7e5"]);
+        let nodes = vec![
+            node(START_FEN, &[("e2e4", &after_e4), ("d2d4", &after_d4)]),
+            node(&after_e4, &[("e7e5", &after_e4_e5)]),
+            node(&after_d4, &[]),
+        ];
+
+        let white = coverage(true, nodes).unwrap();
+        assert!((white - 0.5).abs() < f64::EPSILON);
+    }
+
+    /// Build `SplitNodeInput`s for a set of UCI lines from the start position.
+    fn nodes_from_lines(lines: &[&[&str]]) -> Vec<SplitNodeInput> {
+        let mut map: BTreeMap<String, SplitNodeInput> = BTreeMap::new();
+        for line in lines {
+            let mut fen = START_FEN.to_string();
+            for mv in *line {
+                let child = crate::canonicalize_fen_str(&next_fen(&fen, &[mv])).unwrap();
+                let entry = map.entry(fen.clone()).or_insert_with(|| node(&fen, &[]));
+                if !entry.children.iter().any(|c| c.uci == *mv) {
+                    entry.children.push(SplitChildInput {
+                        uci: mv.to_string(),
+                        fen: child.clone(),
+                    });
+                }
+                fen = child;
+            }
+        }
+        map.into_values().collect()
+    }
+
+    #[test]
+    fn diff_repertoires_pgn_annotates_added_and_removed_lines() {
+        let old = nodes_from_lines(&[&["e2e4", "e7e5", "g1f3"], &["e2e4", "c7c5", "g1f3"]]);
+        let new = nodes_from_lines(&[&["e2e4", "e7e5", "g1f3"], &["e2e4", "e7e6", "d2d4"]]);
+
+        let pgn = diff_repertoires_pgn(old, new).unwrap();
+        assert!(pgn.contains("[Event \"Repertoire diff\"]"));
+        assert!(
+            pgn.contains("1. e4 c5 {-removed} (1... e6 {+added} 2. d4) 2. Nf3 *"),
+            "{pgn}"
+        );
+        assert!(!pgn.contains("e5"), "unchanged lines are omitted: {pgn}");
+        assert!(pgn.trim_end().ends_with('*'));
+    }
+
+    #[test]
+    fn quiz_order_is_seeded_and_covers_every_decision_point() {
+        let nodes = nodes_from_lines(&[
+            &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"],
+            &["e2e4", "c7c5", "g1f3", "d7d6", "d2d4"],
+            &["e2e4", "e7e6", "d2d4", "d7d5", "b1c3"],
+        ]);
+        let expected = decision_points(true, &nodes).unwrap();
+        assert_eq!(expected.len(), 7);
+
+        for weighted in [false, true] {
+            let first = quiz_order(true, nodes.clone(), 42, weighted).unwrap();
+            let second = quiz_order(true, nodes.clone(), 42, weighted).unwrap();
+            assert_eq!(first, second);
+            let mut sorted = first.clone();
+            sorted.sort();
+            assert_eq!(sorted, expected);
+        }
+        let other_seed = quiz_order(true, nodes.clone(), 7, false).unwrap();
+        assert_ne!(other_seed, quiz_order(true, nodes, 42, false).unwrap());
+    }
+
+    #[test]
+    fn order_sensitive_nodes_flags_transpositions_prepared_one_way() {
+        let nodes = nodes_from_lines(&[
+            &["d2d4", "d7d5", "g1f3"],
+            &["e2e4", "e7e5", "f1c4"],
+            &["e2e3", "e7e5", "g1f3"],
+            &["g1f3", "e7e5", "e2e3"],
+        ]);
+        let one_way = next_fen(START_FEN, &["d2d4", "d7d5", "g1f3"]);
+
+        let flagged = order_sensitive_nodes(nodes).unwrap();
+        assert_eq!(flagged, vec![canonicalize_fen_str(&one_way).unwrap()]);
+    }
+
+    #[test]
+    fn memorization_load_counts_transposed_decision_points_once() {
+        let mut nodes = nodes_from_lines(&[
+            &["g1f3", "d7d5", "d2d4", "g8f6", "c2c4"],
+            &["d2d4", "d7d5", "g1f3", "g8f6", "c2c4"],
+        ]);
+        let transposed = next_fen(START_FEN, &["d2d4", "d7d5", "g1f3", "g8f6"]);
+        nodes.push(node(
+            &transposed,
+            &[("c2c4", &next_fen(&transposed, &["c2c4"]))],
+        ));
+        assert_eq!(decision_points(true, &nodes).unwrap().len(), 5);
+
+        assert_eq!(memorization_load(true, nodes).unwrap(), 4);
+    }
+
+    #[test]
+    fn only_moves_flags_positions_with_a_single_good_move() {
+        let nodes = nodes_from_lines(&[&["e2e4", "e7e5", "g1f3"], &["e2e4", "d7d5", "e4d5"]]);
+        let after_e5 = next_fen(START_FEN, &["e2e4", "e7e5"]);
+        let after_d5 = next_fen(START_FEN, &["e2e4", "d7d5"]);
+        let eval_cache: HashMap<String, HashMap<String, i32>> = HashMap::from([
+            (
+                after_e5,
+                HashMap::from([
+                    ("g1f3".to_string(), 40),
+                    ("b1c3".to_string(), 30),
+                    ("f1c4".to_string(), 25),
+                ]),
+            ),
+            (
+                after_d5.clone(),
+                HashMap::from([
+                    ("e4d5".to_string(), 45),
+                    ("b1c3".to_string(), -60),
+                    ("e4e5".to_string(), -20),
+                ]),
+            ),
+        ]);
+
+        let flagged = only_moves(true, nodes, eval_cache, 50).unwrap();
+        assert_eq!(flagged, vec![canonicalize_fen_str(&after_d5).unwrap()]);
+    }
+
+    #[test]
+    fn analysis_url_encodes_san_under_the_base_url() {
+        let moves = vec!["e2e4".to_string(), "e7e5".to_string()];
+        let url = analysis_url(None, moves, "https://lichess.example/".to_string()).unwrap();
+        assert_eq!(url, "https://lichess.example/analysis/pgn/1.%20e4%20e5");
+
+        let after_e4 = next_fen(START_FEN, &["e2e4"]);
+        let url = analysis_url(
+            Some(after_e4),
+            vec!["c7c5".to_string()],
+            "https://lichess.org".to_string(),
+        )
+        .unwrap();
+        assert!(url.starts_with("https://lichess.org/analysis/pgn/"));
+        assert!(url.ends_with("1...%20c5"));
+
+        assert!(
+            analysis_url(
+                None,
+                vec!["e2e5".to_string()],
+                "https://lichess.org".to_string()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn group_transpositions_groups_indices_by_canonical_fen() {
+        let fens = vec![
+            next_fen(START_FEN, &["g1f3", "d7d5", "d2d4"]),
+            next_fen(START_FEN, &["e2e4"]),
+            next_fen(START_FEN, &["d2d4", "d7d5", "g1f3"]),
+        ];
+        assert_eq!(
+            group_transpositions(fens).unwrap(),
+            vec![vec![0, 2], vec![1]]
+        );
+    }
+
+    #[test]
+    fn merge_node_graphs_unions_edges_without_duplicates() {
+        let first = nodes_from_lines(&[&["e2e4", "e7e5", "g1f3"]]);
+        let second = nodes_from_lines(&[&["e2e4", "e7e5", "f1c4"], &["d2d4"]]);
+
+        let merged = merge_node_graphs(vec![first, second]).unwrap();
+        let expected = nodes_from_lines(&[
+            &["e2e4", "e7e5", "g1f3"],
+            &["e2e4", "e7e5", "f1c4"],
+            &["d2d4"],
+        ]);
+        let edges = |nodes: &[SplitNodeInput]| -> BTreeMap<String, Vec<(String, String)>> {
+            nodes
+                .iter()
+                .map(|node| {
+                    let mut children: Vec<(String, String)> = node
+                        .children
+                        .iter()
+                        .map(|child| (child.uci.clone(), child.fen.clone()))
+                        .collect();
+                    children.sort();
+                    (canonicalize_fen_str(&node.fen).unwrap(), children)
+                })
+                .collect()
+        };
+        assert_eq!(edges(&merged), edges(&expected));
+
+        let after_e4 = next_fen(START_FEN, &["e2e4"]);
+        let conflicting = vec![node(START_FEN, &[("e2e4", START_FEN)])];
+        let err = merge_node_graphs(vec![
+            vec![node(START_FEN, &[("e2e4", &after_e4)])],
+            conflicting,
+        ])
+        .err()
+        .expect("conflict");
+        Python::initialize();
+        assert!(err.to_string().contains(START_FEN));
+    }
+
+    #[test]
+    fn prune_recommendations_flags_prepared_moves_that_lose_too_much() {
+        let nodes = nodes_from_lines(&[
+            &["e2e4", "e7e5", "g1f3"],
+            &["e2e4", "d7d5", "b1c3"],
+            &["e2e4", "d7d5", "e4d5"],
+        ]);
+        let after_e5 = next_fen(START_FEN, &["e2e4", "e7e5"]);
+        let after_d5 = next_fen(START_FEN, &["e2e4", "d7d5"]);
+        let eval_cache: HashMap<String, HashMap<String, i32>> = HashMap::from([
+            (
+                after_e5,
+                HashMap::from([("g1f3".to_string(), 40), ("b1c3".to_string(), 30)]),
+            ),
+            (
+                after_d5.clone(),
+                HashMap::from([
+                    ("e4d5".to_string(), 45),
+                    ("b1c3".to_string(), -60),
+                    ("e4e5".to_string(), -20),
+                ]),
+            ),
+        ]);
+
+        let pruned = prune_recommendations(nodes, eval_cache, true, 50).unwrap();
+        let parent = canonicalize_fen_str(&after_d5).unwrap();
+        assert_eq!(pruned, vec![(parent, "b1c3".to_string())]);
+    }
+
+    #[test]
+    fn orphan_positions_reports_disconnected_and_illegally_linked_nodes() {
+        let mut nodes = nodes_from_lines(&[&["e2e4", "e7e5"]]);
+        let stray = next_fen(START_FEN, &["d2d4", "d7d5"]);
+        nodes.push(node(&stray, &[("c2c4", &next_fen(&stray, &["c2c4"]))]));
+        let bogus_parent = next_fen(START_FEN, &["g1f3"]);
+        let bogus_child = next_fen(START_FEN, &["b1c3", "g8f6"]);
+        nodes.push(node(&bogus_child, &[]));
+        nodes.push(node(START_FEN, &[("g1f3", &bogus_parent)]));
+        // The edge below claims a move that does not lead to the recorded child.
+        nodes.push(node(&bogus_parent, &[("b8c6", &bogus_child)]));
+
+        let orphans = orphan_positions(START_FEN.to_string(), nodes).unwrap();
+        let mut expected = vec![stray, bogus_child];
+        expected.sort();
+        assert_eq!(orphans, expected);
+    }
+
+    #[test]
+    fn transposing_moves_returns_every_edge_into_the_target() {
+        let nodes = nodes_from_lines(&[&["g1f3", "d7d5", "d2d4"], &["d2d4", "d7d5", "g1f3"]]);
+        let via_knight = next_fen(START_FEN, &["g1f3", "d7d5"]);
+        let via_pawn = next_fen(START_FEN, &["d2d4", "d7d5"]);
+        let target = next_fen(START_FEN, &["d2d4", "d7d5", "g1f3"]);
+
+        let edges = transposing_moves(nodes, target).unwrap();
+        let mut expected = vec![
+            (
+                canonicalize_fen_str(&via_knight).unwrap(),
+                "d2d4".to_string(),
+            ),
+            (canonicalize_fen_str(&via_pawn).unwrap(), "g1f3".to_string()),
+        ];
+        expected.sort();
+        assert_eq!(edges, expected);
+    }
+}
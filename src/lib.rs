@@ -1,46 +1,135 @@
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyDict, PyList, PyTuple};
 use pyo3::{Bound, FromPyObject};
 
 use shakmaty::Position;
 use shakmaty::fen::Fen;
 use shakmaty::san::SanPlus;
 use shakmaty::uci::UciMove;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
 use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Move, Role, Square};
 
-use std::collections::{HashMap, HashSet};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
 
-mod stockfish;
-use stockfish::stockfish_evaluate;
+mod graph;
+use graph::{
+    analysis_url, coverage, diff_repertoires_pgn, group_transpositions, main_line,
+    memorization_load, merge_node_graphs, only_moves, order_sensitive_nodes, orphan_positions,
+    prune_recommendations, quiz_order, transposing_moves,
+};
+pub mod stockfish;
+use stockfish::{
+    evaluate_candidates, list_stockfish_pools, shutdown_stockfish_pool, shutdown_stockfish_pools,
+    stockfish_evaluate, stockfish_evaluate_batch, stockfish_evaluate_cached, warmup_stockfish_pool,
+};
+pub mod checkpoint;
+pub mod frequency;
+pub mod pgn;
+use pgn::{pgn_to_fens, unique_positions};
 pub mod study;
+pub mod work_queue;
 
 /// A Python module implemented in Rust.
 #[pymodule]
 fn _core(_py: Python<'_>, m: Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(player_move_analysis, &m)?)?;
+    m.add_function(wrap_pyfunction!(fingerprint_examples, &m)?)?;
     m.add_function(wrap_pyfunction!(player_turn_mask, &m)?)?;
+    m.add_function(wrap_pyfunction!(legal_moves, &m)?)?;
+    m.add_function(wrap_pyfunction!(san_to_uci, &m)?)?;
+    m.add_function(wrap_pyfunction!(uci_to_san, &m)?)?;
+    m.add_function(wrap_pyfunction!(game_fens, &m)?)?;
+    m.add_function(wrap_pyfunction!(perft, &m)?)?;
+    m.add_function(wrap_pyfunction!(position_hash, &m)?)?;
+    m.add_function(wrap_pyfunction!(position_status, &m)?)?;
     m.add_function(wrap_pyfunction!(split_repertoire_nodes, &m)?)?;
+    m.add_function(wrap_pyfunction!(coverage, &m)?)?;
+    m.add_function(wrap_pyfunction!(diff_repertoires_pgn, &m)?)?;
+    m.add_function(wrap_pyfunction!(quiz_order, &m)?)?;
+    m.add_function(wrap_pyfunction!(orphan_positions, &m)?)?;
+    m.add_function(wrap_pyfunction!(transposing_moves, &m)?)?;
+    m.add_function(wrap_pyfunction!(main_line, &m)?)?;
+    m.add_function(wrap_pyfunction!(only_moves, &m)?)?;
+    m.add_function(wrap_pyfunction!(memorization_load, &m)?)?;
+    m.add_function(wrap_pyfunction!(order_sensitive_nodes, &m)?)?;
+    m.add_function(wrap_pyfunction!(prune_recommendations, &m)?)?;
+    m.add_function(wrap_pyfunction!(merge_node_graphs, &m)?)?;
+    m.add_function(wrap_pyfunction!(group_transpositions, &m)?)?;
+    m.add_function(wrap_pyfunction!(analysis_url, &m)?)?;
+    m.add_function(wrap_pyfunction!(unique_positions, &m)?)?;
+    m.add_function(wrap_pyfunction!(pgn_to_fens, &m)?)?;
     m.add_function(wrap_pyfunction!(canonicalize_fen, &m)?)?;
+    m.add_function(wrap_pyfunction!(canonicalize_fens, &m)?)?;
+    m.add_function(wrap_pyfunction!(is_canonical, &m)?)?;
     m.add_function(wrap_pyfunction!(stockfish_evaluate, &m)?)?;
+    m.add_function(wrap_pyfunction!(stockfish_evaluate_batch, &m)?)?;
+    m.add_function(wrap_pyfunction!(stockfish_evaluate_cached, &m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate_candidates, &m)?)?;
+    m.add_function(wrap_pyfunction!(warmup_stockfish_pool, &m)?)?;
+    m.add_function(wrap_pyfunction!(list_stockfish_pools, &m)?)?;
+    m.add_function(wrap_pyfunction!(shutdown_stockfish_pools, &m)?)?;
+    m.add_function(wrap_pyfunction!(shutdown_stockfish_pool, &m)?)?;
     Ok(())
 }
 
+/// Canonical form of `fen_text`. With `reset_counters=False` the halfmove and
+/// fullmove clocks are kept and only the rest of the FEN is normalized. With
+/// `chess960=True` castling rights may name rooks on any file.
 #[pyfunction]
-fn canonicalize_fen(fen_text: String) -> PyResult<String> {
-    canonicalize_fen_str(&fen_text)
-        .map_err(|err| PyValueError::new_err(format!("{err} (fen='{fen_text}')")))
+#[pyo3(signature = (fen_text, reset_counters=true, chess960=false))]
+fn canonicalize_fen(fen_text: String, reset_counters: bool, chess960: bool) -> PyResult<String> {
+    let mode = if chess960 {
+        CastlingMode::Chess960
+    } else {
+        CastlingMode::Standard
+    };
+    let canonical = normalize_fen_str(&fen_text, mode).map(|normalized| {
+        if reset_counters {
+            reset_move_counters(&normalized)
+        } else {
+            normalized
+        }
+    });
+    canonical.map_err(|err| PyValueError::new_err(format!("{err} (fen='{fen_text}')")))
+}
+
+/// Canonicalize every FEN in one call; an invalid entry is reported with its
+/// index.
+#[pyfunction]
+fn canonicalize_fens(fens: Vec<String>) -> PyResult<Vec<String>> {
+    fens.iter()
+        .enumerate()
+        .map(|(index, fen_text)| {
+            canonicalize_fen_str(fen_text).map_err(|err| {
+                PyValueError::new_err(format!("{err} (index {index}, fen='{fen_text}')"))
+            })
+        })
+        .collect()
+}
+
+#[pyfunction]
+fn is_canonical(fen: String) -> PyResult<bool> {
+    let canonical = canonicalize_fen(fen.clone(), true, false)?;
+    Ok(canonical == fen)
 }
 
 pub fn canonicalize_fen_str(fen_text: &str) -> Result<String, String> {
+    normalize_fen_str(fen_text, CastlingMode::Standard)
+        .map(|normalized| reset_move_counters(&normalized))
+}
+
+/// Round-trip `fen_text` through a position, normalizing everything but the
+/// move counters.
+fn normalize_fen_str(fen_text: &str, mode: CastlingMode) -> Result<String, String> {
     let fen = Fen::from_str(fen_text)
         .map_err(|err| format!("Invalid FEN while canonicalizing: {err}"))?;
     let position: Chess = fen
-        .into_position(CastlingMode::Standard)
+        .into_position(mode)
         .map_err(|err| format!("Unable to construct position while canonicalizing: {err}"))?;
-    let normalized = Fen::from_position(position, EnPassantMode::Legal).to_string();
-    Ok(reset_move_counters(&normalized))
+    Ok(Fen::from_position(position, EnPassantMode::Legal).to_string())
 }
 
 fn reset_move_counters(fen_text: &str) -> String {
@@ -53,11 +142,19 @@ fn reset_move_counters(fen_text: &str) -> String {
     fen_text.to_string()
 }
 
+/// Global frequency of every player move and, per node, its moves ranked by
+/// that frequency. With `report_conflicts`, a further list holds the nodes that
+/// give more than one distinct player move, so callers can warn about
+/// ambiguous repertoire entries. With `with_move_counts`, a last dict maps each
+/// ranked FEN to the number of moves recorded at its node.
 #[pyfunction]
-fn player_move_analysis(
-    py: Python<'_>,
+#[pyo3(signature = (nodes, report_conflicts=false, with_move_counts=false))]
+fn player_move_analysis<'py>(
+    py: Python<'py>,
     nodes: Vec<PyNodeInput>,
-) -> PyResult<(Py<PyAny>, Py<PyAny>)> {
+    report_conflicts: bool,
+    with_move_counts: bool,
+) -> PyResult<Bound<'py, PyTuple>> {
     let mut analyzed_nodes: Vec<NodeAnalysis> = Vec::with_capacity(nodes.len());
     let mut frequencies: HashMap<Fingerprint, u32> = HashMap::new();
 
@@ -112,35 +209,227 @@ fn player_move_analysis(
             .sort_by(|a, b| b.frequency.cmp(&a.frequency).then(a.san.cmp(&b.san)));
     }
 
-    let freq_payload = build_frequency_payload(py, &frequencies)?;
-    let rankings_payload = build_rankings_payload(py, analyzed_nodes)?;
-    Ok((freq_payload, rankings_payload))
+    let conflicts_payload = if report_conflicts {
+        Some(build_conflicts_payload(py, &analyzed_nodes)?)
+    } else {
+        None
+    };
+    let move_counts_payload = if with_move_counts {
+        Some(build_move_counts_payload(py, &analyzed_nodes)?)
+    } else {
+        None
+    };
+    let mut items = vec![
+        build_frequency_payload(py, &frequencies)?,
+        build_rankings_payload(py, analyzed_nodes)?,
+    ];
+    items.extend(conflicts_payload);
+    items.extend(move_counts_payload);
+    PyTuple::new(py, items)
+}
+
+#[pyfunction]
+fn fingerprint_examples(py: Python<'_>, nodes: Vec<PyGameNodeInput>) -> PyResult<Py<PyAny>> {
+    let examples = collect_fingerprint_examples(nodes)?;
+    let items = PyList::empty(py);
+    for example in examples {
+        let entry = PyDict::new(py);
+        entry.set_item("piece", role_symbol(example.fingerprint.role))?;
+        entry.set_item("from_square", square_name(example.fingerprint.from))?;
+        entry.set_item("to_square", square_name(example.fingerprint.to))?;
+        entry.set_item("promotion", example.fingerprint.promotion.map(role_symbol))?;
+        entry.set_item("frequency", example.frequency)?;
+        entry.set_item("game_id", example.game_id)?;
+        entry.set_item("ply", example.ply)?;
+        entry.set_item("uci", example.uci)?;
+        items.append(entry)?;
+    }
+    Ok(items.into())
+}
+
+fn collect_fingerprint_examples(nodes: Vec<PyGameNodeInput>) -> PyResult<Vec<FingerprintExample>> {
+    let mut examples: HashMap<Fingerprint, FingerprintExample> = HashMap::new();
+    for node in nodes {
+        let position = parse_position(&node.fen)?;
+        for move_text in node.moves {
+            let mv = parse_uci_move(&position, &node.fen, &move_text)?;
+            let fingerprint = Fingerprint::from_move(&mv)?;
+            examples
+                .entry(fingerprint.clone())
+                .or_insert_with(|| FingerprintExample {
+                    fingerprint,
+                    frequency: 0,
+                    game_id: node.game_id.clone(),
+                    ply: node.ply,
+                    uci: move_text.clone(),
+                })
+                .frequency += 1;
+        }
+    }
+    let mut examples: Vec<FingerprintExample> = examples.into_values().collect();
+    examples.sort_by(|a, b| {
+        b.frequency
+            .cmp(&a.frequency)
+            .then_with(|| a.uci.cmp(&b.uci))
+    });
+    Ok(examples)
 }
 
 #[pyfunction]
 fn player_turn_mask(side_is_white: bool, fens: Vec<String>) -> PyResult<Vec<bool>> {
-    let target_color = if side_is_white {
+    let side = if side_is_white {
         Color::White
     } else {
         Color::Black
     };
+    player_turn_flags(side, &fens).map_err(PyValueError::new_err)
+}
+
+/// `player_turn_mask` for Rust callers: whether `side` is to move in each FEN.
+pub fn player_turn_flags(side: Color, fens: &[String]) -> Result<Vec<bool>, String> {
     let mut mask: Vec<bool> = Vec::with_capacity(fens.len());
     for fen_text in fens {
-        let fen = Fen::from_str(&fen_text).map_err(|err| {
+        let fen = Fen::from_str(fen_text)
+            .map_err(|err| format!("Invalid FEN '{fen_text}' while checking turn: {err}"))?;
+        let position: Chess = fen.into_position(CastlingMode::Standard).map_err(|err| {
+            format!("Unable to construct position from '{fen_text}' while checking turn: {err}")
+        })?;
+        mask.push(position.turn() == side);
+    }
+    Ok(mask)
+}
+
+/// Legal moves of `fen` as `(uci, san)` pairs, sorted by SAN.
+#[pyfunction]
+fn legal_moves(fen: String) -> PyResult<Vec<(String, String)>> {
+    let parsed = Fen::from_str(&fen).map_err(|err| {
+        PyValueError::new_err(format!(
+            "Invalid FEN '{}' while listing legal moves: {err}",
+            fen
+        ))
+    })?;
+    let position: Chess = parsed
+        .into_position(CastlingMode::Standard)
+        .map_err(|err| {
             PyValueError::new_err(format!(
-                "Invalid FEN '{}' while checking turn: {err}",
-                fen_text
+                "Unable to construct position from '{}' while listing legal moves: {err}",
+                fen
             ))
         })?;
-        let position: Chess = fen.into_position(CastlingMode::Standard).map_err(|err| {
+    let mut moves: Vec<(String, String)> = position
+        .legal_moves()
+        .iter()
+        .map(|mv| {
+            (
+                UciMove::from_move(mv, CastlingMode::Standard).to_string(),
+                SanPlus::from_move(position.clone(), mv).to_string(),
+            )
+        })
+        .collect();
+    moves.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(moves)
+}
+
+/// UCI notation of `san` played in `fen`.
+#[pyfunction]
+fn san_to_uci(fen: String, san: String) -> PyResult<String> {
+    let position = parse_position(&fen)?;
+    let parsed = SanPlus::from_ascii(san.as_bytes())
+        .map_err(|err| PyValueError::new_err(format!("Invalid SAN '{san}' for {fen}: {err}")))?;
+    let mv = parsed
+        .san
+        .to_move(&position)
+        .map_err(|_| PyValueError::new_err(format!("Move '{san}' is illegal in position {fen}")))?;
+    Ok(UciMove::from_move(&mv, CastlingMode::Standard).to_string())
+}
+
+/// SAN notation, with check and mate suffixes, of `uci` played in `fen`.
+#[pyfunction]
+fn uci_to_san(fen: String, uci: String) -> PyResult<String> {
+    let position = parse_position(&fen)?;
+    let mv = parse_uci_move(&position, &fen, &uci)?;
+    Ok(SanPlus::from_move(position, &mv).to_string())
+}
+
+/// Canonical FEN of the starting position and of the position after each of
+/// `moves` (UCI), so the result has `moves.len() + 1` entries.
+#[pyfunction]
+#[pyo3(signature = (start_fen, moves))]
+fn game_fens(start_fen: Option<String>, moves: Vec<String>) -> PyResult<Vec<String>> {
+    let start_fen = start_fen
+        .unwrap_or_else(|| Fen::from_position(Chess::new(), EnPassantMode::Legal).to_string());
+    let mut position = parse_position(&start_fen)?;
+    let mut fens: Vec<String> = Vec::with_capacity(moves.len() + 1);
+    fens.push(canonical_position_fen(&position)?);
+    for (index, move_text) in moves.iter().enumerate() {
+        let ply = index + 1;
+        let uci = UciMove::from_str(move_text).map_err(|err| {
+            PyValueError::new_err(format!("Invalid UCI '{move_text}' at ply {ply}: {err}"))
+        })?;
+        let mv = uci.to_move(&position).map_err(|_| {
             PyValueError::new_err(format!(
-                "Unable to construct position from '{}' while checking turn: {err}",
-                fen_text
+                "Move '{move_text}' at ply {ply} is illegal in position {}",
+                fens[index]
             ))
         })?;
-        mask.push(position.turn() == target_color);
+        position.play_unchecked(&mv);
+        fens.push(canonical_position_fen(&position)?);
     }
-    Ok(mask)
+    Ok(fens)
+}
+
+/// Number of leaf nodes `depth` plies below `fen`; depth 0 counts the position
+/// itself.
+#[pyfunction]
+fn perft(fen: String, depth: u32) -> PyResult<u64> {
+    let position = parse_position(&fen)?;
+    Ok(shakmaty::perft(&position, depth))
+}
+
+/// 64-bit Zobrist hash of `fen`. Like canonical FENs it ignores the move
+/// counters and only counts en passant squares with a legal capture, so
+/// transposing move orders hash equal.
+#[pyfunction]
+fn position_hash(fen: String) -> PyResult<u64> {
+    let position = parse_position(&fen)?;
+    Ok(position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0)
+}
+
+struct PositionStatus {
+    is_check: bool,
+    is_checkmate: bool,
+    is_stalemate: bool,
+    is_insufficient_material: bool,
+    has_legal_moves: bool,
+}
+
+/// Whether `fen` is in check or terminal, as a dict of booleans.
+#[pyfunction]
+fn position_status(py: Python<'_>, fen: String) -> PyResult<Py<PyAny>> {
+    let status = collect_position_status(&fen)?;
+    let dict = PyDict::new(py);
+    dict.set_item("is_check", status.is_check)?;
+    dict.set_item("is_checkmate", status.is_checkmate)?;
+    dict.set_item("is_stalemate", status.is_stalemate)?;
+    dict.set_item("is_insufficient_material", status.is_insufficient_material)?;
+    dict.set_item("has_legal_moves", status.has_legal_moves)?;
+    Ok(dict.into())
+}
+
+fn collect_position_status(fen: &str) -> PyResult<PositionStatus> {
+    let position = parse_position(fen)?;
+    Ok(PositionStatus {
+        is_check: position.is_check(),
+        is_checkmate: position.is_checkmate(),
+        is_stalemate: position.is_stalemate(),
+        is_insufficient_material: position.is_insufficient_material(),
+        has_legal_moves: !position.legal_moves().is_empty(),
+    })
+}
+
+fn canonical_position_fen(position: &Chess) -> PyResult<String> {
+    let fen = Fen::from_position(position.clone(), EnPassantMode::Legal).to_string();
+    canonicalize_fen_str(&fen).map_err(PyValueError::new_err)
 }
 
 fn build_frequency_payload(
@@ -153,6 +442,7 @@ fn build_frequency_payload(
         entry.set_item("piece", role_symbol(fingerprint.role))?;
         entry.set_item("from_square", square_name(fingerprint.from))?;
         entry.set_item("to_square", square_name(fingerprint.to))?;
+        entry.set_item("promotion", fingerprint.promotion.map(role_symbol))?;
         entry.set_item("frequency", count)?;
         items.append(entry)?;
     }
@@ -178,17 +468,114 @@ fn build_rankings_payload(
     Ok(rankings.into())
 }
 
+/// `{fen: move_count}`, keyed like the rankings payload, so a later node for
+/// the same FEN replaces an earlier one in both.
+fn build_move_counts_payload(
+    py: Python<'_>,
+    analyzed_nodes: &[NodeAnalysis],
+) -> PyResult<Py<PyAny>> {
+    let move_counts = PyDict::new(py);
+    for node in analyzed_nodes {
+        move_counts.set_item(&node.fen, node.entries.len())?;
+    }
+    Ok(move_counts.into())
+}
+
+/// `{fen, moves}` for every FEN whose nodes give more than one distinct move,
+/// in first-seen order, with the moves ranked as in the rankings payload.
+fn build_conflicts_payload(py: Python<'_>, analyzed_nodes: &[NodeAnalysis]) -> PyResult<Py<PyAny>> {
+    let mut by_fen: Vec<(&str, Vec<&MoveEntry>)> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for node in analyzed_nodes {
+        let slot = *index.entry(node.fen.as_str()).or_insert_with(|| {
+            by_fen.push((node.fen.as_str(), Vec::new()));
+            by_fen.len() - 1
+        });
+        let entries = &mut by_fen[slot].1;
+        for entry in &node.entries {
+            if !entries.iter().any(|seen| seen.uci == entry.uci) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    let conflicts = PyList::empty(py);
+    for (fen, mut entries) in by_fen {
+        if entries.len() < 2 {
+            continue;
+        }
+        entries.sort_by(|a, b| b.frequency.cmp(&a.frequency).then(a.san.cmp(&b.san)));
+        let moves = PyList::empty(py);
+        for entry in entries {
+            let payload = PyDict::new(py);
+            payload.set_item("uci", &entry.uci)?;
+            payload.set_item("san", &entry.san)?;
+            payload.set_item("frequency", entry.frequency)?;
+            moves.append(payload)?;
+        }
+        let conflict = PyDict::new(py);
+        conflict.set_item("fen", fen)?;
+        conflict.set_item("moves", moves)?;
+        conflicts.append(conflict)?;
+    }
+    Ok(conflicts.into())
+}
+
 #[derive(FromPyObject)]
 struct PyNodeInput {
     fen: String,
     moves: Vec<String>,
 }
 
+#[derive(FromPyObject)]
+struct PyGameNodeInput {
+    fen: String,
+    moves: Vec<String>,
+    game_id: String,
+    ply: u32,
+}
+
+struct FingerprintExample {
+    fingerprint: Fingerprint,
+    frequency: u32,
+    game_id: String,
+    ply: u32,
+    uci: String,
+}
+
+fn parse_position(fen_text: &str) -> PyResult<Chess> {
+    parse_position_with_mode(fen_text, CastlingMode::Standard)
+}
+
+fn parse_position_with_mode(fen_text: &str, mode: CastlingMode) -> PyResult<Chess> {
+    position_from_fen(fen_text, mode).map_err(PyValueError::new_err)
+}
+
+fn parse_uci_move(position: &Chess, fen_text: &str, move_text: &str) -> PyResult<Move> {
+    uci_move_in(position, fen_text, move_text).map_err(PyValueError::new_err)
+}
+
+/// `parse_position_with_mode` without Python types, for the engine layer.
+fn position_from_fen(fen_text: &str, mode: CastlingMode) -> Result<Chess, String> {
+    let fen = Fen::from_str(fen_text).map_err(|err| format!("Invalid FEN '{fen_text}': {err}"))?;
+    fen.into_position(mode)
+        .map_err(|err| format!("Unable to construct position from '{fen_text}': {err}"))
+}
+
+/// `parse_uci_move` without Python types, for the engine layer.
+fn uci_move_in(position: &Chess, fen_text: &str, move_text: &str) -> Result<Move, String> {
+    let uci = UciMove::from_str(move_text)
+        .map_err(|err| format!("Invalid UCI '{move_text}' for {fen_text}: {err}"))?;
+    uci.to_move(position)
+        .map_err(|_| format!("Move '{move_text}' is illegal in position {fen_text}"))
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 struct Fingerprint {
     role: Role,
     from: Square,
     to: Square,
+    promotion: Option<Role>,
 }
 
 impl Fingerprint {
@@ -198,7 +585,12 @@ impl Fingerprint {
             .from()
             .ok_or_else(|| PyValueError::new_err("Move lacks origin square"))?;
         let to = mv.to();
-        Ok(Self { role, from, to })
+        Ok(Self {
+            role,
+            from,
+            to,
+            promotion: mv.promotion(),
+        })
     }
 }
 
@@ -229,74 +621,110 @@ fn square_name(square: Square) -> String {
     square.to_string()
 }
 
-#[derive(Clone, FromPyObject)]
-struct SplitChildInput {
-    uci: String,
-    fen: String,
+#[derive(Clone, FromPyObject, IntoPyObject, Deserialize)]
+pub struct SplitChildInput {
+    pub uci: String,
+    pub fen: String,
 }
 
-#[derive(Clone, FromPyObject)]
-struct SplitNodeInput {
-    fen: String,
-    children: Vec<SplitChildInput>,
+#[derive(Clone, FromPyObject, IntoPyObject, Deserialize)]
+pub struct SplitNodeInput {
+    pub fen: String,
+    pub children: Vec<SplitChildInput>,
 }
 
-struct SplitEventPayload {
-    fen: String,
-    prefix: Vec<String>,
-    move_count: u64,
+/// A subtree emitted by the splitter: its root, the UCI moves leading to it
+/// from the split root, and the number of moves it holds.
+pub struct SplitEventPayload {
+    pub fen: String,
+    pub prefix: Vec<String>,
+    pub move_count: u64,
 }
 
+struct SplitLimits {
+    max_moves: u64,
+    min_moves: u64,
+    max_ply: Option<u64>,
+}
+
+/// Split the repertoire into events whose subtrees hold at most `max_moves`
+/// moves. A child subtree with fewer than `min_moves` moves is never emitted on
+/// its own; it stays with its parent, which is then emitted as well, counting
+/// only the moves not split off into the larger children's events. With
+/// `max_ply`, a node that many plies from the root is always emitted, whichever
+/// limit triggers first.
 #[pyfunction]
+#[pyo3(signature = (root_fen, nodes, max_moves, min_moves=0, max_ply=None))]
 fn split_repertoire_nodes(
     root_fen: String,
     nodes: Vec<SplitNodeInput>,
     max_moves: u64,
+    min_moves: u64,
+    max_ply: Option<u64>,
 ) -> PyResult<Vec<(String, Vec<String>, u64)>> {
-    let mut node_map: HashMap<String, SplitNodeInput> = HashMap::new();
+    let events = split_nodes(&root_fen, nodes, max_moves, min_moves, max_ply)
+        .map_err(PyValueError::new_err)?;
+    Ok(events
+        .into_iter()
+        .map(|event| (event.fen, event.prefix, event.move_count))
+        .collect())
+}
+
+/// `split_repertoire_nodes` for Rust callers, such as the `split` binary.
+pub fn split_nodes(
+    root_fen: &str,
+    nodes: Vec<SplitNodeInput>,
+    max_moves: u64,
+    min_moves: u64,
+    max_ply: Option<u64>,
+) -> Result<Vec<SplitEventPayload>, String> {
+    let mut node_map: BTreeMap<String, SplitNodeInput> = BTreeMap::new();
     for node in nodes {
-        Fen::from_str(&node.fen).map_err(|err| {
-            PyValueError::new_err(format!("Invalid FEN '{}' in node list: {err}", node.fen))
-        })?;
+        Fen::from_str(&node.fen)
+            .map_err(|err| format!("Invalid FEN '{}' in node list: {err}", node.fen))?;
         node_map.insert(node.fen.clone(), node);
     }
-    let max_moves = max_moves.max(1);
-    let move_counts = compute_move_counts(&node_map)?;
+    let limits = SplitLimits {
+        max_moves: max_moves.max(1),
+        min_moves,
+        max_ply,
+    };
+    let move_counts = compute_move_counts(&node_map);
     let mut prefix_moves: Vec<String> = Vec::new();
     let mut prefix_fens: HashSet<String> = HashSet::new();
-    prefix_fens.insert(root_fen.clone());
+    prefix_fens.insert(root_fen.to_string());
     let mut events: Vec<SplitEventPayload> = Vec::new();
     split_node(
-        &root_fen,
+        root_fen,
         &node_map,
         &move_counts,
-        max_moves,
+        &limits,
         &mut prefix_moves,
         &mut prefix_fens,
         &mut events,
     )?;
-    Ok(events
-        .into_iter()
-        .map(|event| (event.fen, event.prefix, event.move_count))
-        .collect())
+    Ok(events)
 }
 
 fn split_node(
     fen: &str,
-    nodes: &HashMap<String, SplitNodeInput>,
+    nodes: &BTreeMap<String, SplitNodeInput>,
     move_counts: &HashMap<String, u64>,
-    max_moves: u64,
+    limits: &SplitLimits,
     prefix_moves: &mut Vec<String>,
     prefix_fens: &mut HashSet<String>,
     events: &mut Vec<SplitEventPayload>,
-) -> PyResult<()> {
+) -> Result<(), String> {
     let node_children = nodes.get(fen);
     let mut sorted_children: Vec<&SplitChildInput> = Vec::new();
     if let Some(node) = node_children {
         sorted_children = sort_children(node)?;
     }
     let count = *move_counts.get(fen).unwrap_or(&0);
-    if count <= max_moves || sorted_children.is_empty() {
+    let at_max_ply = limits
+        .max_ply
+        .is_some_and(|max_ply| prefix_moves.len() as u64 >= max_ply);
+    if count <= limits.max_moves || sorted_children.is_empty() || at_max_ply {
         events.push(SplitEventPayload {
             fen: fen.to_string(),
             prefix: prefix_moves.clone(),
@@ -305,17 +733,31 @@ fn split_node(
         return Ok(());
     }
 
-    for child in sorted_children {
-        if prefix_fens.contains(&child.fen) {
-            continue;
-        }
+    let (large, small): (Vec<&SplitChildInput>, Vec<&SplitChildInput>) = sorted_children
+        .into_iter()
+        .filter(|child| !prefix_fens.contains(&child.fen))
+        .partition(|child| *move_counts.get(&child.fen).unwrap_or(&0) >= limits.min_moves);
+    if !small.is_empty() || large.is_empty() {
+        // Each large child, and the move reaching it, goes to its own events.
+        let split_off: u64 = large
+            .iter()
+            .map(|child| 1 + *move_counts.get(&child.fen).unwrap_or(&0))
+            .sum();
+        events.push(SplitEventPayload {
+            fen: fen.to_string(),
+            prefix: prefix_moves.clone(),
+            move_count: count.saturating_sub(split_off),
+        });
+    }
+
+    for child in large {
         prefix_moves.push(child.uci.clone());
         prefix_fens.insert(child.fen.clone());
         split_node(
             &child.fen,
             nodes,
             move_counts,
-            max_moves,
+            limits,
             prefix_moves,
             prefix_fens,
             events,
@@ -326,71 +768,68 @@ fn split_node(
     Ok(())
 }
 
-fn sort_children(node: &SplitNodeInput) -> PyResult<Vec<&SplitChildInput>> {
-    let fen = Fen::from_str(&node.fen).map_err(|err| {
-        PyValueError::new_err(format!(
-            "Invalid FEN '{}' while sorting children: {err}",
-            node.fen
-        ))
-    })?;
+fn sort_children(node: &SplitNodeInput) -> Result<Vec<&SplitChildInput>, String> {
+    let fen = Fen::from_str(&node.fen)
+        .map_err(|err| format!("Invalid FEN '{}' while sorting children: {err}", node.fen))?;
     let position: Chess = fen.into_position(CastlingMode::Standard).map_err(|err| {
-        PyValueError::new_err(format!(
+        format!(
             "Unable to construct position from '{}' while sorting children: {err}",
             node.fen
-        ))
+        )
     })?;
     let mut decorated: Vec<(String, &SplitChildInput)> = Vec::with_capacity(node.children.len());
     for child in &node.children {
         let uci = UciMove::from_str(&child.uci).map_err(|err| {
-            PyValueError::new_err(format!(
+            format!(
                 "Invalid UCI '{}' for node {} while sorting children: {err}",
                 child.uci, node.fen
-            ))
-        })?;
-        let mv = uci.to_move(&position).map_err(|_| {
-            PyValueError::new_err(format!(
-                "Move '{}' is illegal in position {}",
-                child.uci, node.fen
-            ))
+            )
         })?;
+        let mv = uci
+            .to_move(&position)
+            .map_err(|_| format!("Move '{}' is illegal in position {}", child.uci, node.fen))?;
         let san = SanPlus::from_move(position.clone(), &mv).to_string();
         decorated.push((san, child));
     }
-    decorated.sort_by(|a, b| a.0.cmp(&b.0));
+    decorated.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then_with(|| a.1.uci.cmp(&b.1.uci))
+            .then_with(|| a.1.fen.cmp(&b.1.fen))
+    });
     Ok(decorated.into_iter().map(|(_, child)| child).collect())
 }
 
-fn compute_move_counts(nodes: &HashMap<String, SplitNodeInput>) -> PyResult<HashMap<String, u64>> {
+fn compute_move_counts(nodes: &BTreeMap<String, SplitNodeInput>) -> HashMap<String, u64> {
     let mut memo: HashMap<String, u64> = HashMap::new();
     let mut visiting: HashSet<String> = HashSet::new();
     for fen in nodes.keys() {
-        dfs_move_count(fen, nodes, &mut memo, &mut visiting)?;
+        dfs_move_count(fen, nodes, &mut memo, &mut visiting);
     }
-    Ok(memo)
+    memo
 }
 
 fn dfs_move_count(
     fen: &str,
-    nodes: &HashMap<String, SplitNodeInput>,
+    nodes: &BTreeMap<String, SplitNodeInput>,
     memo: &mut HashMap<String, u64>,
     visiting: &mut HashSet<String>,
-) -> PyResult<u64> {
+) -> u64 {
     if let Some(value) = memo.get(fen) {
-        return Ok(*value);
+        return *value;
     }
     if !visiting.insert(fen.to_string()) {
-        return Ok(0);
+        return 0;
     }
     let mut total = 0u64;
     if let Some(node) = nodes.get(fen) {
         total += node.children.len() as u64;
         for child in &node.children {
-            total += dfs_move_count(&child.fen, nodes, memo, visiting)?;
+            total += dfs_move_count(&child.fen, nodes, memo, visiting);
         }
     }
     visiting.remove(fen);
     memo.insert(fen.to_string(), total);
-    Ok(total)
+    total
 }
 
 #[cfg(test)]
@@ -417,6 +856,11 @@ mod tests {
         }
     }
 
+    /// `player_move_analysis` without conflict reporting, as `(frequencies, rankings)`.
+    fn analyze(py: Python<'_>, nodes: Vec<PyNodeInput>) -> PyResult<(Py<PyAny>, Py<PyAny>)> {
+        player_move_analysis(py, nodes, false, false)?.extract()
+    }
+
     #[test]
     fn player_move_analysis_counts_frequencies() {
         initialize_python();
@@ -426,7 +870,7 @@ mod tests {
                 node(START_FEN, &["g1f3"]),
             ];
 
-            let (freq_obj, rankings_obj) = player_move_analysis(py, nodes).unwrap();
+            let (freq_obj, rankings_obj) = analyze(py, nodes).unwrap();
 
             let freq_list = freq_obj
                 .into_bound(py)
@@ -501,12 +945,150 @@ mod tests {
         });
     }
 
+    #[test]
+    fn player_move_analysis_reports_move_count_per_node() {
+        initialize_python();
+        Python::attach(|py| {
+            let after_e4_e5 = next_fen(START_FEN, &["e2e4", "e7e5"]);
+            let nodes = vec![
+                node(START_FEN, &["g1f3", "e2e4"]),
+                node(&after_e4_e5, &["g1f3"]),
+            ];
+
+            let result = player_move_analysis(py, nodes, false, true).unwrap();
+            assert_eq!(result.len(), 3);
+            let move_counts: HashMap<String, usize> = result
+                .get_item(2)
+                .expect("move counts item")
+                .extract()
+                .expect("move counts dict");
+            assert_eq!(
+                move_counts,
+                HashMap::from([(START_FEN.to_string(), 2), (after_e4_e5, 1)])
+            );
+
+            let rankings = result
+                .get_item(1)
+                .expect("rankings item")
+                .cast_into::<PyDict>()
+                .expect("rankings dict");
+            let first_move = rankings
+                .get_item(START_FEN)
+                .expect("fen lookup")
+                .expect("fen value")
+                .cast_into::<PyList>()
+                .expect("moves list")
+                .get_item(0)
+                .expect("first move")
+                .cast_into::<PyDict>()
+                .expect("move dict");
+            assert!(first_move.get_item("move_count").expect("lookup").is_none());
+        });
+    }
+
+    #[test]
+    fn player_move_analysis_counts_promotions_separately() {
+        initialize_python();
+        Python::attach(|py| {
+            let promotion_fen = "8/4P3/8/8/8/8/8/k6K w - - 0 1";
+            let nodes = vec![
+                node(promotion_fen, &["e7e8q", "e7e8n"]),
+                node(promotion_fen, &["e7e8q"]),
+            ];
+
+            let (freq_obj, _) = analyze(py, nodes).unwrap();
+
+            let freq_list = freq_obj
+                .into_bound(py)
+                .cast_into::<PyList>()
+                .expect("frequency list");
+            let mut by_promotion = HashMap::new();
+            for entry in freq_list.iter() {
+                let entry = entry.cast::<PyDict>().expect("freq entry dict");
+                let promotion: Option<String> = entry
+                    .get_item("promotion")
+                    .expect("promotion lookup")
+                    .expect("promotion value")
+                    .extract()
+                    .expect("promotion str");
+                let count: u32 = entry
+                    .get_item("frequency")
+                    .expect("frequency lookup")
+                    .expect("frequency value")
+                    .extract()
+                    .expect("count");
+                by_promotion.insert(promotion, count);
+            }
+            assert_eq!(by_promotion.len(), 2);
+            assert_eq!(by_promotion.get(&Some("Q".to_string())), Some(&2));
+            assert_eq!(by_promotion.get(&Some("N".to_string())), Some(&1));
+        });
+    }
+
+    #[test]
+    fn player_move_analysis_reports_nodes_with_several_player_moves() {
+        initialize_python();
+        Python::attach(|py| {
+            let after_e4_e5 = next_fen(START_FEN, &["e2e4", "e7e5"]);
+            let nodes = vec![
+                node(START_FEN, &["e2e4"]),
+                node(&after_e4_e5, &["g1f3", "f1c4"]),
+                node(START_FEN, &["e2e4"]),
+            ];
+
+            let result = player_move_analysis(py, nodes, true, false).unwrap();
+            assert_eq!(result.len(), 3);
+            let conflicts = result
+                .get_item(2)
+                .expect("conflicts item")
+                .cast_into::<PyList>()
+                .expect("conflicts list");
+            assert_eq!(conflicts.len(), 1);
+            let conflict = conflicts
+                .get_item(0)
+                .expect("conflict lookup")
+                .cast_into::<PyDict>()
+                .expect("conflict dict");
+            let fen: String = conflict
+                .get_item("fen")
+                .expect("fen lookup")
+                .expect("fen value")
+                .extract()
+                .expect("fen str");
+            assert_eq!(fen, after_e4_e5);
+            let sans: Vec<String> = conflict
+                .get_item("moves")
+                .expect("moves lookup")
+                .expect("moves value")
+                .cast_into::<PyList>()
+                .expect("moves list")
+                .iter()
+                .map(|entry| {
+                    entry
+                        .cast::<PyDict>()
+                        .expect("move dict")
+                        .get_item("san")
+                        .expect("san lookup")
+                        .expect("san value")
+                        .extract::<String>()
+                        .expect("san str")
+                })
+                .collect();
+            assert_eq!(sans, vec!["Bc4", "Nf3"]);
+
+            let plain =
+                player_move_analysis(py, vec![node(START_FEN, &["e2e4", "d2d4"])], false, false)
+                    .unwrap();
+            assert_eq!(plain.len(), 2);
+        });
+    }
+
     #[test]
     fn player_move_analysis_rejects_invalid_fen() {
         initialize_python();
         Python::attach(|py| {
             let nodes = vec![node("not a fen", &["e2e4"])];
-            let err = player_move_analysis(py, nodes).unwrap_err();
+            let err = analyze(py, nodes).unwrap_err();
             assert!(err.is_instance_of::<PyValueError>(py));
         });
     }
@@ -516,7 +1098,7 @@ mod tests {
         initialize_python();
         Python::attach(|py| {
             let nodes = vec![node(START_FEN, &["badmove"])];
-            let err = player_move_analysis(py, nodes).unwrap_err();
+            let err = analyze(py, nodes).unwrap_err();
             assert!(err.is_instance_of::<PyValueError>(py));
         });
     }
@@ -526,7 +1108,7 @@ mod tests {
         initialize_python();
         Python::attach(|py| {
             let nodes = vec![node(START_FEN, &["e2e5"])];
-            let err = player_move_analysis(py, nodes).unwrap_err();
+            let err = analyze(py, nodes).unwrap_err();
             assert!(err.is_instance_of::<PyValueError>(py));
         });
     }
@@ -554,6 +1136,240 @@ mod tests {
         });
     }
 
+    #[test]
+    fn legal_moves_lists_uci_and_san_sorted_by_san() {
+        let moves = legal_moves(START_FEN.to_string()).unwrap();
+        assert_eq!(moves.len(), 20);
+        assert_eq!(moves[0], ("b1a3".to_string(), "Na3".to_string()));
+        assert!(moves.contains(&("g1f3".to_string(), "Nf3".to_string())));
+        let sans: Vec<&String> = moves.iter().map(|(_, san)| san).collect();
+        assert!(sans.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn legal_moves_rejects_invalid_fen() {
+        initialize_python();
+        let err = legal_moves("bad fen".to_string()).unwrap_err();
+        Python::attach(|py| {
+            assert!(err.is_instance_of::<PyValueError>(py));
+        });
+    }
+
+    #[test]
+    fn san_and_uci_convert_castling_and_promotion() {
+        let castling = "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        assert_eq!(
+            san_to_uci(castling.to_string(), "O-O".to_string()).unwrap(),
+            "e1g1"
+        );
+        assert_eq!(
+            uci_to_san(castling.to_string(), "e1g1".to_string()).unwrap(),
+            "O-O"
+        );
+
+        let promotion = "8/4P3/8/8/8/8/k7/4K3 w - - 0 1";
+        assert_eq!(
+            san_to_uci(promotion.to_string(), "e8=N".to_string()).unwrap(),
+            "e7e8n"
+        );
+        assert_eq!(
+            uci_to_san(promotion.to_string(), "e7e8q".to_string()).unwrap(),
+            "e8=Q"
+        );
+
+        initialize_python();
+        let err = san_to_uci(castling.to_string(), "O-O-O".to_string()).unwrap_err();
+        assert!(err.to_string().contains("O-O-O"));
+        let err = uci_to_san(castling.to_string(), "zz".to_string()).unwrap_err();
+        assert!(err.to_string().contains("zz"));
+    }
+
+    #[test]
+    fn game_fens_returns_start_and_every_ply() {
+        let moves = vec!["e2e4".to_string(), "e7e5".to_string(), "g1f3".to_string()];
+        let fens = game_fens(None, moves).unwrap();
+        let expected: Vec<String> = [
+            START_FEN.to_string(),
+            next_fen(START_FEN, &["e2e4"]),
+            next_fen(START_FEN, &["e2e4", "e7e5"]),
+            next_fen(START_FEN, &["e2e4", "e7e5", "g1f3"]),
+        ]
+        .iter()
+        .map(|fen| canonicalize_fen_str(fen).unwrap())
+        .collect();
+        assert_eq!(fens, expected);
+
+        initialize_python();
+        let err = game_fens(None, vec!["e2e4".to_string(), "e2e4".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("at ply 2"));
+    }
+
+    #[test]
+    fn perft_matches_known_node_counts() {
+        assert_eq!(perft(START_FEN.to_string(), 0).unwrap(), 1);
+        assert_eq!(perft(START_FEN.to_string(), 3).unwrap(), 8902);
+        // "Kiwipete" exercises castling, en passant and promotions.
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(perft(kiwipete.to_string(), 1).unwrap(), 48);
+        assert_eq!(perft(kiwipete.to_string(), 2).unwrap(), 2039);
+        assert!(perft("bad fen".to_string(), 1).is_err());
+    }
+
+    #[test]
+    fn position_hash_matches_across_transpositions() {
+        let direct = next_fen(START_FEN, &["e2e4", "e7e5", "g1f3", "b8c6"]);
+        let transposed = next_fen(START_FEN, &["g1f3", "b8c6", "e2e4", "e7e5"]);
+        assert_ne!(direct, transposed);
+        assert_eq!(
+            position_hash(direct.clone()).unwrap(),
+            position_hash(transposed).unwrap()
+        );
+        assert_ne!(
+            position_hash(direct).unwrap(),
+            position_hash(next_fen(START_FEN, &["e2e4", "e7e5"])).unwrap()
+        );
+    }
+
+    #[test]
+    fn position_status_flags_mate_and_stalemate() {
+        let back_rank_mate = collect_position_status("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert!(back_rank_mate.is_check);
+        assert!(back_rank_mate.is_checkmate);
+        assert!(!back_rank_mate.is_stalemate);
+        assert!(!back_rank_mate.has_legal_moves);
+
+        let stalemate = collect_position_status("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!stalemate.is_check);
+        assert!(!stalemate.is_checkmate);
+        assert!(stalemate.is_stalemate);
+        assert!(!stalemate.is_insufficient_material);
+
+        let bare_kings = collect_position_status("8/8/4k3/8/8/4K3/8/8 w - - 0 1").unwrap();
+        assert!(bare_kings.is_insufficient_material);
+        assert!(bare_kings.has_legal_moves);
+
+        initialize_python();
+        Python::attach(|py| {
+            let status = position_status(py, START_FEN.to_string()).unwrap();
+            let status = status.bind(py).cast::<PyDict>().unwrap();
+            let has_moves: bool = status
+                .get_item("has_legal_moves")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert!(has_moves);
+            assert!(position_status(py, "bad fen".to_string()).is_err());
+        });
+    }
+
+    #[test]
+    fn canonicalize_fens_matches_single_calls_and_reports_failing_index() {
+        let fens = vec![
+            START_FEN.replace(" 0 1", " 4 9"),
+            next_fen(START_FEN, &["e2e4"]),
+            next_fen(START_FEN, &["e2e4", "e7e5", "g1f3"]),
+        ];
+        let expected: Vec<String> = fens
+            .iter()
+            .map(|fen| canonicalize_fen(fen.clone(), true, false).unwrap())
+            .collect();
+        assert_eq!(canonicalize_fens(fens.clone()).unwrap(), expected);
+
+        let mut with_bad = fens;
+        with_bad.insert(2, "bad fen".to_string());
+        let err = canonicalize_fens(with_bad).unwrap_err();
+        initialize_python();
+        let message = err.to_string();
+        assert!(message.contains("index 2"), "{message}");
+        assert!(message.contains("bad fen"), "{message}");
+    }
+
+    #[test]
+    fn canonicalize_fen_can_keep_move_counters() {
+        let midgame = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 7 12";
+        assert_eq!(
+            canonicalize_fen(midgame.to_string(), true, false).unwrap(),
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1"
+        );
+        assert_eq!(
+            canonicalize_fen(midgame.to_string(), false, false).unwrap(),
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 7 12"
+        );
+    }
+
+    #[test]
+    fn canonicalize_fen_accepts_chess960_castling_in_960_mode() {
+        let shuffled = "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 3 7";
+        initialize_python();
+        assert!(canonicalize_fen(shuffled.to_string(), true, false).is_err());
+
+        // Shredder-FEN rights come back in X-FEN form: both rooks are outermost.
+        let canonical = canonicalize_fen(shuffled.to_string(), true, true).unwrap();
+        assert_eq!(
+            canonical,
+            "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w KQkq - 0 1"
+        );
+        assert_eq!(
+            canonicalize_fen(canonical.clone(), true, true).unwrap(),
+            canonical
+        );
+        assert_eq!(
+            canonicalize_fen(shuffled.to_string(), false, true).unwrap(),
+            canonical.replace(" 0 1", " 3 7")
+        );
+    }
+
+    #[test]
+    fn is_canonical_accepts_canonicalized_fen() {
+        let canonical =
+            canonicalize_fen(next_fen(START_FEN, &["e2e4", "e7e5"]), true, false).unwrap();
+        assert!(is_canonical(canonical).unwrap());
+    }
+
+    #[test]
+    fn is_canonical_rejects_nonzero_halfmove_clock() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1".to_string();
+        assert!(!is_canonical(fen).unwrap());
+    }
+
+    #[test]
+    fn fingerprint_examples_reference_first_occurrence() {
+        let after_e4 = next_fen(START_FEN, &["e2e4"]);
+        let after_e4_e5 = next_fen(&after_e4, &["e7e5"]);
+        let nodes = vec![
+            PyGameNodeInput {
+                fen: START_FEN.to_string(),
+                moves: vec!["e2e4".to_string()],
+                game_id: "game-1".to_string(),
+                ply: 0,
+            },
+            PyGameNodeInput {
+                fen: after_e4_e5.clone(),
+                moves: vec!["g1f3".to_string()],
+                game_id: "game-1".to_string(),
+                ply: 2,
+            },
+            PyGameNodeInput {
+                fen: after_e4_e5,
+                moves: vec!["g1f3".to_string()],
+                game_id: "game-2".to_string(),
+                ply: 2,
+            },
+        ];
+
+        let examples = collect_fingerprint_examples(nodes).unwrap();
+        assert_eq!(examples.len(), 2);
+        let knight = &examples[0];
+        assert_eq!(knight.fingerprint.role, Role::Knight);
+        assert_eq!(knight.frequency, 2);
+        assert_eq!(knight.game_id, "game-1");
+        assert_eq!(knight.ply, 2);
+        assert_eq!(knight.uci, "g1f3");
+        assert_eq!(examples[1].game_id, "game-1");
+        assert_eq!(examples[1].ply, 0);
+    }
+
     fn ensure_edge(
         map: &mut HashMap<String, SplitNodeInput>,
         from_fen: &str,
@@ -614,7 +1430,7 @@ mod tests {
     #[test]
     fn split_repertoire_nodes_generates_expected_prefixes() {
         let nodes = build_shared_prefix_nodes();
-        let events = split_repertoire_nodes(START_FEN.to_string(), nodes, 3).unwrap();
+        let events = split_repertoire_nodes(START_FEN.to_string(), nodes, 3, 0, None).unwrap();
         assert_eq!(events.len(), 7);
         let mut seen_suffixes = std::collections::HashSet::new();
         for (_, prefix, _) in events {
@@ -630,6 +1446,134 @@ mod tests {
         assert_eq!(seen_suffixes, expected);
     }
 
+    #[test]
+    fn main_line_follows_most_frequent_moves_with_san_tiebreak() {
+        let common = ["e2e4", "e7e5", "g1f3", "b8c6", "b1c3", "g8f6"];
+        let branch = next_fen(START_FEN, &common);
+
+        let nodes = build_shared_prefix_nodes();
+        let mut expected: Vec<String> = common.iter().map(|mv| mv.to_string()).collect();
+        expected.push("f1b5".to_string());
+        assert_eq!(
+            main_line(START_FEN.to_string(), nodes, true).unwrap(),
+            expected
+        );
+
+        // A second h-pawn push elsewhere makes h2h3 the most frequent move at the branch.
+        let mut map: HashMap<String, SplitNodeInput> = HashMap::new();
+        let after_bb5 = next_fen(&branch, &["f1b5"]);
+        let after_a6 = next_fen(&after_bb5, &["a7a6"]);
+        ensure_edge(&mut map, &after_bb5, "a7a6", &after_a6);
+        ensure_edge(&mut map, &after_a6, "h2h3", &next_fen(&after_a6, &["h2h3"]));
+        let mut nodes = build_shared_prefix_nodes();
+        nodes.extend(map.into_values());
+        *expected.last_mut().unwrap() = "h2h3".to_string();
+        assert_eq!(
+            main_line(START_FEN.to_string(), nodes, true).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn split_repertoire_nodes_keeps_small_children_with_their_parent() {
+        let lines: [&[&str]; 4] = [
+            &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"],
+            &["e2e4", "c7c5", "g1f3", "d7d6", "d2d4"],
+            &["d2d4"],
+            &["c2c4", "c7c5"],
+        ];
+        let mut map: HashMap<String, SplitNodeInput> = HashMap::new();
+        for line in lines {
+            let mut current_fen = START_FEN.to_string();
+            for &mv in line {
+                let next = next_fen(&current_fen, std::slice::from_ref(&mv));
+                ensure_edge(&mut map, &current_fen, mv, &next);
+                current_fen = next;
+            }
+        }
+        let nodes: Vec<SplitNodeInput> = map.into_values().collect();
+
+        let split = |min_moves: u64| -> Vec<(Vec<String>, u64)> {
+            split_repertoire_nodes(START_FEN.to_string(), nodes.clone(), 3, min_moves, None)
+                .unwrap()
+                .into_iter()
+                .map(|(_, prefix, move_count)| (prefix, move_count))
+                .collect()
+        };
+        let unfloored: Vec<Vec<String>> = split(0).into_iter().map(|(prefix, _)| prefix).collect();
+        assert!(unfloored.contains(&vec!["d2d4".to_string()]));
+        assert!(unfloored.contains(&vec!["c2c4".to_string()]));
+
+        // The root keeps d2d4, c2c4 and c7c5; 1. e4 and its subtree are split off.
+        let floored = split(2);
+        assert_eq!(
+            floored,
+            vec![
+                (Vec::<String>::new(), 3),
+                (vec!["e2e4".to_string(), "c7c5".to_string()], 3),
+                (vec!["e2e4".to_string(), "e7e5".to_string()], 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_repertoire_nodes_splits_at_max_ply() {
+        let line = [
+            "e2e4", "c7c5", "g1f3", "d7d6", "d2d4", "c5d4", "f3d4", "g8f6", "b1c3", "a7a6",
+        ];
+        let mut map: HashMap<String, SplitNodeInput> = HashMap::new();
+        let mut current_fen = START_FEN.to_string();
+        for mv in line {
+            let next = next_fen(&current_fen, &[mv]);
+            ensure_edge(&mut map, &current_fen, mv, &next);
+            current_fen = next;
+        }
+        let nodes: Vec<SplitNodeInput> = map.into_values().collect();
+
+        let unlimited =
+            split_repertoire_nodes(START_FEN.to_string(), nodes.clone(), 100, 0, None).unwrap();
+        assert_eq!(unlimited.len(), 1);
+        assert!(unlimited[0].1.is_empty());
+
+        // max_moves alone would still split deeper; max_ply cuts first.
+        let events = split_repertoire_nodes(START_FEN.to_string(), nodes, 1, 0, Some(4)).unwrap();
+        assert_eq!(events.len(), 1);
+        let (fen, prefix, move_count) = &events[0];
+        assert_eq!(prefix, &line[..4]);
+        assert_eq!(fen, &next_fen(START_FEN, &line[..4]));
+        assert_eq!(*move_count, 6);
+    }
+
+    #[test]
+    fn split_repertoire_nodes_is_deterministic() {
+        let mut map: HashMap<String, SplitNodeInput> = build_shared_prefix_nodes()
+            .into_iter()
+            .map(|node| (node.fen.clone(), node))
+            .collect();
+        let after_d4 = next_fen(START_FEN, &["d2d4"]);
+        let after_d5 = next_fen(&after_d4, &["d7d5"]);
+        ensure_edge(&mut map, START_FEN, "d2d4", &after_d4);
+        ensure_edge(&mut map, &after_d4, "d7d5", &after_d5);
+        // Looping back into the tree makes move counts depend on visiting order.
+        ensure_edge(&mut map, &after_d5, "c1f4", START_FEN);
+        let nodes: Vec<SplitNodeInput> = map.into_values().collect();
+
+        let first =
+            split_repertoire_nodes(START_FEN.to_string(), nodes.clone(), 3, 0, None).unwrap();
+        for _ in 0..5 {
+            let mut shuffled = nodes.clone();
+            shuffled.reverse();
+            assert_eq!(
+                split_repertoire_nodes(START_FEN.to_string(), nodes.clone(), 3, 0, None).unwrap(),
+                first
+            );
+            assert_eq!(
+                split_repertoire_nodes(START_FEN.to_string(), shuffled, 3, 0, None).unwrap(),
+                first
+            );
+        }
+    }
+
     #[test]
     fn split_repertoire_nodes_rejects_invalid_fen() {
         let nodes = vec![SplitNodeInput {
@@ -639,7 +1583,7 @@ mod tests {
                 fen: START_FEN.to_string(),
             }],
         }];
-        let err = split_repertoire_nodes(START_FEN.to_string(), nodes, 5).unwrap_err();
+        let err = split_repertoire_nodes(START_FEN.to_string(), nodes, 5, 0, None).unwrap_err();
         Python::attach(|py| {
             assert!(err.is_instance_of::<PyValueError>(py));
         });
@@ -652,7 +1596,7 @@ mod tests {
         ensure_edge(&mut map, START_FEN, "e2e4", &second_fen);
         ensure_edge(&mut map, &second_fen, "e7e5", START_FEN);
         let nodes: Vec<SplitNodeInput> = map.into_values().collect();
-        let events = split_repertoire_nodes(START_FEN.to_string(), nodes, 1).unwrap();
+        let events = split_repertoire_nodes(START_FEN.to_string(), nodes, 1, 0, None).unwrap();
         assert!(!events.is_empty());
     }
 }
@@ -0,0 +1,514 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Move, Position};
+
+use std::collections::HashSet;
+
+use crate::canonical_position_fen;
+
+/// A game's headers, moves and result, taken from the `Result` header or,
+/// failing that, the movetext's termination marker.
+pub struct PgnGame {
+    /// Every header tag in file order, `Result` included.
+    pub headers: Vec<(String, String)>,
+    pub result: Option<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+/// A move, the NAGs (`$1`, `$2`, ...) and `{...}` comments that follow it, and
+/// the alternative lines that branch off in its place.
+pub struct PgnMove {
+    pub san: SanPlus,
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+/// Parse every game in the file, keeping headers, comments and variations
+/// (RAV), each attached to the move it follows or replaces. Comments with no
+/// move before them in their line are dropped. A game ends at its result token
+/// or when the next game's headers start.
+pub fn parse_games(text: &str) -> Result<Vec<PgnGame>, String> {
+    let mut parser = GameParser::default();
+    let mut in_headers = false;
+    for text_line in text.lines() {
+        let trimmed = text_line.trim_start();
+        if parser.comment_depth == 0 && trimmed.starts_with('[') {
+            if !in_headers {
+                parser.finish_game();
+                in_headers = true;
+            }
+            let mut rest = trimmed;
+            while let Some((name, value, after)) = parse_tag(rest) {
+                if name == "Result" {
+                    parser.result = Some(value.clone());
+                }
+                parser.headers.push((name.to_string(), value));
+                rest = after;
+            }
+            continue;
+        }
+        for raw in text_line.split_whitespace() {
+            in_headers = false;
+            parser.movetext_token(raw)?;
+        }
+    }
+    parser.finish_game();
+    Ok(parser.games)
+}
+
+/// State of `parse_games` between tokens: the games so far and the headers,
+/// open lines and comment of the game being read.
+struct GameParser {
+    games: Vec<PgnGame>,
+    headers: Vec<(String, String)>,
+    result: Option<String>,
+    /// `lines[0]` is the mainline; every open variation pushes another line.
+    lines: Vec<Vec<PgnMove>>,
+    comment_depth: u32,
+    comment: String,
+}
+
+impl Default for GameParser {
+    fn default() -> Self {
+        Self {
+            games: Vec::new(),
+            headers: Vec::new(),
+            result: None,
+            lines: vec![Vec::new()],
+            comment_depth: 0,
+            comment: String::new(),
+        }
+    }
+}
+
+impl GameParser {
+    /// Apply one whitespace-separated movetext token: moves, NAGs, variation
+    /// marks, comments and the result that ends a game. A comment carries its
+    /// brace depth across tokens, so multi-word comments are kept whole; stray
+    /// `}` are ignored.
+    fn movetext_token(&mut self, raw: &str) -> Result<(), String> {
+        let mut kept = String::new();
+        for c in raw.chars() {
+            match c {
+                '{' if self.comment_depth == 0 => {
+                    self.moves_text(&std::mem::take(&mut kept))?;
+                    self.comment_depth = 1;
+                }
+                '}' if self.comment_depth == 1 => {
+                    self.comment_depth = 0;
+                    self.attach_comment();
+                }
+                '}' if self.comment_depth == 0 => {}
+                _ if self.comment_depth > 0 => {
+                    match c {
+                        '{' => self.comment_depth += 1,
+                        '}' => self.comment_depth -= 1,
+                        _ => {}
+                    }
+                    self.comment.push(c);
+                }
+                _ => kept.push(c),
+            }
+        }
+        if self.comment_depth > 0 {
+            self.comment.push(' ');
+        }
+        self.moves_text(&kept)
+    }
+
+    /// Apply the part of a token outside comments.
+    fn moves_text(&mut self, raw: &str) -> Result<(), String> {
+        for token in split_variation_marks(raw) {
+            let token = strip_move_number(token);
+            match token {
+                "(" => self.lines.push(Vec::new()),
+                ")" => close_variation(&mut self.lines),
+                _ if token.starts_with('$') => {
+                    let nag = token[1..].parse::<u8>().ok();
+                    if let (Some(nag), Some(annotated)) = (nag, self.last_move()) {
+                        annotated.nags.push(nag);
+                    }
+                }
+                "" => {}
+                "*" | "1-0" | "0-1" | "1/2-1/2" => {
+                    self.result.get_or_insert_with(|| token.to_string());
+                    self.finish_game();
+                }
+                _ => {
+                    let san = SanPlus::from_ascii(token.as_bytes())
+                        .map_err(|err| format!("Invalid SAN token in PGN: {token} ({err})"))?;
+                    self.lines.last_mut().expect("mainline").push(PgnMove {
+                        san,
+                        nags: Vec::new(),
+                        comments: Vec::new(),
+                        variations: Vec::new(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn last_move(&mut self) -> Option<&mut PgnMove> {
+        self.lines.last_mut().and_then(|line| line.last_mut())
+    }
+
+    /// Attach the comment just closed to the last move of the current line.
+    fn attach_comment(&mut self) {
+        let comment = std::mem::take(&mut self.comment);
+        let comment = comment.trim();
+        if let Some(commented) = self.last_move()
+            && !comment.is_empty()
+        {
+            commented.comments.push(comment.to_string());
+        }
+    }
+
+    /// Close any open variations and move a non-empty mainline into `games`.
+    /// The pending headers and result are consumed either way, so they never
+    /// leak into the next game.
+    fn finish_game(&mut self) {
+        while self.lines.len() > 1 {
+            close_variation(&mut self.lines);
+        }
+        let moves = std::mem::take(&mut self.lines[0]);
+        let headers = std::mem::take(&mut self.headers);
+        let result = self.result.take();
+        if !moves.is_empty() {
+            self.games.push(PgnGame {
+                headers,
+                result,
+                moves,
+            });
+        }
+    }
+}
+
+/// Split one `[Name "value"]` tag off the start of `text`, unescaping `\"` and
+/// `\\` in the value, and return the text after it.
+fn parse_tag(text: &str) -> Option<(&str, String, &str)> {
+    let body = text.trim_start().strip_prefix('[')?.trim_start();
+    let name_end = body.find(|c: char| c.is_whitespace() || c == '"')?;
+    let (name, rest) = body.split_at(name_end);
+    let quoted = rest.trim_start().strip_prefix('"')?;
+    let mut chars = quoted.char_indices();
+    let mut value = String::new();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next().map(|(_, escaped)| escaped)),
+            '"' => {
+                let after = quoted[idx + 1..].trim_start().strip_prefix(']')?;
+                return Some((name, value, after));
+            }
+            _ => value.push(c),
+        }
+    }
+    None
+}
+
+/// Split `(` and `)` off a token, e.g. `(1...` or `Nf3))`.
+fn split_variation_marks(raw: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (idx, c) in raw.char_indices() {
+        if c == '(' || c == ')' {
+            if start < idx {
+                pieces.push(&raw[start..idx]);
+            }
+            pieces.push(&raw[idx..idx + 1]);
+            start = idx + 1;
+        }
+    }
+    if start < raw.len() {
+        pieces.push(&raw[start..]);
+    }
+    pieces
+}
+
+/// Drop a move number glued to a move, e.g. `1.e4` or `3...Nf6`; a bare
+/// `12.` or `12...` becomes empty.
+fn strip_move_number(token: &str) -> &str {
+    let digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if digits.len() < token.len() && digits.starts_with('.') {
+        digits.trim_start_matches('.')
+    } else {
+        token
+    }
+}
+
+/// Attach the innermost open variation to the move it replaces. Stray `)` and
+/// variations with nothing to replace are dropped.
+fn close_variation(lines: &mut Vec<Vec<PgnMove>>) {
+    if lines.len() < 2 {
+        return;
+    }
+    let variation = lines.pop().unwrap_or_default();
+    if let Some(replaced) = lines.last_mut().and_then(|line| line.last_mut())
+        && !variation.is_empty()
+    {
+        replaced.variations.push(variation);
+    }
+}
+
+/// Write `game` back out as PGN: its headers, with `Result` first when the game
+/// has none, and its movetext with NAGs, comments and variations.
+pub fn format_game(game: &PgnGame) -> String {
+    write_game(game, &[], 0)
+}
+
+/// `format_game` for a game starting at `setup`. A position other than the
+/// standard start gets `FEN` and `SetUp` headers, and moves are numbered from
+/// its move counter.
+pub fn format_game_from(game: &PgnGame, setup: &Chess) -> String {
+    if *setup == Chess::new() {
+        return format_game(game);
+    }
+    let fen = Fen::from_position(setup.clone(), EnPassantMode::Legal).to_string();
+    let start_ply =
+        (setup.fullmoves().get() as usize - 1) * 2 + usize::from(setup.turn() == Color::Black);
+    write_game(game, &[("FEN", fen), ("SetUp", "1".to_string())], start_ply)
+}
+
+/// Write the headers, replacing the game's own `FEN` and `SetUp` when
+/// `setup_headers` are given, then the movetext from `start_ply`.
+fn write_game(game: &PgnGame, setup_headers: &[(&str, String)], start_ply: usize) -> String {
+    let result = game.result.as_deref().unwrap_or("*");
+    let mut text = String::new();
+    if !game.headers.iter().any(|(name, _)| name == "Result") {
+        write_tag(&mut text, "Result", result);
+    }
+    for (name, value) in &game.headers {
+        match name.as_str() {
+            "Result" => write_tag(&mut text, name, result),
+            "FEN" | "SetUp" if !setup_headers.is_empty() => {}
+            _ => write_tag(&mut text, name, value),
+        }
+    }
+    for (name, value) in setup_headers {
+        write_tag(&mut text, name, value);
+    }
+    text.push('\n');
+    write_line(&mut text, &game.moves, start_ply);
+    text.push_str(result);
+    text.push('\n');
+    text
+}
+
+/// Append `[Name "value"]`, escaping `\` and `"` in the value.
+fn write_tag(out: &mut String, name: &str, value: &str) {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    out.push_str(&format!("[{name} \"{value}\"]\n"));
+}
+
+/// Append `line`, whose first move is played at `start_ply`, to `out`. Black
+/// moves get a `N...` number at the start of a line and after a comment or a
+/// variation.
+fn write_line(out: &mut String, line: &[PgnMove], start_ply: usize) {
+    let mut needs_number = true;
+    for (offset, pgn_move) in line.iter().enumerate() {
+        let ply = start_ply + offset;
+        let move_number = ply / 2 + 1;
+        if ply.is_multiple_of(2) {
+            out.push_str(&format!("{move_number}. "));
+        } else if needs_number {
+            out.push_str(&format!("{move_number}... "));
+        }
+        out.push_str(&pgn_move.san.to_string());
+        for nag in &pgn_move.nags {
+            out.push_str(&format!(" ${nag}"));
+        }
+        for comment in &pgn_move.comments {
+            out.push_str(&format!(" {{ {comment} }}"));
+        }
+        out.push(' ');
+        needs_number = !pgn_move.comments.is_empty() || !pgn_move.variations.is_empty();
+        for variation in &pgn_move.variations {
+            out.push('(');
+            write_line(out, variation, ply);
+            out.truncate(out.trim_end().len());
+            out.push_str(") ");
+        }
+    }
+}
+
+/// `(parent_fen, uci, child_fen)` for every mainline move of every game in
+/// `pgn`, with canonical FENs. Comments, NAGs and variations are skipped.
+#[pyfunction]
+pub fn pgn_to_fens(pgn: String) -> PyResult<Vec<(String, String, String)>> {
+    let games = parse_games(&pgn).map_err(PyValueError::new_err)?;
+    let mut triples: Vec<(String, String, String)> = Vec::new();
+    for game in &games {
+        let mut position = Chess::new();
+        let mut parent = canonical_position_fen(&position)?;
+        for pgn_move in &game.moves {
+            let mv = play_san(&position, pgn_move)?;
+            position.play_unchecked(&mv);
+            let child = canonical_position_fen(&position)?;
+            let uci = UciMove::from_move(&mv, CastlingMode::Standard).to_string();
+            triples.push((parent, uci, child.clone()));
+            parent = child;
+        }
+    }
+    Ok(triples)
+}
+
+/// Number of distinct canonical FENs visited along the mainlines of every game
+/// in `pgn_text`, including the starting position.
+#[pyfunction]
+pub fn unique_positions(pgn_text: String) -> PyResult<u64> {
+    let games = parse_games(&pgn_text).map_err(PyValueError::new_err)?;
+    let mut seen: HashSet<String> = HashSet::new();
+    for game in &games {
+        let mut position = Chess::new();
+        seen.insert(canonical_position_fen(&position)?);
+        for pgn_move in &game.moves {
+            let mv = play_san(&position, pgn_move)?;
+            position.play_unchecked(&mv);
+            seen.insert(canonical_position_fen(&position)?);
+        }
+    }
+    Ok(seen.len() as u64)
+}
+
+fn play_san(position: &Chess, pgn_move: &PgnMove) -> PyResult<Move> {
+    pgn_move.san.san.to_move(position).map_err(|err| {
+        PyValueError::new_err(format!("Illegal move {} in PGN: {err}", pgn_move.san))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_games_splits_games_and_keeps_variations() {
+        let games = parse_games(
+            "[Result \"1-0\"]\n\n1. e4 {best by test} e5 (1... c5 2. Nf3) 2. Nf3 1-0\n\n1. d4 *",
+        )
+        .unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].result.as_deref(), Some("1-0"));
+        assert_eq!(games[0].moves.len(), 3);
+        assert_eq!(games[0].moves[1].variations[0].len(), 2);
+        assert_eq!(games[1].result.as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn parse_games_reads_header_values_with_spaces() {
+        let pgn = "[Event \"Rated Blitz game\"]\n\
+                   [Site \"https://lichess.org/abcdefgh\"]\n\
+                   [Result \"0-1\"]\n\
+                   [Opening \"Sicilian Defense: \\\"Open\\\" [B50]\"]\n\
+                   \n\
+                   1. e4 c5 0-1\n\
+                   \n\
+                   [Event \"Casual Rapid game\"] [Result \"1/2-1/2\"]\n\
+                   \n\
+                   1. d4 d5 { a [bracketed] comment\n\
+                   [spanning] lines } 2. c4 1/2-1/2\n";
+        let games = parse_games(pgn).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].result.as_deref(), Some("0-1"));
+        assert_eq!(games[0].moves.len(), 2);
+        assert_eq!(games[1].result.as_deref(), Some("1/2-1/2"));
+        assert_eq!(games[1].moves.len(), 3);
+    }
+
+    #[test]
+    fn parse_games_keeps_mainline_after_glued_parentheses() {
+        let sans = |moves: &[PgnMove]| -> Vec<String> {
+            moves.iter().map(|m| m.san.to_string()).collect()
+        };
+
+        let games = parse_games("1. e4 (1. d4 d5) 1... e5 2. Nf3 *").unwrap();
+        assert_eq!(sans(&games[0].moves), vec!["e4", "e5", "Nf3"]);
+        assert_eq!(sans(&games[0].moves[0].variations[0]), vec!["d4", "d5"]);
+
+        // A move glued to the closing or opening parenthesis, a variation
+        // opened and closed within one token, and glued move numbers.
+        let games =
+            parse_games("1. e4 e5 (1...c5 2.Nf3 d6) 2.Nf3 (2.Bc4) Nc6 (2...d6) 3.Bb5 *").unwrap();
+        assert_eq!(sans(&games[0].moves), vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert_eq!(
+            sans(&games[0].moves[1].variations[0]),
+            vec!["c5", "Nf3", "d6"]
+        );
+        assert_eq!(sans(&games[0].moves[2].variations[0]), vec!["Bc4"]);
+        assert_eq!(sans(&games[0].moves[3].variations[0]), vec!["d6"]);
+    }
+
+    #[test]
+    fn parse_games_attaches_nags_to_preceding_move() {
+        let games = parse_games("1. e4 $1 e5 (1... c5 $2 $6) 2. Nf3 $999 *").unwrap();
+        let moves = &games[0].moves;
+        assert_eq!(moves[0].nags, vec![1]);
+        assert!(moves[1].nags.is_empty());
+        assert_eq!(moves[1].variations[0][0].nags, vec![2, 6]);
+        assert!(moves[2].nags.is_empty());
+    }
+
+    #[test]
+    fn format_game_round_trips_variations_and_nags() {
+        let pgn =
+            "[Result \"1-0\"]\n\n1. e4 $1 e5 (1... c5 2. Nf3 (2. c3 d5) 2... d6) 2. Nf3 1-0\n";
+        let games = parse_games(pgn).unwrap();
+        let written = format_game(&games[0]);
+        assert_eq!(written, pgn);
+        let reparsed = parse_games(&written).unwrap();
+        assert_eq!(format_game(&reparsed[0]), written);
+    }
+
+    #[test]
+    fn format_game_round_trips_headers_and_comments() {
+        let pgn = "[Event \"Club \\\"open\\\" night\"]\n[Site \"?\"]\n[Result \"1-0\"]\n\n\
+                   1. e4 { best by test } 1... e5 $1 { open } { game } (1... c5 { sharp }) \
+                   2. Nf3 1-0\n";
+        let games = parse_games(pgn).unwrap();
+        let moves = &games[0].moves;
+        assert_eq!(
+            games[0].headers[0],
+            ("Event".to_string(), "Club \"open\" night".to_string())
+        );
+        assert_eq!(moves[0].comments, vec!["best by test"]);
+        assert_eq!(moves[1].comments, vec!["open", "game"]);
+        assert_eq!(moves[1].variations[0][0].comments, vec!["sharp"]);
+        assert_eq!(format_game(&games[0]), pgn);
+
+        // Comments glued to moves, spread over lines, or with nothing to
+        // follow are handled too.
+        let games = parse_games("{ intro } 1. e4{a\n  b}e5 ({x} 1... c5) *").unwrap();
+        let moves = &games[0].moves;
+        assert_eq!(moves[0].comments, vec!["a b"]);
+        assert!(moves[1].comments.is_empty());
+        assert!(moves[1].variations[0][0].comments.is_empty());
+    }
+
+    #[test]
+    fn pgn_to_fens_lists_mainline_edges() {
+        let pgn = "[Event \"?\"]\n[Result \"*\"]\n\n\
+                   1. e4 e5 {open game} 2. Nf3 $1 Nc6 (2... d6 3. d4) 3. Bb5 a6 *\n"
+            .to_string();
+        let triples = pgn_to_fens(pgn).unwrap();
+        assert_eq!(triples.len(), 6);
+        assert_eq!(
+            triples[0],
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+                "e2e4".to_string(),
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".to_string(),
+            )
+        );
+        assert!(triples.windows(2).all(|pair| pair[0].2 == pair[1].0));
+        assert_eq!(triples[5].1, "a7a6");
+    }
+
+    #[test]
+    fn unique_positions_dedups_shared_positions() {
+        let pgn = "1. e4 e5 2. Nf3 *\n\n1. e4 c5 *\n".to_string();
+        assert_eq!(unique_positions(pgn).unwrap(), 5);
+    }
+}
@@ -1,18 +1,113 @@
-from typing import Any, Dict, List, Sequence, Tuple
+from typing import Any, Callable, Dict, List, Literal, Sequence, Set, Tuple, overload
 
+@overload
 def player_move_analysis(
     nodes: Sequence[Any],
+    report_conflicts: Literal[False] = False,
+    with_move_counts: Literal[False] = False,
 ) -> Tuple[List[Dict[str, Any]], Dict[str, List[Dict[str, Any]]]]: ...
+@overload
+def player_move_analysis(
+    nodes: Sequence[Any],
+    report_conflicts: Literal[True],
+    with_move_counts: Literal[False] = False,
+) -> Tuple[
+    List[Dict[str, Any]], Dict[str, List[Dict[str, Any]]], List[Dict[str, Any]]
+]: ...
+@overload
+def player_move_analysis(
+    nodes: Sequence[Any],
+    report_conflicts: Literal[False] = False,
+    *,
+    with_move_counts: Literal[True],
+) -> Tuple[List[Dict[str, Any]], Dict[str, List[Dict[str, Any]]], Dict[str, int]]: ...
+@overload
+def player_move_analysis(
+    nodes: Sequence[Any],
+    report_conflicts: bool,
+    with_move_counts: bool,
+) -> Tuple[Any, ...]: ...
+def fingerprint_examples(
+    nodes: Sequence[Any],
+) -> List[Dict[str, Any]]: ...
 def player_turn_mask(
     side_is_white: bool,
     fens: Sequence[str],
 ) -> List[bool]: ...
+def legal_moves(fen: str) -> List[Tuple[str, str]]: ...
+def san_to_uci(fen: str, san: str) -> str: ...
+def uci_to_san(fen: str, uci: str) -> str: ...
+def game_fens(start_fen: str | None, moves: Sequence[str]) -> List[str]: ...
+def perft(fen: str, depth: int) -> int: ...
+def position_hash(fen: str) -> int: ...
+def position_status(fen: str) -> Dict[str, bool]: ...
 def split_repertoire_nodes(
     root_fen: str,
     nodes: Sequence[Any],
     max_moves: int,
+    min_moves: int = 0,
+    max_ply: int | None = None,
 ) -> List[Tuple[str, List[str], int]]: ...
-def canonicalize_fen(fen: str) -> str: ...
+def coverage(
+    side_is_white: bool,
+    nodes: Sequence[Any],
+) -> float: ...
+def diff_repertoires_pgn(
+    old_nodes: Sequence[Any],
+    new_nodes: Sequence[Any],
+) -> str: ...
+def quiz_order(
+    side_is_white: bool,
+    nodes: Sequence[Any],
+    seed: int,
+    weighted: bool = False,
+) -> List[str]: ...
+def orphan_positions(
+    root_fen: str,
+    nodes: Sequence[Any],
+) -> List[str]: ...
+def transposing_moves(
+    nodes: Sequence[Any],
+    target_fen: str,
+) -> List[Tuple[str, str]]: ...
+def main_line(
+    root_fen: str,
+    nodes: Sequence[Any],
+    side_is_white: bool,
+) -> List[str]: ...
+def only_moves(
+    side_is_white: bool,
+    nodes: Sequence[Any],
+    eval_cache: Dict[str, Dict[str, int]],
+    threshold: int,
+) -> List[str]: ...
+def pgn_to_fens(pgn: str) -> List[Tuple[str, str, str]]: ...
+def unique_positions(pgn_text: str) -> int: ...
+def analysis_url(
+    start_fen: str | None,
+    moves: Sequence[str],
+    base_url: str = "https://lichess.org",
+) -> str: ...
+def group_transpositions(fens: Sequence[str]) -> List[List[int]]: ...
+def merge_node_graphs(graphs: Sequence[Sequence[Any]]) -> List[Dict[str, Any]]: ...
+def prune_recommendations(
+    nodes: Sequence[Any],
+    eval_cache: Dict[str, Dict[str, int]],
+    side_is_white: bool,
+    loss_threshold: int,
+) -> List[Tuple[str, str]]: ...
+def memorization_load(
+    side_is_white: bool,
+    nodes: Sequence[Any],
+) -> int: ...
+def order_sensitive_nodes(
+    nodes: Sequence[Any],
+) -> List[str]: ...
+def canonicalize_fen(
+    fen: str, reset_counters: bool = True, chess960: bool = False
+) -> str: ...
+def canonicalize_fens(fens: Sequence[str]) -> List[str]: ...
+def is_canonical(fen: str) -> bool: ...
 def stockfish_evaluate(
     fen: str,
     engine_path: str,
@@ -20,4 +115,89 @@ def stockfish_evaluate(
     multi_pv: int,
     think_time: float | None,
     pool_size: int,
+    hash_mb: int | None = None,
+    threads: int | None = None,
+    timeout: float | None = None,
+    nice: int | None = None,
+    ponder: bool = False,
+    syzygy_path: str | None = None,
+    syzygy_probe_depth: int | None = None,
+    white_pov: bool = False,
+    skill_level: int | None = None,
+    uci_elo: int | None = None,
+    chess960: bool = False,
+    progress: Callable[[Dict[str, Any]], Any] | None = None,
+    searchmoves: Sequence[str] | None = None,
+    nodes_limit: int | None = None,
+    mate_in: int | None = None,
+    options: Sequence[Tuple[str, str]] | None = None,
+) -> Dict[str, Any]: ...
+def stockfish_evaluate_batch(
+    fens: Sequence[str],
+    engine_path: str,
+    depth: int,
+    multi_pv: int,
+    think_time: float | None,
+    pool_size: int,
+    skip_if_cached: bool = False,
+    known_fens: Set[str] | None = None,
+) -> List[Dict[str, Any]]: ...
+def stockfish_evaluate_cached(
+    fen: str,
+    engine_path: str,
+    depth: int,
+    multi_pv: int,
+    think_time: float | None,
+    pool_size: int,
+    cache_path: str,
+    hash_mb: int | None = None,
+    threads: int | None = None,
+    timeout: float | None = None,
+    nice: int | None = None,
+    syzygy_path: str | None = None,
+    syzygy_probe_depth: int | None = None,
+    white_pov: bool = False,
+    skill_level: int | None = None,
+    uci_elo: int | None = None,
+    chess960: bool = False,
+    searchmoves: Sequence[str] | None = None,
+    nodes_limit: int | None = None,
+    mate_in: int | None = None,
 ) -> Dict[str, Any]: ...
+def evaluate_candidates(
+    fen: str,
+    candidate_ucis: Sequence[str],
+    engine_path: str,
+    depth: int,
+    think_time: float | None = None,
+    pool_size: int = 1,
+) -> List[Dict[str, Any]]: ...
+def warmup_stockfish_pool(
+    engine_path: str,
+    depth: int,
+    multi_pv: int,
+    think_time: float | None,
+    pool_size: int,
+    hash_mb: int | None = None,
+    threads: int | None = None,
+    timeout: float | None = None,
+    nice: int | None = None,
+    ponder: bool = False,
+    syzygy_path: str | None = None,
+    syzygy_probe_depth: int | None = None,
+    skill_level: int | None = None,
+    uci_elo: int | None = None,
+    chess960: bool = False,
+    nodes_limit: int | None = None,
+    mate_in: int | None = None,
+    options: Sequence[Tuple[str, str]] | None = None,
+) -> int: ...
+def list_stockfish_pools() -> List[Dict[str, Any]]: ...
+def shutdown_stockfish_pools() -> int: ...
+def shutdown_stockfish_pool(
+    engine_path: str,
+    depth: int,
+    multi_pv: int,
+    think_time: float | None,
+    pool_size: int,
+) -> int: ...
@@ -26,8 +26,17 @@ class RepertoireSplitter:
     def __init__(self, repertoire: Repertoire):
         self.repertoire = repertoire
 
-    def split_events(self, max_moves: int = 1000) -> list[SplitEvent]:
-        """Return split events where each subtree stays within ``max_moves``."""
+    def split_events(
+        self,
+        max_moves: int = 1000,
+        min_moves: int = 0,
+        max_ply: int | None = None,
+    ) -> list[SplitEvent]:
+        """Return split events where each subtree stays within ``max_moves``.
+
+        Subtrees smaller than ``min_moves`` stay with their parent's event, and
+        ``max_ply`` cuts every event at that depth from the root.
+        """
 
         max_moves = max(1, int(max_moves))
         payload = self._split_payload()
@@ -35,6 +44,8 @@ class RepertoireSplitter:
             self.repertoire.root_node.fen,
             payload,
             max_moves,
+            max(0, int(min_moves)),
+            None if max_ply is None else max(0, int(max_ply)),
         )
         events: list[SplitEvent] = []
         for fen, prefix_uci, move_count in raw_events:
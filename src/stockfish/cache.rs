@@ -0,0 +1,373 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{
+    EngineError, EngineResult, EvalPayload, PoolKey, StockfishPool, get_or_create_pool,
+    ignore_progress,
+};
+
+/// How long a connection waits for another process's write to finish.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS evaluations (
+    cache_key TEXT PRIMARY KEY,
+    depth INTEGER NOT NULL,
+    payload TEXT NOT NULL
+);
+";
+
+/// A stored evaluation and the depth it was requested at.
+struct CacheEntry {
+    depth: u32,
+    payload: EvalPayload,
+}
+
+/// Engine settings that change what a search returns. Depth is left out so a
+/// deeper cached result can answer a shallower request.
+#[derive(Serialize)]
+struct CacheSettings<'a> {
+    engine_path: &'a str,
+    multi_pv: u32,
+    think_time_ms: Option<u64>,
+    syzygy_path: Option<&'a str>,
+    syzygy_probe_depth: Option<u32>,
+    skill_level: Option<u32>,
+    uci_elo: Option<u32>,
+    chess960: bool,
+    nodes_limit: Option<u64>,
+    mate_in: Option<u32>,
+    searchmoves: &'a [String],
+}
+
+/// Evaluate `fen` like `stockfish_evaluate`, reusing results stored in the
+/// SQLite database at `cache_path`.
+///
+/// Entries are keyed by the canonical FEN plus the engine settings. A stored
+/// entry answers any request at or below its depth; otherwise the position is
+/// searched and its row written. Several processes may share one cache file.
+/// The returned dict carries `cached`.
+#[pyfunction]
+#[pyo3(signature = (fen, engine_path, depth, multi_pv, think_time, pool_size, cache_path, hash_mb=None, threads=None, timeout=None, nice=None, syzygy_path=None, syzygy_probe_depth=None, white_pov=false, skill_level=None, uci_elo=None, chess960=false, searchmoves=None, nodes_limit=None, mate_in=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn stockfish_evaluate_cached(
+    py: Python<'_>,
+    fen: String,
+    engine_path: String,
+    depth: u32,
+    multi_pv: u32,
+    think_time: Option<f64>,
+    pool_size: usize,
+    cache_path: String,
+    hash_mb: Option<u32>,
+    threads: Option<u32>,
+    timeout: Option<f64>,
+    nice: Option<i32>,
+    syzygy_path: Option<String>,
+    syzygy_probe_depth: Option<u32>,
+    white_pov: bool,
+    skill_level: Option<u32>,
+    uci_elo: Option<u32>,
+    chess960: bool,
+    searchmoves: Option<Vec<String>>,
+    nodes_limit: Option<u64>,
+    mate_in: Option<u32>,
+) -> PyResult<Py<PyAny>> {
+    let key = PoolKey::from_settings(
+        &engine_path,
+        depth,
+        multi_pv,
+        think_time,
+        pool_size,
+        hash_mb,
+        threads,
+        timeout,
+        nice,
+        false,
+        syzygy_path,
+        syzygy_probe_depth,
+        skill_level,
+        uci_elo,
+        chess960,
+        nodes_limit,
+        mate_in,
+        None,
+    )?;
+    let searchmoves = searchmoves.unwrap_or_default();
+    let (mut payload, cached) =
+        evaluate_cached(&key, Path::new(&cache_path), &fen, &searchmoves, || {
+            get_or_create_pool(&key)
+        })?;
+    payload.fen = fen;
+    payload.apply_point_of_view(white_pov, key.castling_mode())?;
+    let dict = payload
+        .to_pydict(py)?
+        .into_bound(py)
+        .cast_into::<PyDict>()?;
+    dict.set_item("cached", cached)?;
+    Ok(dict.into_any().unbind())
+}
+
+/// Look `fen` up in the cache, searching on the pool from `pool` and storing
+/// the result on a miss. The pool is only requested on a miss, so hits never
+/// start an engine. Returns the payload and whether it came from the cache.
+fn evaluate_cached<F>(
+    key: &PoolKey,
+    cache_path: &Path,
+    fen: &str,
+    searchmoves: &[String],
+    pool: F,
+) -> EngineResult<(EvalPayload, bool)>
+where
+    F: FnOnce() -> EngineResult<Arc<StockfishPool>>,
+{
+    let cache_key = cache_key(key, fen, searchmoves)?;
+    let conn = open_cache(cache_path)?;
+    if let Some(entry) = lookup(&conn, cache_path, &cache_key)?
+        && entry.depth >= key.depth
+    {
+        return Ok((entry.payload, true));
+    }
+    let payload = pool()?.evaluate(fen, searchmoves, &mut ignore_progress)?;
+    store(
+        &conn,
+        cache_path,
+        &cache_key,
+        &CacheEntry {
+            depth: key.depth,
+            payload: payload.clone(),
+        },
+    )?;
+    Ok((payload, false))
+}
+
+fn cache_key(key: &PoolKey, fen: &str, searchmoves: &[String]) -> EngineResult<String> {
+    let canonical = crate::canonicalize_fen_str(fen).unwrap_or_else(|_| fen.to_string());
+    let settings = CacheSettings {
+        engine_path: &key.engine_path,
+        multi_pv: key.multi_pv,
+        think_time_ms: key.think_time_ms,
+        syzygy_path: key.syzygy_path.as_deref(),
+        syzygy_probe_depth: key.syzygy_probe_depth,
+        skill_level: key.skill_level,
+        uci_elo: key.uci_elo,
+        chess960: key.chess960,
+        nodes_limit: key.nodes_limit,
+        mate_in: key.mate_in,
+        searchmoves,
+    };
+    let settings =
+        serde_json::to_string(&settings).map_err(|err| EngineError::Engine(err.to_string()))?;
+    Ok(format!("{canonical} {settings}"))
+}
+
+fn open_cache(path: &Path) -> EngineResult<Connection> {
+    let open = || -> rusqlite::Result<Connection> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(conn)
+    };
+    open().map_err(|err| {
+        EngineError::Engine(format!(
+            "Failed to open evaluation cache {}: {err}",
+            path.display()
+        ))
+    })
+}
+
+fn lookup(conn: &Connection, path: &Path, cache_key: &str) -> EngineResult<Option<CacheEntry>> {
+    let row: Option<(u32, String)> = conn
+        .query_row(
+            "SELECT depth, payload FROM evaluations WHERE cache_key = ?1",
+            params![cache_key],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(database_error(path))?;
+    let Some((depth, payload)) = row else {
+        return Ok(None);
+    };
+    let payload = serde_json::from_str(&payload).map_err(|err| {
+        EngineError::InvalidInput(format!(
+            "Invalid entry in evaluation cache {}: {err}",
+            path.display()
+        ))
+    })?;
+    Ok(Some(CacheEntry { depth, payload }))
+}
+
+/// Write one entry in a single statement, so concurrent writers never lose
+/// each other's rows. A shallower result never replaces a deeper one.
+fn store(conn: &Connection, path: &Path, cache_key: &str, entry: &CacheEntry) -> EngineResult<()> {
+    let payload = serde_json::to_string(&entry.payload)
+        .map_err(|err| EngineError::Engine(err.to_string()))?;
+    conn.execute(
+        "INSERT INTO evaluations (cache_key, depth, payload) VALUES (?1, ?2, ?3)
+         ON CONFLICT (cache_key) DO UPDATE SET depth = excluded.depth, payload = excluded.payload
+         WHERE excluded.depth >= evaluations.depth",
+        params![cache_key, entry.depth, payload],
+    )
+    .map_err(database_error(path))?;
+    Ok(())
+}
+
+fn database_error(path: &Path) -> impl Fn(rusqlite::Error) -> EngineError + '_ {
+    move |err| EngineError::Engine(format!("Evaluation cache {} failed: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockfish::StockfishWorker;
+    use crate::stockfish::tests::{MockIo, test_key};
+    use tempfile::tempdir;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn no_engine() -> EngineResult<Arc<StockfishPool>> {
+        panic!("a cache hit must not start an engine")
+    }
+
+    #[test]
+    fn miss_evaluates_and_populates_cache() {
+        let dir = tempdir().expect("tempdir");
+        let cache_path = dir.path().join("evals.sqlite");
+        let mock = MockIo::new(vec![
+            "info depth 12 nodes 5000 multipv 1 score cp 25 pv e2e4 e7e5",
+            "bestmove e2e4",
+        ]);
+        let writes = mock.writes();
+        let key = test_key();
+        let pool = Arc::new(StockfishPool::with_workers(
+            &key,
+            vec![StockfishWorker::with_io(Box::new(mock))],
+            Box::new(|_| Err(EngineError::Engine("no respawn in tests".to_string()))),
+        ));
+
+        let (payload, cached) =
+            evaluate_cached(&key, &cache_path, START, &[], || Ok(pool.clone())).unwrap();
+        assert!(!cached);
+        assert_eq!(payload.pvs[0].cp, Some(25));
+        assert_eq!(
+            writes
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|cmd| cmd.starts_with("go"))
+                .count(),
+            1
+        );
+
+        // Same position with other move counters and a shallower request hits.
+        let shallower = PoolKey {
+            depth: 8,
+            ..test_key()
+        };
+        let moved_counters = START.replace(" 0 1", " 4 9");
+        let (cached_payload, cached) =
+            evaluate_cached(&shallower, &cache_path, &moved_counters, &[], no_engine).unwrap();
+        assert!(cached);
+        assert_eq!(cached_payload.best_move.as_deref(), Some("e2e4"));
+        assert_eq!(cached_payload.pvs[0].moves, vec!["e2e4", "e7e5"]);
+    }
+
+    #[test]
+    fn hit_returns_stored_payload_without_engine() {
+        let dir = tempdir().expect("tempdir");
+        let cache_path = dir.path().join("evals.sqlite");
+        let key = test_key();
+        let stored = EvalPayload {
+            fen: START.to_string(),
+            depth: 20,
+            seldepth: 24,
+            knodes: 900,
+            nps: 1000,
+            hashfull: 0,
+            tbhits: 0,
+            tablebase: false,
+            dtz: None,
+            best_move: Some("d2d4".to_string()),
+            ponder_move: None,
+            pvs: Vec::new(),
+            info_strings: Vec::new(),
+        };
+        let conn = open_cache(&cache_path).unwrap();
+        store(
+            &conn,
+            &cache_path,
+            &cache_key(&key, START, &[]).unwrap(),
+            &CacheEntry {
+                depth: 20,
+                payload: stored,
+            },
+        )
+        .unwrap();
+
+        let (payload, cached) = evaluate_cached(&key, &cache_path, START, &[], no_engine).unwrap();
+        assert!(cached);
+        assert_eq!(payload.depth, 20);
+        assert_eq!(payload.best_move.as_deref(), Some("d2d4"));
+
+        // Other settings or a deeper request do not match the stored entry.
+        let deeper = PoolKey {
+            depth: 30,
+            ..test_key()
+        };
+        let wider = PoolKey {
+            multi_pv: 5,
+            ..test_key()
+        };
+        for other in [deeper, wider] {
+            let err = evaluate_cached(&other, &cache_path, START, &[], || {
+                Err(EngineError::Engine("engine requested".to_string()))
+            })
+            .unwrap_err();
+            assert!(err.to_string().contains("engine requested"));
+        }
+    }
+
+    #[test]
+    fn store_keeps_other_writers_rows_and_the_deepest_result() {
+        let dir = tempdir().expect("tempdir");
+        let cache_path = dir.path().join("evals.sqlite");
+        let payload = |depth: u32| EvalPayload {
+            fen: START.to_string(),
+            depth,
+            seldepth: depth,
+            knodes: 0,
+            nps: 0,
+            hashfull: 0,
+            tbhits: 0,
+            tablebase: false,
+            dtz: None,
+            best_move: None,
+            ponder_move: None,
+            pvs: Vec::new(),
+            info_strings: Vec::new(),
+        };
+        let entry = |depth: u32| CacheEntry {
+            depth,
+            payload: payload(depth),
+        };
+        // Two connections stand in for two processes sharing the file.
+        let first = open_cache(&cache_path).unwrap();
+        let second = open_cache(&cache_path).unwrap();
+
+        store(&first, &cache_path, "a", &entry(18)).unwrap();
+        store(&second, &cache_path, "b", &entry(12)).unwrap();
+        store(&second, &cache_path, "a", &entry(10)).unwrap();
+        store(&first, &cache_path, "b", &entry(16)).unwrap();
+
+        let depth = |key: &str| lookup(&first, &cache_path, key).unwrap().unwrap().depth;
+        assert_eq!(depth("a"), 18);
+        assert_eq!(depth("b"), 16);
+        assert!(lookup(&second, &cache_path, "c").unwrap().is_none());
+    }
+}
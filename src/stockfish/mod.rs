@@ -1,12 +1,63 @@
 use once_cell::sync::Lazy;
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyList};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::{CastlingMode, Color, EnPassantMode, Position};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::panic::{self, AssertUnwindSafe};
+use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, Stdio};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+mod cache;
+pub use cache::stockfish_evaluate_cached;
+
+const MAX_HASH_MB: u32 = 65536;
+/// Stockfish reports tablebase wins as `cp 20000 - plies` within this many plies.
+const TB_WIN_CP: i32 = 20000;
+const TB_MAX_PLY: i32 = 246;
+/// Engine stderr lines kept for error messages.
+const STDERR_TAIL_LINES: usize = 20;
+const STDERR_EXIT_GRACE: Duration = Duration::from_millis(200);
+
+/// Failure in the engine layer. It carries no Python state so Rust binaries can
+/// drive engines too; pyfunctions raise `InvalidInput` as `ValueError` and
+/// `Engine` as `RuntimeError`.
+#[derive(Debug)]
+pub enum EngineError {
+    InvalidInput(String),
+    Engine(String),
+}
+
+type EngineResult<T> = Result<T, EngineError>;
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::InvalidInput(message) | EngineError::Engine(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<EngineError> for PyErr {
+    fn from(err: EngineError) -> Self {
+        match err {
+            EngineError::InvalidInput(message) => PyValueError::new_err(message),
+            EngineError::Engine(message) => PyRuntimeError::new_err(message),
+        }
+    }
+}
 
 static STOCKFISH_POOLS: Lazy<Mutex<HashMap<PoolKey, Arc<StockfishPool>>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
@@ -18,9 +69,26 @@ struct PoolKey {
     multi_pv: u32,
     think_time_ms: Option<u64>,
     pool_size: usize,
+    hash_mb: Option<u32>,
+    threads: Option<u32>,
+    timeout_ms: Option<u64>,
+    nice: Option<i32>,
+    ponder: bool,
+    syzygy_path: Option<String>,
+    syzygy_probe_depth: Option<u32>,
+    skill_level: Option<u32>,
+    uci_elo: Option<u32>,
+    chess960: bool,
+    nodes_limit: Option<u64>,
+    mate_in: Option<u32>,
+    /// Extra `setoption` name/value pairs, sorted so the same set always maps
+    /// to the same pool.
+    options: Vec<(String, String)>,
 }
 
 #[pyfunction]
+#[pyo3(signature = (fen, engine_path, depth, multi_pv, think_time, pool_size, hash_mb=None, threads=None, timeout=None, nice=None, ponder=false, syzygy_path=None, syzygy_probe_depth=None, white_pov=false, skill_level=None, uci_elo=None, chess960=false, progress=None, searchmoves=None, nodes_limit=None, mate_in=None, options=None))]
+#[allow(clippy::too_many_arguments)]
 pub fn stockfish_evaluate(
     py: Python<'_>,
     fen: String,
@@ -29,27 +97,515 @@ pub fn stockfish_evaluate(
     multi_pv: u32,
     think_time: Option<f64>,
     pool_size: usize,
+    hash_mb: Option<u32>,
+    threads: Option<u32>,
+    timeout: Option<f64>,
+    nice: Option<i32>,
+    ponder: bool,
+    syzygy_path: Option<String>,
+    syzygy_probe_depth: Option<u32>,
+    white_pov: bool,
+    skill_level: Option<u32>,
+    uci_elo: Option<u32>,
+    chess960: bool,
+    progress: Option<Py<PyAny>>,
+    searchmoves: Option<Vec<String>>,
+    nodes_limit: Option<u64>,
+    mate_in: Option<u32>,
+    options: Option<Vec<(String, String)>>,
+) -> PyResult<Py<PyAny>> {
+    let key = PoolKey::from_settings(
+        &engine_path,
+        depth,
+        multi_pv,
+        think_time,
+        pool_size,
+        hash_mb,
+        threads,
+        timeout,
+        nice,
+        ponder,
+        syzygy_path,
+        syzygy_probe_depth,
+        skill_level,
+        uci_elo,
+        chess960,
+        nodes_limit,
+        mate_in,
+        options,
+    )?;
+    let searchmoves = searchmoves.unwrap_or_default();
+    let pool = get_or_create_pool(&key)?;
+    // The engine layer only sees that the callback failed; its Python exception
+    // is kept here and re-raised as is.
+    let mut callback_error: Option<PyErr> = None;
+    let result = match progress {
+        Some(callback) => pool.evaluate(&fen, &searchmoves, &mut |update: &SearchProgress| {
+            // A panicking callback must not unwind through the locked worker.
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                callback.call1(py, (update.to_pydict(py)?,)).map(|_| ())
+            }))
+            .unwrap_or_else(|_| Err(PyRuntimeError::new_err("progress callback panicked")))
+            .map_err(|err| {
+                callback_error = Some(err);
+                EngineError::Engine("progress callback failed".to_string())
+            })
+        }),
+        None => pool.evaluate(&fen, &searchmoves, &mut ignore_progress),
+    };
+    let mut payload = result.map_err(|err| callback_error.take().unwrap_or_else(|| err.into()))?;
+    payload.apply_point_of_view(white_pov, key.castling_mode())?;
+    payload.to_pydict(py)
+}
+
+/// Evaluate several FENs through one pool, searching on all workers in parallel.
+///
+/// Entries that fail carry an `error` message instead of PVs. With `skip_if_cached`, FENs whose canonical form is in `known_fens` are not
+/// searched and come back as `{"fen": ..., "cached": True}`.
+#[pyfunction]
+#[pyo3(signature = (fens, engine_path, depth, multi_pv, think_time, pool_size, skip_if_cached=false, known_fens=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn stockfish_evaluate_batch(
+    py: Python<'_>,
+    fens: Vec<String>,
+    engine_path: String,
+    depth: u32,
+    multi_pv: u32,
+    think_time: Option<f64>,
+    pool_size: usize,
+    skip_if_cached: bool,
+    known_fens: Option<HashSet<String>>,
+) -> PyResult<Py<PyAny>> {
+    let key = PoolKey {
+        think_time_ms: seconds_to_ms(think_time),
+        ..PoolKey::with_defaults(&engine_path, depth, multi_pv, pool_size)
+    };
+    let known = if skip_if_cached {
+        known_fens.unwrap_or_default()
+    } else {
+        HashSet::new()
+    };
+    let pool = get_or_create_pool(&key)?;
+    let results = py.detach(|| pool.evaluate_batch(&fens, &known));
+    let items = PyList::empty(py);
+    for result in results {
+        let entry = match result {
+            BatchEntry::Cached(fen) => {
+                let dict = PyDict::new(py);
+                dict.set_item("fen", fen)?;
+                dict.set_item("cached", true)?;
+                dict
+            }
+            BatchEntry::Evaluated(payload) => {
+                let dict = payload
+                    .to_pydict(py)?
+                    .into_bound(py)
+                    .cast_into::<PyDict>()?;
+                dict.set_item("cached", false)?;
+                dict
+            }
+            BatchEntry::Failed(fen, err) => {
+                let dict = PyDict::new(py);
+                dict.set_item("fen", fen)?;
+                dict.set_item("cached", false)?;
+                dict.set_item("error", err.to_string())?;
+                dict
+            }
+        };
+        items.append(entry)?;
+    }
+    Ok(items.into())
+}
+
+/// Evaluate the position after each candidate move, best first for the mover.
+///
+/// Every candidate is searched on the same pool worker. Scores are negated from
+/// the reply's side to move so that higher is better for the side playing `fen`.
+#[pyfunction]
+#[pyo3(signature = (fen, candidate_ucis, engine_path, depth, think_time=None, pool_size=1))]
+pub fn evaluate_candidates(
+    py: Python<'_>,
+    fen: String,
+    candidate_ucis: Vec<String>,
+    engine_path: String,
+    depth: u32,
+    think_time: Option<f64>,
+    pool_size: usize,
 ) -> PyResult<Py<PyAny>> {
-    let think_time_ms = think_time.and_then(|secs| {
+    let key = PoolKey {
+        think_time_ms: seconds_to_ms(think_time),
+        ..PoolKey::with_defaults(&engine_path, depth, 1, pool_size)
+    };
+    let pool = get_or_create_pool(&key)?;
+    let ranked = pool.rank_candidates(&fen, &candidate_ucis)?;
+    let items = PyList::empty(py);
+    for candidate in ranked {
+        let entry = PyDict::new(py);
+        entry.set_item("uci", candidate.uci)?;
+        entry.set_item("san", candidate.san)?;
+        entry.set_item("fen", candidate.fen)?;
+        entry.set_item("cp", candidate.cp)?;
+        entry.set_item("mate", candidate.mate)?;
+        entry.set_item("best_reply", candidate.best_reply)?;
+        items.append(entry)?;
+    }
+    Ok(items.into())
+}
+
+struct CandidateEval {
+    uci: String,
+    san: String,
+    fen: String,
+    cp: Option<i32>,
+    mate: Option<i32>,
+    best_reply: Option<String>,
+}
+
+impl CandidateEval {
+    /// Total order on scores for the mover: delivering mate sooner ranks highest,
+    /// being mated sooner lowest, and centipawns in between.
+    fn rank(&self) -> i64 {
+        const MATE: i64 = 1_000_000;
+        match (self.mate, self.cp) {
+            (Some(mate), _) if mate >= 0 => MATE - i64::from(mate),
+            (Some(mate), _) => -MATE - i64::from(mate),
+            (None, Some(cp)) => i64::from(cp),
+            (None, None) => -2 * MATE,
+        }
+    }
+}
+
+/// Spawn and initialize the pool `stockfish_evaluate` would use for these settings,
+/// so the first evaluation does not wait for the engines to start.
+///
+/// Returns the number of workers in the pool.
+#[pyfunction]
+#[pyo3(signature = (engine_path, depth, multi_pv, think_time, pool_size, hash_mb=None, threads=None, timeout=None, nice=None, ponder=false, syzygy_path=None, syzygy_probe_depth=None, skill_level=None, uci_elo=None, chess960=false, nodes_limit=None, mate_in=None, options=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn warmup_stockfish_pool(
+    py: Python<'_>,
+    engine_path: String,
+    depth: u32,
+    multi_pv: u32,
+    think_time: Option<f64>,
+    pool_size: usize,
+    hash_mb: Option<u32>,
+    threads: Option<u32>,
+    timeout: Option<f64>,
+    nice: Option<i32>,
+    ponder: bool,
+    syzygy_path: Option<String>,
+    syzygy_probe_depth: Option<u32>,
+    skill_level: Option<u32>,
+    uci_elo: Option<u32>,
+    chess960: bool,
+    nodes_limit: Option<u64>,
+    mate_in: Option<u32>,
+    options: Option<Vec<(String, String)>>,
+) -> PyResult<usize> {
+    let key = PoolKey::from_settings(
+        &engine_path,
+        depth,
+        multi_pv,
+        think_time,
+        pool_size,
+        hash_mb,
+        threads,
+        timeout,
+        nice,
+        ponder,
+        syzygy_path,
+        syzygy_probe_depth,
+        skill_level,
+        uci_elo,
+        chess960,
+        nodes_limit,
+        mate_in,
+        options,
+    )?;
+    let pool = py.detach(|| get_or_create_pool(&key))?;
+    Ok(pool.workers.len())
+}
+
+/// Describe every live pool: its engine, search settings and number of workers.
+#[pyfunction]
+pub fn list_stockfish_pools(py: Python<'_>) -> PyResult<Py<PyAny>> {
+    let mut pools: Vec<(PoolKey, usize)> = STOCKFISH_POOLS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(key, pool)| (key.clone(), pool.workers.len()))
+        .collect();
+    pools.sort_by(|(a, _), (b, _)| {
+        (
+            &a.engine_path,
+            a.depth,
+            a.multi_pv,
+            a.think_time_ms,
+            a.pool_size,
+        )
+            .cmp(&(
+                &b.engine_path,
+                b.depth,
+                b.multi_pv,
+                b.think_time_ms,
+                b.pool_size,
+            ))
+    });
+    let items = PyList::empty(py);
+    for (key, workers) in pools {
+        let dict = PyDict::new(py);
+        dict.set_item("engine_path", key.engine_path)?;
+        dict.set_item("depth", key.depth)?;
+        dict.set_item("multi_pv", key.multi_pv)?;
+        dict.set_item("think_time_ms", key.think_time_ms)?;
+        dict.set_item("pool_size", key.pool_size)?;
+        dict.set_item("workers", workers)?;
+        items.append(dict)?;
+    }
+    Ok(items.into())
+}
+
+/// Drop every cached pool, quitting its engines once no evaluation still holds it.
+///
+/// Returns the number of pools released; the next evaluation spawns fresh workers.
+#[pyfunction]
+pub fn shutdown_stockfish_pools(py: Python<'_>) -> usize {
+    let pools = std::mem::take(&mut *STOCKFISH_POOLS.lock().unwrap());
+    let released = pools.len();
+    py.detach(|| drop(pools));
+    released
+}
+
+/// Drop the pools created for these search settings, whatever their other options.
+///
+/// Returns the number of pools released.
+#[pyfunction]
+pub fn shutdown_stockfish_pool(
+    py: Python<'_>,
+    engine_path: String,
+    depth: u32,
+    multi_pv: u32,
+    think_time: Option<f64>,
+    pool_size: usize,
+) -> usize {
+    let think_time_ms = seconds_to_ms(think_time);
+    let pool_size = pool_size.max(1);
+    let mut released = Vec::new();
+    STOCKFISH_POOLS.lock().unwrap().retain(|key, pool| {
+        let matches = key.engine_path == engine_path
+            && key.depth == depth
+            && key.multi_pv == multi_pv
+            && key.think_time_ms == think_time_ms
+            && key.pool_size == pool_size;
+        if matches {
+            released.push(pool.clone());
+        }
+        !matches
+    });
+    let count = released.len();
+    py.detach(|| drop(released));
+    count
+}
+
+/// An engine pool for Rust callers such as the CLI binaries, which cannot use
+/// the Python-facing functions.
+pub struct EnginePool {
+    pool: StockfishPool,
+}
+
+/// Score of one principal variation, from White's point of view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineScore {
+    pub cp: Option<i32>,
+    pub mate: Option<i32>,
+    pub moves: Vec<String>,
+}
+
+impl EnginePool {
+    pub fn new(
+        engine_path: &str,
+        depth: u32,
+        multi_pv: u32,
+        pool_size: usize,
+    ) -> Result<Self, EngineError> {
+        let key = PoolKey::with_defaults(engine_path, depth, multi_pv, pool_size);
+        key.validate()?;
+        Ok(Self {
+            pool: StockfishPool::new(&key)?,
+        })
+    }
+
+    /// Search `fen` to the pool's depth, returning its lines best first.
+    pub fn evaluate(&self, fen: &str) -> Result<Vec<LineScore>, EngineError> {
+        let mut payload = self.pool.evaluate(fen, &[], &mut ignore_progress)?;
+        payload.apply_point_of_view(true, CastlingMode::Standard)?;
+        Ok(payload
+            .pvs
+            .into_iter()
+            .map(|entry| LineScore {
+                cp: entry.cp,
+                mate: entry.mate,
+                moves: entry.moves,
+            })
+            .collect())
+    }
+}
+
+/// `stockfish_evaluate` for Rust callers: the full payload as JSON, with the
+/// same fields as the Python dict. The engine is taken from the shared pool
+/// registry.
+pub fn evaluate_fen_json(
+    fen: &str,
+    engine_path: &str,
+    depth: u32,
+    multi_pv: u32,
+    white_pov: bool,
+) -> Result<String, EngineError> {
+    let key = PoolKey::with_defaults(engine_path, depth, multi_pv, 1);
+    key.validate()?;
+    let pool = get_or_create_pool(&key)?;
+    payload_json(&pool, fen, white_pov)
+}
+
+fn payload_json(pool: &StockfishPool, fen: &str, white_pov: bool) -> EngineResult<String> {
+    let mut payload = pool.evaluate(fen, &[], &mut ignore_progress)?;
+    payload.apply_point_of_view(white_pov, pool.key.castling_mode())?;
+    serde_json::to_string(&payload)
+        .map_err(|err| EngineError::Engine(format!("Failed to serialize evaluation: {err}")))
+}
+
+enum BatchEntry {
+    Cached(String),
+    Evaluated(EvalPayload),
+    Failed(String, EngineError),
+}
+
+impl PoolKey {
+    /// Settings for a plain depth-limited search with every engine option at
+    /// its default.
+    fn with_defaults(engine_path: &str, depth: u32, multi_pv: u32, pool_size: usize) -> Self {
+        PoolKey {
+            engine_path: engine_path.to_string(),
+            depth,
+            multi_pv,
+            think_time_ms: None,
+            pool_size: pool_size.max(1),
+            hash_mb: None,
+            threads: None,
+            timeout_ms: None,
+            nice: None,
+            ponder: false,
+            syzygy_path: None,
+            syzygy_probe_depth: None,
+            skill_level: None,
+            uci_elo: None,
+            chess960: false,
+            nodes_limit: None,
+            mate_in: None,
+            options: Vec::new(),
+        }
+    }
+
+    /// Settings as the pyfunctions receive them: times in seconds, `hash_mb`
+    /// clamped to what the engine accepts and `options` sorted, so equal
+    /// settings always map to the same pool. The key is validated.
+    #[allow(clippy::too_many_arguments)]
+    fn from_settings(
+        engine_path: &str,
+        depth: u32,
+        multi_pv: u32,
+        think_time: Option<f64>,
+        pool_size: usize,
+        hash_mb: Option<u32>,
+        threads: Option<u32>,
+        timeout: Option<f64>,
+        nice: Option<i32>,
+        ponder: bool,
+        syzygy_path: Option<String>,
+        syzygy_probe_depth: Option<u32>,
+        skill_level: Option<u32>,
+        uci_elo: Option<u32>,
+        chess960: bool,
+        nodes_limit: Option<u64>,
+        mate_in: Option<u32>,
+        options: Option<Vec<(String, String)>>,
+    ) -> EngineResult<Self> {
+        let mut options = options.unwrap_or_default();
+        options.sort();
+        let key = PoolKey {
+            think_time_ms: seconds_to_ms(think_time),
+            hash_mb: hash_mb.map(|mb| mb.clamp(1, MAX_HASH_MB)),
+            threads,
+            timeout_ms: seconds_to_ms(timeout),
+            nice,
+            ponder,
+            syzygy_path,
+            syzygy_probe_depth,
+            skill_level,
+            uci_elo,
+            chess960,
+            nodes_limit,
+            mate_in,
+            options,
+            ..PoolKey::with_defaults(engine_path, depth, multi_pv, pool_size)
+        };
+        key.validate()?;
+        Ok(key)
+    }
+
+    /// Reject settings that would only fail once an engine is running.
+    fn validate(&self) -> EngineResult<()> {
+        if self.threads == Some(0) {
+            return Err(EngineError::InvalidInput(
+                "threads must be at least 1".to_string(),
+            ));
+        }
+        for (name, value) in &self.options {
+            if name.trim().is_empty() {
+                return Err(EngineError::InvalidInput(
+                    "engine option names must not be empty".to_string(),
+                ));
+            }
+            if [name, value].iter().any(|text| text.contains(['\n', '\r'])) {
+                return Err(EngineError::InvalidInput(format!(
+                    "engine option '{name}' must fit on one line"
+                )));
+            }
+        }
+        if let Some(path) = &self.syzygy_path {
+            let separator = if cfg!(windows) { ';' } else { ':' };
+            for dir in path.split(separator).filter(|dir| !dir.is_empty()) {
+                if !std::path::Path::new(dir).is_dir() {
+                    return Err(EngineError::Engine(format!(
+                        "Syzygy tablebase directory '{dir}' does not exist"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn castling_mode(&self) -> CastlingMode {
+        if self.chess960 {
+            CastlingMode::Chess960
+        } else {
+            CastlingMode::Standard
+        }
+    }
+}
+
+fn seconds_to_ms(seconds: Option<f64>) -> Option<u64> {
+    seconds.and_then(|secs| {
         if secs <= 0.0 {
             None
         } else {
             Some((secs * 1000.0).round().clamp(1.0, f64::MAX) as u64)
         }
-    });
-    let key = PoolKey {
-        engine_path: engine_path.clone(),
-        depth,
-        multi_pv,
-        think_time_ms,
-        pool_size: pool_size.max(1),
-    };
-    let pool = get_or_create_pool(&key)?;
-    let payload = pool.evaluate(&fen)?;
-    payload.to_pydict(py)
+    })
 }
 
-fn get_or_create_pool(key: &PoolKey) -> PyResult<Arc<StockfishPool>> {
+fn get_or_create_pool(key: &PoolKey) -> EngineResult<Arc<StockfishPool>> {
     let mut registry = STOCKFISH_POOLS.lock().unwrap();
     if let Some(pool) = registry.get(key) {
         return Ok(pool.clone());
@@ -59,103 +615,407 @@ fn get_or_create_pool(key: &PoolKey) -> PyResult<Arc<StockfishPool>> {
     Ok(pool)
 }
 
+type WorkerSpawner = Box<dyn Fn(&PoolKey) -> EngineResult<StockfishWorker> + Send + Sync>;
+
 struct StockfishPool {
     workers: Vec<Arc<Mutex<StockfishWorker>>>,
     next: AtomicUsize,
     key: PoolKey,
+    spawner: WorkerSpawner,
 }
 
 impl StockfishPool {
-    fn new(key: &PoolKey) -> PyResult<Self> {
+    fn new(key: &PoolKey) -> EngineResult<Self> {
         let worker_count = key.pool_size.max(1);
         let mut workers = Vec::with_capacity(worker_count);
         for _ in 0..worker_count {
-            workers.push(Arc::new(Mutex::new(StockfishWorker::spawn(
-                &key.engine_path,
-                key.multi_pv,
-            )?)));
+            workers.push(StockfishWorker::spawn(key)?);
         }
-        Ok(Self {
+        Ok(Self::with_workers(
+            key,
             workers,
+            Box::new(StockfishWorker::spawn),
+        ))
+    }
+
+    fn with_workers(key: &PoolKey, workers: Vec<StockfishWorker>, spawner: WorkerSpawner) -> Self {
+        Self {
+            workers: workers
+                .into_iter()
+                .map(|worker| Arc::new(Mutex::new(worker)))
+                .collect(),
             next: AtomicUsize::new(0),
             key: key.clone(),
-        })
+            spawner,
+        }
+    }
+
+    fn evaluate(
+        &self,
+        fen: &str,
+        searchmoves: &[String],
+        progress: ProgressFn<'_>,
+    ) -> EngineResult<EvalPayload> {
+        let idx = self.next.fetch_add(1, Ordering::SeqCst) % self.workers.len().max(1);
+        self.evaluate_on(idx, fen, searchmoves, progress)
     }
 
-    fn evaluate(&self, fen: &str) -> PyResult<EvalPayload> {
+    /// Play each candidate from `fen` and evaluate the result on one worker.
+    fn rank_candidates(
+        &self,
+        fen: &str,
+        candidates: &[String],
+    ) -> EngineResult<Vec<CandidateEval>> {
+        let position = crate::position_from_fen(fen, CastlingMode::Standard)
+            .map_err(EngineError::InvalidInput)?;
         let idx = self.next.fetch_add(1, Ordering::SeqCst) % self.workers.len().max(1);
+        let mut ranked = Vec::with_capacity(candidates.len());
+        for uci in candidates {
+            let mv = crate::uci_move_in(&position, fen, uci).map_err(EngineError::InvalidInput)?;
+            let san = SanPlus::from_move(position.clone(), &mv).to_string();
+            let mut after = position.clone();
+            after.play_unchecked(&mv);
+            let child_fen = Fen::from_position(after, EnPassantMode::Legal).to_string();
+            let payload = self.evaluate_on(idx, &child_fen, &[], &mut ignore_progress)?;
+            let top = payload.pvs.first();
+            ranked.push(CandidateEval {
+                uci: uci.clone(),
+                san,
+                cp: top.and_then(|entry| entry.cp).map(|cp| -cp),
+                mate: top.and_then(|entry| entry.mate).map(|mate| -mate),
+                best_reply: payload.best_move,
+                fen: child_fen,
+            });
+        }
+        ranked.sort_by_key(|candidate| std::cmp::Reverse(candidate.rank()));
+        Ok(ranked)
+    }
+
+    /// Evaluate on worker `idx`, replacing it if its engine has died.
+    ///
+    /// A worker whose process terminates mid-search is respawned and the search
+    /// retried, at most once per pool slot. If respawning fails, the error from
+    /// the failed evaluation is returned.
+    fn evaluate_on(
+        &self,
+        idx: usize,
+        fen: &str,
+        searchmoves: &[String],
+        progress: ProgressFn<'_>,
+    ) -> EngineResult<EvalPayload> {
         let worker_arc = self.workers[idx].clone();
         let mut worker = worker_arc.lock().unwrap();
-        worker.evaluate(fen, &self.key)
+        if worker.is_dead() {
+            *worker = (self.spawner)(&self.key)?;
+        }
+        let mut retries_left = self.workers.len().max(1);
+        loop {
+            match worker.evaluate(fen, &self.key, searchmoves, progress) {
+                Err(err) if worker.state == WorkerState::Terminated && retries_left > 0 => {
+                    retries_left -= 1;
+                    match (self.spawner)(&self.key) {
+                        Ok(fresh) => *worker = fresh,
+                        Err(_) => return Err(err),
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Evaluate `fens` in parallel, one thread per worker draining a shared queue.
+    ///
+    /// Results keep the input order; an invalid FEN or failed search only affects
+    /// its own entry.
+    fn evaluate_batch(&self, fens: &[String], known_fens: &HashSet<String>) -> Vec<BatchEntry> {
+        let known: HashSet<String> = known_fens
+            .iter()
+            .map(|fen| crate::canonicalize_fen_str(fen).unwrap_or_else(|_| fen.clone()))
+            .collect();
+        let mut results: Vec<Option<BatchEntry>> = Vec::with_capacity(fens.len());
+        let mut pending: Vec<usize> = Vec::new();
+        for (index, fen) in fens.iter().enumerate() {
+            match crate::canonicalize_fen_str(fen) {
+                Ok(canonical) if known.contains(&canonical) => {
+                    results.push(Some(BatchEntry::Cached(fen.clone())));
+                }
+                Ok(_) => {
+                    results.push(None);
+                    pending.push(index);
+                }
+                Err(err) => results.push(Some(BatchEntry::Failed(
+                    fen.clone(),
+                    EngineError::InvalidInput(format!("{err} (fen='{fen}')")),
+                ))),
+            }
+        }
+
+        let next_pending = AtomicUsize::new(0);
+        let evaluated: Mutex<Vec<(usize, BatchEntry)>> = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for idx in 0..self.workers.len().min(pending.len()) {
+                let next_pending = &next_pending;
+                let pending = &pending;
+                let evaluated = &evaluated;
+                scope.spawn(move || {
+                    while let Some(&index) =
+                        pending.get(next_pending.fetch_add(1, Ordering::SeqCst))
+                    {
+                        let fen = &fens[index];
+                        let entry = match self.evaluate_on(idx, fen, &[], &mut ignore_progress) {
+                            Ok(payload) => BatchEntry::Evaluated(payload),
+                            Err(err) => BatchEntry::Failed(fen.clone(), err),
+                        };
+                        evaluated.lock().unwrap().push((index, entry));
+                    }
+                });
+            }
+        });
+        for (index, entry) in evaluated.into_inner().unwrap() {
+            results[index] = Some(entry);
+        }
+        results.into_iter().flatten().collect()
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WorkerState {
+    Ready,
+    TimedOut,
+    Terminated,
+}
+
 struct StockfishWorker {
     io: Box<dyn EngineIo + Send>,
+    state: WorkerState,
+    warnings: Vec<String>,
 }
 
 impl StockfishWorker {
-    fn spawn(engine_path: &str, multi_pv: u32) -> PyResult<Self> {
-        let io = ProcessIo::spawn(engine_path).map_err(|err| {
-            PyRuntimeError::new_err(format!(
+    fn spawn(key: &PoolKey) -> EngineResult<Self> {
+        let io = ProcessIo::spawn(&key.engine_path, key.nice).map_err(|err| {
+            EngineError::Engine(format!(
                 "Unable to launch Stockfish at '{}': {err}",
-                engine_path
+                key.engine_path
             ))
         })?;
-        let mut worker = Self { io: Box::new(io) };
-        worker.initialize(multi_pv)?;
+        let mut warnings = Vec::new();
+        if key.nice.is_some() && !cfg!(unix) {
+            warnings.push("nice is only supported on Unix; ignoring".to_string());
+        }
+        let mut worker = Self {
+            io: Box::new(io),
+            state: WorkerState::Ready,
+            warnings,
+        };
+        worker.initialize(key)?;
         Ok(worker)
     }
 
-    fn initialize(&mut self, multi_pv: u32) -> PyResult<()> {
+    fn initialize(&mut self, key: &PoolKey) -> EngineResult<()> {
         self.send_line("uci")?;
         self.wait_for("uciok")?;
-        self.send_line(&format!("setoption name MultiPV value {}", multi_pv))?;
+        if let Some(threads) = key.threads {
+            self.send_line(&format!("setoption name Threads value {}", threads))?;
+        }
+        if let Some(hash_mb) = key.hash_mb {
+            self.send_line(&format!("setoption name Hash value {}", hash_mb))?;
+        }
+        self.send_line(&format!("setoption name MultiPV value {}", key.multi_pv))?;
+        if key.chess960 {
+            self.send_line("setoption name UCI_Chess960 value true")?;
+        }
+        if key.ponder {
+            self.send_line("setoption name Ponder value true")?;
+        }
+        if let Some(path) = &key.syzygy_path {
+            self.send_line(&format!("setoption name SyzygyPath value {}", path))?;
+        }
+        if let Some(depth) = key.syzygy_probe_depth {
+            self.send_line(&format!("setoption name SyzygyProbeDepth value {}", depth))?;
+        }
+        // A target Elo supersedes Skill Level; Stockfish would apply only one anyway.
+        if let Some(elo) = key.uci_elo {
+            self.send_line("setoption name UCI_LimitStrength value true")?;
+            self.send_line(&format!("setoption name UCI_Elo value {}", elo))?;
+        } else if let Some(level) = key.skill_level {
+            self.send_line(&format!("setoption name Skill Level value {}", level))?;
+        }
+        for (name, value) in &key.options {
+            self.send_line(&format!("setoption name {} value {}", name.trim(), value))?;
+        }
         self.send_line("isready")?;
         self.wait_for("readyok")
     }
 
-    fn evaluate(&mut self, fen: &str, key: &PoolKey) -> PyResult<EvalPayload> {
+    /// Run a search and collect its info lines until `bestmove`.
+    ///
+    /// With `timeout_ms` set, an overdue search is sent `stop`; if the engine then
+    /// answers with `bestmove` within another timeout window the partial result is
+    /// returned, otherwise the process is killed and the worker marked dead.
+    ///
+    /// Non-empty `searchmoves` restrict the search to those moves and are checked
+    /// for legality before anything is sent to the engine.
+    ///
+    /// `progress` is called whenever the search reaches a new depth. If it fails the
+    /// search is stopped and its error returned; an engine that does not answer
+    /// `stop` is killed so the worker is respawned on next use.
+    fn evaluate(
+        &mut self,
+        fen: &str,
+        key: &PoolKey,
+        searchmoves: &[String],
+        progress: ProgressFn<'_>,
+    ) -> EngineResult<EvalPayload> {
+        if !searchmoves.is_empty() {
+            let position = crate::position_from_fen(fen, key.castling_mode())
+                .map_err(EngineError::InvalidInput)?;
+            for uci in searchmoves {
+                crate::uci_move_in(&position, fen, uci).map_err(EngineError::InvalidInput)?;
+            }
+        }
         self.send_line("ucinewgame")?;
         self.send_line(&format!("position fen {}", fen))?;
-        self.send_line(&self.go_command(key))?;
+        self.send_line(&self.go_command(key, searchmoves))?;
         let mut parser = InfoParser::new();
+        parser.info_strings.extend(self.warnings.iter().cloned());
+        let timeout = key.timeout_ms.map(Duration::from_millis);
+        if !self.read_until_bestmove(&mut parser, timeout, progress)? {
+            self.send_line("stop")?;
+            if !self.read_until_bestmove(&mut parser, timeout, progress)? {
+                self.io.kill();
+                self.state = WorkerState::TimedOut;
+                return Err(EngineError::Engine(format!(
+                    "Stockfish timed out after {} ms evaluating '{}' and was killed",
+                    key.timeout_ms.unwrap_or_default(),
+                    fen
+                )));
+            }
+        }
+        parser.into_payload(fen)
+    }
+
+    /// Returns `Ok(false)` if `timeout` elapsed before `bestmove` arrived.
+    fn read_until_bestmove(
+        &mut self,
+        parser: &mut InfoParser,
+        timeout: Option<Duration>,
+        progress: ProgressFn<'_>,
+    ) -> EngineResult<bool> {
+        let deadline = timeout.map(|limit| Instant::now() + limit);
         loop {
-            let line = self.read_line().map_err(|err| {
-                PyRuntimeError::new_err(format!("Stockfish terminated unexpectedly: {err}"))
-            })?;
-            if line.starts_with("info ") {
+            let line = match self.read_line_until(deadline) {
+                Ok(Some(line)) => line,
+                Ok(None) => return Ok(false),
+                Err(err) => {
+                    self.state = WorkerState::Terminated;
+                    return Err(
+                        self.engine_error(format!("Stockfish terminated unexpectedly: {err}"))
+                    );
+                }
+            };
+            // Diagnostics such as "info string Available processors" are free
+            // text; parsing them as search info could pick up a stray "depth".
+            if let Some(text) = line.strip_prefix("info string") {
+                let text = text.trim();
+                if !text.is_empty() {
+                    parser.info_strings.push(text.to_string());
+                }
+            } else if line.starts_with("info ") {
+                let previous_depth = parser.depth;
                 parser.consume(&line);
+                if parser.depth > previous_depth
+                    && let Err(err) = progress(&parser.progress())
+                {
+                    self.abort_search(timeout);
+                    return Err(err);
+                }
             } else if line.starts_with("bestmove") {
-                break;
+                parser.consume_bestmove(&line);
+                return Ok(true);
             }
         }
-        parser.into_payload(fen)
     }
 
-    fn go_command(&self, key: &PoolKey) -> String {
-        if let Some(ms) = key.think_time_ms {
+    fn read_line_until(&mut self, deadline: Option<Instant>) -> std::io::Result<Option<String>> {
+        match deadline {
+            None => self.read_line().map(Some),
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Ok(None);
+                }
+                self.io.read_line_timeout(remaining)
+            }
+        }
+    }
+
+    /// Stop the running search and discard its output up to `bestmove`.
+    fn abort_search(&mut self, timeout: Option<Duration>) {
+        let mut discarded = InfoParser::new();
+        let stopped = self.send_line("stop").is_ok()
+            && matches!(
+                self.read_until_bestmove(&mut discarded, timeout, &mut ignore_progress),
+                Ok(true)
+            );
+        if !stopped {
+            self.io.kill();
+            self.state = WorkerState::TimedOut;
+        }
+    }
+
+    fn is_dead(&self) -> bool {
+        self.state != WorkerState::Ready
+    }
+
+    /// Build the `go` command from the first limit set, in order of precedence:
+    /// `mate_in`, then `nodes_limit`, then `think_time_ms`, falling back to `depth`.
+    fn go_command(&self, key: &PoolKey, searchmoves: &[String]) -> String {
+        let mut command = if let Some(moves) = key.mate_in {
+            format!("go mate {}", moves)
+        } else if let Some(nodes) = key.nodes_limit {
+            format!("go nodes {}", nodes)
+        } else if let Some(ms) = key.think_time_ms {
             format!("go movetime {}", ms)
         } else {
             format!("go depth {}", key.depth)
+        };
+        if !searchmoves.is_empty() {
+            command.push_str(" searchmoves ");
+            command.push_str(&searchmoves.join(" "));
         }
+        command
     }
 
-    fn send_line(&mut self, line: &str) -> PyResult<()> {
-        self.io.write_line(line).map_err(|err| {
-            PyRuntimeError::new_err(format!("Failed to communicate with Stockfish: {err}"))
-        })
+    fn send_line(&mut self, line: &str) -> EngineResult<()> {
+        if let Err(err) = self.io.write_line(line) {
+            self.state = WorkerState::Terminated;
+            return Err(self.engine_error(format!("Failed to communicate with Stockfish: {err}")));
+        }
+        Ok(())
+    }
+
+    /// Runtime error for a failed engine, with whatever it last wrote to stderr.
+    fn engine_error(&mut self, message: String) -> EngineError {
+        let tail = self.io.stderr_tail();
+        if tail.is_empty() {
+            return EngineError::Engine(message);
+        }
+        EngineError::Engine(format!("{message}\nStockfish stderr:\n{}", tail.join("\n")))
     }
 
     fn read_line(&mut self) -> std::io::Result<String> {
         self.io.read_line()
     }
 
-    fn wait_for(&mut self, needle: &str) -> PyResult<()> {
+    fn wait_for(&mut self, needle: &str) -> EngineResult<()> {
         loop {
-            let line = self.read_line().map_err(|err| {
-                PyRuntimeError::new_err(format!("Error waiting for '{}': {err}", needle))
-            })?;
+            let line = match self.read_line() {
+                Ok(line) => line,
+                Err(err) => {
+                    return Err(self.engine_error(format!("Error waiting for '{}': {err}", needle)));
+                }
+            };
             if line.contains(needle) {
                 return Ok(());
             }
@@ -172,22 +1032,48 @@ impl Drop for StockfishWorker {
 trait EngineIo {
     fn write_line(&mut self, line: &str) -> std::io::Result<()>;
     fn read_line(&mut self) -> std::io::Result<String>;
+    /// Returns `Ok(None)` when no line arrives within `timeout`.
+    fn read_line_timeout(&mut self, timeout: Duration) -> std::io::Result<Option<String>>;
+    fn kill(&mut self);
     fn shutdown(&mut self);
+    /// The last lines the engine wrote to stderr, oldest first.
+    fn stderr_tail(&mut self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 struct ProcessIo {
     child: Child,
     stdin: BufWriter<ChildStdin>,
-    stdout: BufReader<ChildStdout>,
+    lines: Receiver<std::io::Result<String>>,
+    reader: Option<JoinHandle<()>>,
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    stderr_reader: Option<JoinHandle<()>>,
 }
 
 impl ProcessIo {
-    fn spawn(engine_path: &str) -> std::io::Result<Self> {
-        let mut child = Command::new(engine_path)
+    fn spawn(engine_path: &str, nice: Option<i32>) -> std::io::Result<Self> {
+        let mut command = Command::new(engine_path);
+        command
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()?;
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        if let Some(niceness) = nice {
+            use std::os::unix::process::CommandExt;
+            // SAFETY: setpriority is async-signal-safe and touches no shared state.
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setpriority(libc::PRIO_PROCESS, 0, niceness) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = nice;
+        let mut child = command.spawn()?;
         let stdin = child
             .stdin
             .take()
@@ -196,14 +1082,63 @@ impl ProcessIo {
             .stdout
             .take()
             .ok_or_else(|| std::io::Error::other("missing stdout"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| std::io::Error::other("missing stderr"))?;
+        let (sender, lines) = mpsc::channel();
+        let reader = thread::spawn(move || read_stdout_lines(BufReader::new(stdout), sender));
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+        let tail = stderr_tail.clone();
+        let stderr_reader = thread::spawn(move || read_stderr_tail(BufReader::new(stderr), tail));
         Ok(Self {
             child,
             stdin: BufWriter::new(stdin),
-            stdout: BufReader::new(stdout),
+            lines,
+            reader: Some(reader),
+            stderr_tail,
+            stderr_reader: Some(stderr_reader),
         })
     }
 }
 
+/// Forward engine stdout line by line so reads can be bounded by a timeout.
+fn read_stdout_lines(
+    mut stdout: BufReader<ChildStdout>,
+    sender: mpsc::Sender<std::io::Result<String>>,
+) {
+    loop {
+        let mut buf = String::new();
+        let result = match stdout.read_line(&mut buf) {
+            Ok(0) => Err(closed_pipe_error()),
+            Ok(_) => Ok(buf),
+            Err(err) => Err(err),
+        };
+        let finished = result.is_err();
+        if sender.send(result).is_err() || finished {
+            return;
+        }
+    }
+}
+
+/// Keep the last `STDERR_TAIL_LINES` lines of engine stderr until the pipe closes.
+fn read_stderr_tail(stderr: BufReader<ChildStderr>, tail: Arc<Mutex<VecDeque<String>>>) {
+    for line in stderr.lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        let mut tail = tail.lock().unwrap();
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+}
+
+fn closed_pipe_error() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Stockfish closed pipe")
+}
+
 impl EngineIo for ProcessIo {
     fn write_line(&mut self, line: &str) -> std::io::Result<()> {
         self.stdin.write_all(line.as_bytes())?;
@@ -212,49 +1147,126 @@ impl EngineIo for ProcessIo {
     }
 
     fn read_line(&mut self) -> std::io::Result<String> {
-        let mut buf = String::new();
-        let bytes = self.stdout.read_line(&mut buf)?;
-        if bytes == 0 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::UnexpectedEof,
-                "Stockfish closed pipe",
-            ));
+        self.lines
+            .recv()
+            .unwrap_or_else(|_| Err(closed_pipe_error()))
+    }
+
+    fn read_line_timeout(&mut self, timeout: Duration) -> std::io::Result<Option<String>> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(result) => result.map(Some),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(closed_pipe_error()),
         }
-        Ok(buf)
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
     }
 
     fn shutdown(&mut self) {
         let _ = self.write_line("quit");
         let _ = self.child.wait();
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        if let Some(reader) = self.stderr_reader.take() {
+            let _ = reader.join();
+        }
+    }
+
+    fn stderr_tail(&mut self) -> Vec<String> {
+        // A failing engine may still be exiting; once it has, let the reader drain
+        // what it wrote last.
+        let deadline = Instant::now() + STDERR_EXIT_GRACE;
+        while matches!(self.child.try_wait(), Ok(None)) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        if matches!(self.child.try_wait(), Ok(Some(_)))
+            && let Some(reader) = self.stderr_reader.take()
+        {
+            let _ = reader.join();
+        }
+        self.stderr_tail.lock().unwrap().iter().cloned().collect()
     }
 }
 
 struct InfoParser {
     depth: u32,
+    seldepth: u32,
     nodes: u64,
+    nps: u64,
+    hashfull: u32,
+    tbhits: u64,
     entries: HashMap<u32, PvEntry>,
+    info_strings: Vec<String>,
+    best_move: Option<String>,
+    ponder_move: Option<String>,
 }
 
 impl InfoParser {
     fn new() -> Self {
         Self {
             depth: 0,
+            seldepth: 0,
             nodes: 0,
+            nps: 0,
+            hashfull: 0,
+            tbhits: 0,
             entries: HashMap::new(),
+            info_strings: Vec::new(),
+            best_move: None,
+            ponder_move: None,
         }
     }
 
-    fn consume(&mut self, line: &str) {
+    /// Record `bestmove <move> [ponder <move>]`; `bestmove (none)` leaves both unset.
+    fn consume_bestmove(&mut self, line: &str) {
         let mut tokens = line.split_whitespace();
-        let mut current_multipv = 1;
-        let mut cp: Option<i32> = None;
-        let mut mate: Option<i32> = None;
         while let Some(token) = tokens.next() {
             match token {
-                "depth" => {
-                    if let Some(parsed) = tokens.next().and_then(|value| value.parse::<u32>().ok())
-                    {
-                        self.depth = parsed;
+                "bestmove" => {
+                    self.best_move = tokens
+                        .next()
+                        .filter(|mv| *mv != "(none)")
+                        .map(str::to_string);
+                }
+                "ponder" => {
+                    self.ponder_move = tokens
+                        .next()
+                        .filter(|mv| *mv != "(none)")
+                        .map(str::to_string);
+                }
+                _ => {}
+            }
+        }
+        if self.best_move.is_none() {
+            self.ponder_move = None;
+        }
+    }
+
+    fn consume(&mut self, line: &str) {
+        let mut tokens = line.split_whitespace().peekable();
+        let mut current_multipv = 1;
+        let mut cp: Option<i32> = None;
+        let mut mate: Option<i32> = None;
+        let mut bound: Option<String> = None;
+        let mut line_tbhits = 0u64;
+        let mut line_depth = 0u32;
+        while let Some(token) = tokens.next() {
+            match token {
+                "depth" => {
+                    if let Some(parsed) = tokens.next().and_then(|value| value.parse::<u32>().ok())
+                    {
+                        self.depth = parsed;
+                        line_depth = parsed;
+                    }
+                }
+                "seldepth" => {
+                    if let Some(parsed) = tokens.next().and_then(|value| value.parse::<u32>().ok())
+                    {
+                        self.seldepth = parsed;
                     }
                 }
                 "nodes" => {
@@ -263,6 +1275,25 @@ impl InfoParser {
                         self.nodes = parsed;
                     }
                 }
+                "nps" => {
+                    if let Some(parsed) = tokens.next().and_then(|value| value.parse::<u64>().ok())
+                    {
+                        self.nps = parsed;
+                    }
+                }
+                "hashfull" => {
+                    if let Some(parsed) = tokens.next().and_then(|value| value.parse::<u32>().ok())
+                    {
+                        self.hashfull = parsed;
+                    }
+                }
+                "tbhits" => {
+                    if let Some(parsed) = tokens.next().and_then(|value| value.parse::<u64>().ok())
+                    {
+                        self.tbhits = parsed;
+                        line_tbhits = parsed;
+                    }
+                }
                 "multipv" => {
                     if let Some(parsed) = tokens.next().and_then(|value| value.parse::<u32>().ok())
                     {
@@ -282,13 +1313,30 @@ impl InfoParser {
                             }
                             _ => {}
                         }
+                        bound = tokens
+                            .next_if(|value| matches!(*value, "lowerbound" | "upperbound"))
+                            .map(str::to_string);
                     }
                 }
                 "pv" => {
                     let moves: Vec<String> = tokens.map(|mv| mv.to_string()).collect();
-                    if !moves.is_empty() {
-                        self.entries
-                            .insert(current_multipv, PvEntry { cp, mate, moves });
+                    // A stray shallower line must not replace a deeper one, and
+                    // a provisional bound must not replace an exact score.
+                    let keep_existing = self.entries.get(&current_multipv).is_some_and(|entry| {
+                        line_depth < entry.depth || (bound.is_some() && entry.bound.is_none())
+                    });
+                    if !moves.is_empty() && !keep_existing {
+                        self.entries.insert(
+                            current_multipv,
+                            PvEntry {
+                                cp,
+                                mate,
+                                moves,
+                                tbhits: line_tbhits,
+                                depth: line_depth,
+                                bound,
+                            },
+                        );
                     }
                     break;
                 }
@@ -297,37 +1345,156 @@ impl InfoParser {
         }
     }
 
-    fn into_payload(self, fen: &str) -> PyResult<EvalPayload> {
+    fn progress(&self) -> SearchProgress {
+        let top = self.entries.get(&1);
+        SearchProgress {
+            depth: self.depth,
+            nodes: self.nodes,
+            cp: top.and_then(|entry| entry.cp),
+            mate: top.and_then(|entry| entry.mate),
+        }
+    }
+
+    fn into_payload(self, fen: &str) -> EngineResult<EvalPayload> {
         let mut entries: Vec<(u32, PvEntry)> = self.entries.into_iter().collect();
         entries.sort_by_key(|(multipv, _)| *multipv);
+        let top = entries.first().map(|(_, entry)| entry);
+        let tablebase = top.is_some_and(|entry| entry.tbhits > 0);
+        let dtz = top
+            .filter(|_| tablebase)
+            .and_then(PvEntry::distance_to_zero);
         Ok(EvalPayload {
             fen: fen.to_string(),
             depth: self.depth,
+            seldepth: self.seldepth,
             knodes: self.nodes / 1000,
+            nps: self.nps,
+            hashfull: self.hashfull,
+            tbhits: self.tbhits,
+            tablebase,
+            dtz,
+            best_move: self.best_move,
+            ponder_move: self.ponder_move,
             pvs: entries.into_iter().map(|(_, entry)| entry).collect(),
+            info_strings: self.info_strings,
         })
     }
 }
 
-struct PvEntry {
+type ProgressFn<'a> = &'a mut dyn FnMut(&SearchProgress) -> EngineResult<()>;
+
+fn ignore_progress(_: &SearchProgress) -> EngineResult<()> {
+    Ok(())
+}
+
+/// Snapshot of a search handed to progress callbacks when a new depth completes.
+struct SearchProgress {
+    depth: u32,
+    nodes: u64,
     cp: Option<i32>,
     mate: Option<i32>,
-    moves: Vec<String>,
 }
 
-struct EvalPayload {
-    fen: String,
-    depth: u32,
-    knodes: u64,
-    pvs: Vec<PvEntry>,
+impl SearchProgress {
+    fn to_pydict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("depth", self.depth)?;
+        dict.set_item("nodes", self.nodes)?;
+        dict.set_item("cp", self.cp)?;
+        dict.set_item("mate", self.mate)?;
+        Ok(dict)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PvEntry {
+    pub cp: Option<i32>,
+    pub mate: Option<i32>,
+    pub moves: Vec<String>,
+    pub tbhits: u64,
+    /// Search depth of the info line this entry came from.
+    pub depth: u32,
+    /// `lowerbound` or `upperbound` when the score is provisional, from a
+    /// search that failed high or low.
+    #[serde(default)]
+    pub bound: Option<String>,
+}
+
+impl PvEntry {
+    /// Signed plies to the tablebase result, from a mate score or a `cp` TB-win score.
+    /// Mate scores count moves, so mating in `n` takes `2n - 1` plies and being
+    /// mated in `n` takes `2n`.
+    fn distance_to_zero(&self) -> Option<i32> {
+        if let Some(mate) = self.mate {
+            return Some(match mate {
+                1.. => 2 * mate - 1,
+                _ => 2 * mate,
+            });
+        }
+        let cp = self.cp?;
+        let plies = TB_WIN_CP - cp.abs();
+        if (0..=TB_MAX_PLY).contains(&plies) {
+            Some(plies * cp.signum())
+        } else {
+            None
+        }
+    }
+}
+
+/// Everything one search reports, as returned by `stockfish_evaluate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalPayload {
+    pub fen: String,
+    pub depth: u32,
+    pub seldepth: u32,
+    pub knodes: u64,
+    pub nps: u64,
+    pub hashfull: u32,
+    pub tbhits: u64,
+    pub tablebase: bool,
+    pub dtz: Option<i32>,
+    pub best_move: Option<String>,
+    pub ponder_move: Option<String>,
+    pub pvs: Vec<PvEntry>,
+    pub info_strings: Vec<String>,
 }
 
 impl EvalPayload {
+    /// Stockfish scores are relative to the side to move; with `white_pov` they are
+    /// negated for Black-to-move positions so every score reads from White's side.
+    fn apply_point_of_view(&mut self, white_pov: bool, mode: CastlingMode) -> EngineResult<()> {
+        if !white_pov {
+            return Ok(());
+        }
+        let turn = crate::position_from_fen(&self.fen, mode)
+            .map_err(EngineError::InvalidInput)?
+            .turn();
+        if turn == Color::White {
+            return Ok(());
+        }
+        for entry in &mut self.pvs {
+            entry.cp = entry.cp.map(|cp| -cp);
+            entry.mate = entry.mate.map(|mate| -mate);
+        }
+        self.dtz = self.dtz.map(|dtz| -dtz);
+        Ok(())
+    }
+
     fn to_pydict(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
         let dict = PyDict::new(py);
         dict.set_item("fen", &self.fen)?;
         dict.set_item("depth", self.depth)?;
+        dict.set_item("seldepth", self.seldepth)?;
         dict.set_item("knodes", self.knodes)?;
+        dict.set_item("nps", self.nps)?;
+        dict.set_item("hashfull", self.hashfull)?;
+        dict.set_item("tbhits", self.tbhits)?;
+        dict.set_item("tablebase", self.tablebase)?;
+        if let Some(dtz) = self.dtz {
+            dict.set_item("dtz", dtz)?;
+        }
+        dict.set_item("best_move", &self.best_move)?;
+        dict.set_item("ponder_move", &self.ponder_move)?;
         let pv_list = PyList::empty(py);
         for entry in &self.pvs {
             let pv_dict = PyDict::new(py);
@@ -341,10 +1508,12 @@ impl EvalPayload {
                     pv_dict.set_item("score", mate)?;
                 }
             }
+            pv_dict.set_item("bound", &entry.bound)?;
             pv_dict.set_item("moves", entry.moves.join(" "))?;
             pv_list.append(pv_dict)?;
         }
         dict.set_item("pvs", pv_list)?;
+        dict.set_item("info_strings", &self.info_strings)?;
         Ok(dict.into())
     }
 }
@@ -354,20 +1523,35 @@ mod tests {
     use super::*;
     use std::sync::Mutex as StdMutex;
 
-    struct MockIo {
+    /// Scripted response that makes a timed read report a timeout once.
+    const STALL: &str = "<stall>";
+
+    pub(super) struct MockIo {
         writes: Arc<StdMutex<Vec<String>>>,
         reads: Vec<String>,
+        hangs: bool,
+        killed: Arc<StdMutex<bool>>,
     }
 
     impl MockIo {
-        fn new(responses: Vec<&str>) -> Self {
+        pub(super) fn new(responses: Vec<&str>) -> Self {
             Self {
                 writes: Arc::new(StdMutex::new(Vec::new())),
                 reads: responses.into_iter().map(|s| format!("{s}\n")).collect(),
+                hangs: false,
+                killed: Arc::new(StdMutex::new(false)),
             }
         }
 
-        fn writes(&self) -> Arc<StdMutex<Vec<String>>> {
+        /// A mock that stops responding (rather than closing) once its lines run out.
+        fn hanging(responses: Vec<&str>) -> Self {
+            Self {
+                hangs: true,
+                ..Self::new(responses)
+            }
+        }
+
+        pub(super) fn writes(&self) -> Arc<StdMutex<Vec<String>>> {
             self.writes.clone()
         }
     }
@@ -388,24 +1572,469 @@ mod tests {
             Ok(self.reads.remove(0))
         }
 
+        fn read_line_timeout(&mut self, _timeout: Duration) -> std::io::Result<Option<String>> {
+            if self.reads.is_empty() && self.hangs {
+                return Ok(None);
+            }
+            if self.reads.first().map(|line| line.trim_end()) == Some(STALL) {
+                self.reads.remove(0);
+                return Ok(None);
+            }
+            self.read_line().map(Some)
+        }
+
+        fn kill(&mut self) {
+            *self.killed.lock().unwrap() = true;
+        }
+
         fn shutdown(&mut self) {}
     }
 
     impl StockfishWorker {
-        fn with_io(io: Box<dyn EngineIo + Send>) -> Self {
-            Self { io }
+        pub(super) fn with_io(io: Box<dyn EngineIo + Send>) -> Self {
+            Self {
+                io,
+                state: WorkerState::Ready,
+                warnings: Vec::new(),
+            }
         }
     }
 
+    pub(super) fn test_key() -> PoolKey {
+        PoolKey::with_defaults("engine", 12, 2, 1)
+    }
+
+    #[test]
+    fn from_settings_normalizes_equal_settings_to_one_key() {
+        let settings = |hash_mb, options: Vec<(&str, &str)>| {
+            let options = options
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            PoolKey::from_settings(
+                "engine",
+                12,
+                2,
+                Some(0.5),
+                0,
+                Some(hash_mb),
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                Some(options),
+            )
+            .unwrap()
+        };
+        let key = settings(u32::MAX, vec![("b", "2"), ("a", "1")]);
+        assert!(key == settings(MAX_HASH_MB, vec![("a", "1"), ("b", "2")]));
+        assert_eq!(key.think_time_ms, Some(500));
+        assert_eq!(key.pool_size, 1);
+        assert_eq!(key.hash_mb, Some(MAX_HASH_MB));
+    }
+
     #[test]
     fn parser_collects_multiple_pvs() {
         let mut parser = InfoParser::new();
-        parser.consume("info depth 10 nodes 100000 multipv 1 score cp 50 pv e2e4 e7e5");
-        parser.consume("info depth 10 nodes 100000 multipv 2 score cp 30 pv d2d4 d7d5");
+        parser.consume(
+            "info depth 10 seldepth 14 multipv 1 score cp 50 nodes 100000 nps 250000 \
+             hashfull 12 tbhits 3 time 400 pv e2e4 e7e5",
+        );
+        parser.consume(
+            "info depth 10 seldepth 15 multipv 2 score cp 30 nodes 100000 nps 250000 \
+             hashfull 13 tbhits 4 time 400 pv d2d4 d7d5",
+        );
         let payload = parser.into_payload("fen").unwrap();
         assert_eq!(payload.pvs.len(), 2);
         assert_eq!(payload.depth, 10);
+        assert_eq!(payload.seldepth, 15);
         assert_eq!(payload.knodes, 100);
+        assert_eq!(payload.nps, 250000);
+        assert_eq!(payload.hashfull, 13);
+        assert_eq!(payload.tbhits, 4);
+    }
+
+    #[test]
+    fn parser_keeps_deeper_line_when_a_shallower_one_arrives_late() {
+        let mut parser = InfoParser::new();
+        parser.consume("info depth 20 multipv 1 score cp 45 nodes 90000 pv e2e4 e7e5");
+        parser.consume("info depth 10 multipv 1 score cp -10 nodes 90000 pv a2a3");
+        parser.consume("info depth 20 multipv 2 score cp 30 nodes 90000 pv d2d4");
+        let payload = parser.into_payload("fen").unwrap();
+        assert_eq!(payload.pvs[0].moves, vec!["e2e4", "e7e5"]);
+        assert_eq!(payload.pvs[0].cp, Some(45));
+        assert_eq!(payload.pvs[0].depth, 20);
+
+        let mut parser = InfoParser::new();
+        parser.consume("info depth 20 multipv 1 score cp 45 nodes 90000 pv e2e4");
+        parser.consume("info depth 21 multipv 1 score cp 50 nodes 90000 pv d2d4");
+        let payload = parser.into_payload("fen").unwrap();
+        assert_eq!(payload.pvs[0].moves, vec!["d2d4"]);
+    }
+
+    #[test]
+    fn parser_records_score_bounds_without_overwriting_exact_scores() {
+        let mut parser = InfoParser::new();
+        parser.consume("info depth 18 multipv 1 score cp 50 lowerbound nodes 9000 pv e2e4");
+        let payload = parser.into_payload("fen").unwrap();
+        assert_eq!(payload.pvs[0].cp, Some(50));
+        assert_eq!(payload.pvs[0].bound.as_deref(), Some("lowerbound"));
+        assert_eq!(payload.pvs[0].moves, vec!["e2e4"]);
+
+        let mut parser = InfoParser::new();
+        parser.consume("info depth 18 multipv 1 score cp 32 nodes 9000 pv d2d4 d7d5");
+        parser.consume("info depth 19 multipv 1 score mate 4 upperbound nodes 9900 pv e2e4");
+        let payload = parser.into_payload("fen").unwrap();
+        assert_eq!(payload.pvs[0].cp, Some(32));
+        assert_eq!(payload.pvs[0].bound, None);
+        assert_eq!(payload.pvs[0].moves, vec!["d2d4", "d7d5"]);
+
+        let mut parser = InfoParser::new();
+        parser.consume("info depth 19 multipv 1 score cp 80 upperbound nodes 9000 pv e2e4");
+        parser.consume("info depth 19 multipv 1 score cp 41 nodes 9900 pv c2c4");
+        let payload = parser.into_payload("fen").unwrap();
+        assert_eq!(payload.pvs[0].cp, Some(41));
+        assert_eq!(payload.pvs[0].bound, None);
+    }
+
+    #[test]
+    fn parser_flags_tablebase_backed_top_line() {
+        let mut parser = InfoParser::new();
+        parser.consume("info depth 30 multipv 1 score cp 19985 nodes 5000 tbhits 42 pv e1e2 e8d8");
+        parser.consume("info depth 30 multipv 2 score cp 0 nodes 5000 tbhits 42 pv e1d1 e8d8");
+        let payload = parser.into_payload("fen").unwrap();
+        assert!(payload.tablebase);
+        assert_eq!(payload.dtz, Some(15));
+
+        let mut parser = InfoParser::new();
+        parser.consume("info depth 20 multipv 1 score cp 35 nodes 5000 pv e2e4");
+        let payload = parser.into_payload("fen").unwrap();
+        assert!(!payload.tablebase);
+        assert_eq!(payload.dtz, None);
+    }
+
+    #[test]
+    fn distance_to_zero_is_in_plies_for_mate_and_cp_scores() {
+        let entry = |cp: Option<i32>, mate: Option<i32>| PvEntry {
+            cp,
+            mate,
+            moves: vec!["e1e2".to_string()],
+            tbhits: 1,
+            depth: 30,
+            bound: None,
+        };
+        assert_eq!(entry(None, Some(3)).distance_to_zero(), Some(5));
+        assert_eq!(entry(None, Some(1)).distance_to_zero(), Some(1));
+        assert_eq!(entry(None, Some(-3)).distance_to_zero(), Some(-6));
+        assert_eq!(entry(None, Some(0)).distance_to_zero(), Some(0));
+        assert_eq!(entry(Some(19985), None).distance_to_zero(), Some(15));
+        assert_eq!(entry(Some(-19990), None).distance_to_zero(), Some(-10));
+        assert_eq!(entry(Some(120), None).distance_to_zero(), None);
+    }
+
+    #[test]
+    fn white_pov_flips_scores_only_for_black_to_move() {
+        const BLACK_TO_MOVE: &str = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let evaluate = |white_pov: bool| {
+            let mock = MockIo::new(vec![
+                "info depth 12 multipv 1 score cp 40 nodes 1000 pv e7e5",
+                "info depth 12 multipv 2 score mate 3 nodes 1000 pv d7d5",
+                "bestmove e7e5",
+            ]);
+            let mut worker = StockfishWorker::with_io(Box::new(mock));
+            let mut payload = worker
+                .evaluate(BLACK_TO_MOVE, &test_key(), &[], &mut ignore_progress)
+                .unwrap();
+            payload
+                .apply_point_of_view(white_pov, CastlingMode::Standard)
+                .unwrap();
+            (payload.pvs[0].cp, payload.pvs[1].mate)
+        };
+
+        assert_eq!(evaluate(false), (Some(40), Some(3)));
+        assert_eq!(evaluate(true), (Some(-40), Some(-3)));
+    }
+
+    #[test]
+    fn point_of_view_is_not_checked_without_white_pov() {
+        // Stockfish searches positions shakmaty rejects, such as a pawn on the
+        // back rank; callers that keep mover-relative scores must get them.
+        const BACK_RANK_PAWN: &str = "4k3/8/8/8/8/8/8/4K2P b - - 0 1";
+        let mut parser = InfoParser::new();
+        parser.consume("info depth 12 multipv 1 score cp 55 nodes 1000 pv e8d7");
+        let mut payload = parser.into_payload(BACK_RANK_PAWN).unwrap();
+
+        payload
+            .apply_point_of_view(false, CastlingMode::Standard)
+            .unwrap();
+        assert_eq!(payload.pvs[0].cp, Some(55));
+        assert!(
+            payload
+                .apply_point_of_view(true, CastlingMode::Standard)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn progress_is_reported_once_per_new_depth() {
+        let mock = MockIo::new(vec![
+            "info depth 1 multipv 1 score cp 12 nodes 20 pv e2e4",
+            "info depth 2 multipv 1 score cp 18 nodes 90 pv e2e4 e7e5",
+            "info depth 2 multipv 2 score cp 10 nodes 95 pv d2d4 d7d5",
+            "info string NNUE evaluation enabled",
+            "info depth 3 multipv 1 score mate 4 nodes 400 pv e2e4 e7e5",
+            "bestmove e2e4",
+        ]);
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let mut seen = Vec::new();
+        worker
+            .evaluate("fen", &test_key(), &[], &mut |update| {
+                seen.push((update.depth, update.nodes, update.cp, update.mate));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            seen,
+            vec![
+                (1, 20, Some(12), None),
+                (2, 90, Some(18), None),
+                (3, 400, None, Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn failing_progress_callback_stops_search_and_keeps_worker() {
+        let mock = MockIo::new(vec![
+            "info depth 1 multipv 1 score cp 12 nodes 20 pv e2e4",
+            "info depth 2 multipv 1 score cp 18 nodes 90 pv e2e4 e7e5",
+            "bestmove e2e4",
+        ]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let mut calls = 0;
+        let err = worker
+            .evaluate("fen", &test_key(), &[], &mut |_| {
+                calls += 1;
+                Err(EngineError::Engine("cancelled by caller".to_string()))
+            })
+            .unwrap_err();
+        assert_eq!(calls, 1);
+        assert!(err.to_string().contains("cancelled by caller"));
+        assert!(!worker.is_dead());
+        let writes = writes_handle.lock().unwrap();
+        assert_eq!(writes.last().map(String::as_str), Some("stop"));
+    }
+
+    #[test]
+    fn worker_captures_bestmove_and_ponder() {
+        let mock = MockIo::new(vec![
+            "info depth 18 seldepth 24 multipv 1 score cp 31 nodes 912345 pv e2e4 e7e5 g1f3",
+            "bestmove e2e4 ponder e7e5",
+        ]);
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let payload = worker
+            .evaluate("fen", &test_key(), &[], &mut ignore_progress)
+            .unwrap();
+        assert_eq!(payload.best_move.as_deref(), Some("e2e4"));
+        assert_eq!(payload.ponder_move.as_deref(), Some("e7e5"));
+
+        let mock = MockIo::new(vec!["info depth 0 score mate 0", "bestmove (none)"]);
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let payload = worker
+            .evaluate("fen", &test_key(), &[], &mut ignore_progress)
+            .unwrap();
+        assert_eq!(payload.best_move, None);
+        assert_eq!(payload.ponder_move, None);
+    }
+
+    #[test]
+    fn info_string_lines_are_collected_without_touching_search_info() {
+        let mock = MockIo::new(vec![
+            "info string Available processors: 0-7",
+            "info depth 14 seldepth 20 multipv 1 score cp 27 nodes 50000 pv e2e4 e7e5",
+            "info string depth 99 score cp -500 nodes 1 pv a2a3",
+            "info depth 15 seldepth 21 multipv 1 score cp 29 nodes 64000 pv e2e4 c7c5",
+            "info string",
+            "bestmove e2e4",
+        ]);
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let payload = worker
+            .evaluate("fen", &test_key(), &[], &mut ignore_progress)
+            .unwrap();
+        assert_eq!(payload.depth, 15);
+        assert_eq!(payload.pvs.len(), 1);
+        assert_eq!(payload.pvs[0].cp, Some(29));
+        assert_eq!(payload.pvs[0].moves, vec!["e2e4", "c7c5"]);
+        assert_eq!(
+            payload.info_strings,
+            vec![
+                "Available processors: 0-7",
+                "depth 99 score cp -500 nodes 1 pv a2a3",
+            ]
+        );
+    }
+
+    #[test]
+    fn ponder_option_is_sent_and_ponder_move_captured() {
+        let mock = MockIo::new(vec![
+            "uciok",
+            "readyok",
+            "info depth 12 multipv 1 score cp 25 nodes 40000 pv e2e4 e7e5",
+            "bestmove e2e4 ponder e7e5",
+        ]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let key = PoolKey {
+            ponder: true,
+            ..test_key()
+        };
+        worker.initialize(&key).unwrap();
+        let payload = worker
+            .evaluate("fen", &key, &[], &mut ignore_progress)
+            .unwrap();
+        assert_eq!(payload.best_move.as_deref(), Some("e2e4"));
+        assert_eq!(payload.ponder_move.as_deref(), Some("e7e5"));
+        let writes = writes_handle.lock().unwrap();
+        let ponder_at = writes
+            .iter()
+            .position(|cmd| cmd == "setoption name Ponder value true")
+            .expect("ponder option sent");
+        let ready_at = writes.iter().position(|cmd| cmd == "isready").unwrap();
+        assert!(ponder_at < ready_at);
+    }
+
+    #[test]
+    fn initialize_configures_syzygy_tablebases() {
+        let mock = MockIo::new(vec!["uciok", "readyok"]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let key = PoolKey {
+            syzygy_path: Some("/tb/345:/tb/6".into()),
+            syzygy_probe_depth: Some(4),
+            ..test_key()
+        };
+        worker.initialize(&key).unwrap();
+        let writes = writes_handle.lock().unwrap();
+        assert!(writes.contains(&"setoption name SyzygyPath value /tb/345:/tb/6".to_string()));
+        assert!(writes.contains(&"setoption name SyzygyProbeDepth value 4".to_string()));
+    }
+
+    #[test]
+    fn initialize_limits_strength_by_skill_level_or_elo() {
+        let strength_commands = |skill_level: Option<u32>, uci_elo: Option<u32>| {
+            let mock = MockIo::new(vec!["uciok", "readyok"]);
+            let writes_handle = mock.writes();
+            let mut worker = StockfishWorker::with_io(Box::new(mock));
+            let key = PoolKey {
+                skill_level,
+                uci_elo,
+                ..test_key()
+            };
+            worker.initialize(&key).unwrap();
+            let writes = writes_handle.lock().unwrap();
+            writes
+                .iter()
+                .filter(|cmd| cmd.contains("Skill Level") || cmd.contains("UCI_"))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        assert!(strength_commands(None, None).is_empty());
+        assert_eq!(
+            strength_commands(Some(5), None),
+            vec!["setoption name Skill Level value 5"]
+        );
+        let elo_commands = vec![
+            "setoption name UCI_LimitStrength value true",
+            "setoption name UCI_Elo value 1500",
+        ];
+        assert_eq!(strength_commands(None, Some(1500)), elo_commands);
+        assert_eq!(strength_commands(Some(5), Some(1500)), elo_commands);
+    }
+
+    #[test]
+    fn chess960_initializes_engine_and_parses_shredder_castling() {
+        const START_960: &str = "nrbbqkrn/pppppppp/8/8/8/8/PPPPPPPP/NRBBQKRN b GBgb - 0 1";
+        let mock = MockIo::new(vec![
+            "uciok",
+            "readyok",
+            "info depth 10 multipv 1 score cp 20 nodes 1000 pv e7e5",
+            "bestmove e7e5",
+        ]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let key = PoolKey {
+            chess960: true,
+            ..test_key()
+        };
+        worker.initialize(&key).unwrap();
+        let mut payload = worker
+            .evaluate(START_960, &key, &[], &mut ignore_progress)
+            .unwrap();
+
+        let writes = writes_handle.lock().unwrap();
+        let option_at = writes
+            .iter()
+            .position(|cmd| cmd == "setoption name UCI_Chess960 value true")
+            .expect("chess960 option sent");
+        let ready_at = writes.iter().position(|cmd| cmd == "isready").unwrap();
+        assert!(option_at < ready_at);
+        assert!(
+            payload
+                .apply_point_of_view(true, test_key().castling_mode())
+                .is_err()
+        );
+        payload
+            .apply_point_of_view(true, key.castling_mode())
+            .unwrap();
+        assert_eq!(payload.pvs[0].cp, Some(-20));
+    }
+
+    #[test]
+    fn stockfish_evaluate_rejects_missing_syzygy_path() {
+        Python::initialize();
+        Python::attach(|py| {
+            let err = stockfish_evaluate(
+                py,
+                "8/8/8/8/8/4k3/8/4K2R w K - 0 1".into(),
+                "stockfish-not-spawned".into(),
+                10,
+                1,
+                None,
+                1,
+                None,
+                None,
+                None,
+                None,
+                false,
+                Some("/definitely/missing/syzygy".into()),
+                None,
+                false,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap_err();
+            assert!(err.is_instance_of::<PyRuntimeError>(py));
+            assert!(
+                err.value(py)
+                    .to_string()
+                    .contains("/definitely/missing/syzygy")
+            );
+        });
     }
 
     #[test]
@@ -418,19 +2047,626 @@ mod tests {
         ]);
         let writes_handle = mock.writes();
         let mut worker = StockfishWorker::with_io(Box::new(mock));
-        worker.initialize(2).unwrap();
-        let key = PoolKey {
-            engine_path: "engine".into(),
-            depth: 12,
-            multi_pv: 2,
-            think_time_ms: None,
-            pool_size: 1,
-        };
-        let payload = worker.evaluate("fen", &key).unwrap();
+        let key = test_key();
+        worker.initialize(&key).unwrap();
+        let payload = worker
+            .evaluate("fen", &key, &[], &mut ignore_progress)
+            .unwrap();
         assert_eq!(payload.pvs.len(), 1);
         let writes = writes_handle.lock().unwrap();
         assert_eq!(writes[0], "uci");
         assert!(writes.iter().any(|cmd| cmd.starts_with("position fen")));
         assert!(writes.iter().any(|cmd| cmd.starts_with("go depth")));
     }
+
+    #[test]
+    fn go_command_applies_limit_precedence() {
+        let worker = mock_worker(vec![]);
+        let depth = test_key();
+        let movetime = PoolKey {
+            think_time_ms: Some(1500),
+            ..depth.clone()
+        };
+        let nodes = PoolKey {
+            nodes_limit: Some(250_000),
+            ..movetime.clone()
+        };
+        let mate = PoolKey {
+            mate_in: Some(3),
+            ..nodes.clone()
+        };
+        assert_eq!(worker.go_command(&depth, &[]), "go depth 12");
+        assert_eq!(worker.go_command(&movetime, &[]), "go movetime 1500");
+        assert_eq!(worker.go_command(&nodes, &[]), "go nodes 250000");
+        assert_eq!(worker.go_command(&mate, &[]), "go mate 3");
+    }
+
+    #[test]
+    fn searchmoves_restrict_go_and_reject_illegal_moves_before_sending() {
+        let mock = MockIo::new(vec![
+            "info depth 8 nodes 50000 multipv 1 score cp 15 pv d2d4 d7d5",
+            "bestmove d2d4",
+        ]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let candidates = vec!["e2e4".to_string(), "d2d4".to_string()];
+        worker
+            .evaluate(start, &test_key(), &candidates, &mut ignore_progress)
+            .unwrap();
+        assert!(
+            writes_handle
+                .lock()
+                .unwrap()
+                .contains(&"go depth 12 searchmoves e2e4 d2d4".to_string())
+        );
+
+        let mock = MockIo::new(vec![]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let illegal = vec!["e2e4".to_string(), "e2e5".to_string()];
+        let err = worker
+            .evaluate(start, &test_key(), &illegal, &mut ignore_progress)
+            .unwrap_err();
+        assert!(err.to_string().contains("e2e5"));
+        assert!(writes_handle.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn initialize_sends_extra_options_before_isready() {
+        let mock = MockIo::new(vec!["uciok", "readyok"]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let key = PoolKey {
+            options: vec![
+                ("Contempt".to_string(), "24".to_string()),
+                ("EvalFile".to_string(), "/nets/custom.nnue".to_string()),
+                ("Move Overhead".to_string(), "100".to_string()),
+            ],
+            ..test_key()
+        };
+        worker.initialize(&key).unwrap();
+        let writes = writes_handle.lock().unwrap();
+        assert_eq!(
+            *writes,
+            vec![
+                "uci",
+                "setoption name MultiPV value 2",
+                "setoption name Contempt value 24",
+                "setoption name EvalFile value /nets/custom.nnue",
+                "setoption name Move Overhead value 100",
+                "isready",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_rejects_blank_or_multiline_option_names() {
+        for options in [
+            vec![(" ".to_string(), "1".to_string())],
+            vec![("Hash\nquit".to_string(), "1".to_string())],
+        ] {
+            let key = PoolKey {
+                options,
+                ..test_key()
+            };
+            assert!(matches!(key.validate(), Err(EngineError::InvalidInput(_))));
+        }
+        let key = PoolKey {
+            options: vec![("Ponder".to_string(), "false".to_string())],
+            ..test_key()
+        };
+        assert!(key.validate().is_ok());
+    }
+
+    #[test]
+    fn initialize_sets_threads_and_hash_before_isready() {
+        let mock = MockIo::new(vec!["uciok", "readyok"]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let key = PoolKey {
+            hash_mb: Some(256),
+            threads: Some(8),
+            ..test_key()
+        };
+        worker.initialize(&key).unwrap();
+        let writes = writes_handle.lock().unwrap();
+        assert_eq!(
+            *writes,
+            vec![
+                "uci",
+                "setoption name Threads value 8",
+                "setoption name Hash value 256",
+                "setoption name MultiPV value 2",
+                "isready",
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluate_times_out_and_kills_hung_engine() {
+        let mock = MockIo::hanging(vec![
+            "info depth 5 nodes 1000 multipv 1 score cp 10 pv e2e4",
+        ]);
+        let writes_handle = mock.writes();
+        let killed = mock.killed.clone();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let key = PoolKey {
+            timeout_ms: Some(50),
+            ..test_key()
+        };
+
+        let err = worker
+            .evaluate("fen", &key, &[], &mut ignore_progress)
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        assert!(worker.is_dead());
+        assert!(*killed.lock().unwrap());
+        let writes = writes_handle.lock().unwrap();
+        assert_eq!(writes.last().map(String::as_str), Some("stop"));
+    }
+
+    #[test]
+    fn evaluate_returns_partial_result_when_stop_is_honoured() {
+        let mock = MockIo::new(vec![
+            "info depth 5 nodes 1000 multipv 1 score cp 10 pv e2e4",
+            STALL,
+            "bestmove e2e4",
+        ]);
+        let writes_handle = mock.writes();
+        let mut worker = StockfishWorker::with_io(Box::new(mock));
+        let key = PoolKey {
+            timeout_ms: Some(50),
+            ..test_key()
+        };
+        let payload = worker
+            .evaluate("fen", &key, &[], &mut ignore_progress)
+            .unwrap();
+        assert_eq!(payload.depth, 5);
+        assert!(!worker.is_dead());
+        assert!(
+            writes_handle
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|cmd| cmd == "stop")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_io_applies_niceness_to_engine() {
+        let tmp = tempfile::tempdir().expect("temp dir");
+        let engine = write_fake_engine(
+            tmp.path(),
+            "nice > \"$(dirname \"$0\")/niceness\"\nwhile read line; do\n  [ \"$line\" = quit ] && exit 0\ndone\n",
+        );
+        let mut io = spawn_fake_engine(&engine, Some(19));
+        io.shutdown();
+        let niceness = std::fs::read_to_string(tmp.path().join("niceness")).expect("niceness");
+        assert_eq!(niceness.trim(), "19");
+    }
+
+    #[cfg(unix)]
+    fn write_fake_engine(dir: &std::path::Path, body: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join("engine.sh");
+        std::fs::write(&path, format!("#!/bin/sh\n{body}")).expect("write engine");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+        path
+    }
+
+    /// Spawn a script engine, retrying while another test's fork still holds it open.
+    #[cfg(unix)]
+    fn spawn_fake_engine(path: &std::path::Path, nice: Option<i32>) -> ProcessIo {
+        for _ in 0..50 {
+            match ProcessIo::spawn(path.to_str().unwrap(), nice) {
+                Ok(io) => return io,
+                Err(err) if err.raw_os_error() == Some(libc::ETXTBSY) => {
+                    thread::sleep(Duration::from_millis(20));
+                }
+                Err(err) => panic!("spawn fake engine: {err}"),
+            }
+        }
+        panic!("fake engine stayed busy");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shutdown_releases_pools_so_the_next_lookup_spawns_fresh_workers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let engine = write_fake_engine(
+            tmp.path(),
+            "echo started >> \"$(dirname \"$0\")/spawns\"\n\
+             while read line; do\n\
+               case \"$line\" in\n\
+                 uci) echo uciok ;;\n\
+                 isready) echo readyok ;;\n\
+                 quit) exit 0 ;;\n\
+               esac\n\
+             done\n",
+        );
+        let key = PoolKey {
+            engine_path: engine.to_str().unwrap().into(),
+            ..test_key()
+        };
+        let spawns = || {
+            std::fs::read_to_string(tmp.path().join("spawns"))
+                .unwrap_or_default()
+                .lines()
+                .count()
+        };
+        let pool_with_retry = || {
+            for _ in 0..50 {
+                match get_or_create_pool(&key) {
+                    Ok(pool) => return pool,
+                    Err(_) => thread::sleep(Duration::from_millis(20)),
+                }
+            }
+            panic!("fake engine stayed busy");
+        };
+
+        let first = pool_with_retry();
+        assert!(Arc::ptr_eq(&first, &pool_with_retry()));
+        assert_eq!(spawns(), 1);
+        drop(first);
+
+        Python::initialize();
+        let released = Python::attach(|py| {
+            shutdown_stockfish_pool(py, key.engine_path.clone(), 12, 2, None, 1)
+        });
+        assert_eq!(released, 1);
+        assert!(!STOCKFISH_POOLS.lock().unwrap().contains_key(&key));
+
+        let _fresh = pool_with_retry();
+        assert_eq!(spawns(), 2);
+    }
+
+    /// Warm a pool on a script engine that only answers the handshake.
+    #[cfg(unix)]
+    fn warm_fake_pool(py: Python<'_>, engine_path: &str, pool_size: usize) -> usize {
+        for _ in 0..50 {
+            let warmed = warmup_stockfish_pool(
+                py,
+                engine_path.to_string(),
+                9,
+                2,
+                None,
+                pool_size,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+            );
+            match warmed {
+                Ok(workers) => return workers,
+                Err(_) => thread::sleep(Duration::from_millis(20)),
+            }
+        }
+        panic!("fake engine stayed busy");
+    }
+
+    #[cfg(unix)]
+    fn write_handshake_engine(dir: &std::path::Path) -> String {
+        let engine = write_fake_engine(
+            dir,
+            "while read line; do\n\
+               case \"$line\" in\n\
+                 uci) echo uciok ;;\n\
+                 isready) echo readyok ;;\n\
+                 quit) exit 0 ;;\n\
+               esac\n\
+             done\n",
+        );
+        engine.to_str().unwrap().to_string()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn warmup_registers_pool_before_any_evaluation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let engine_path = write_handshake_engine(tmp.path());
+        let key = PoolKey {
+            engine_path: engine_path.clone(),
+            depth: 9,
+            pool_size: 2,
+            ..test_key()
+        };
+
+        Python::initialize();
+        let workers = Python::attach(|py| warm_fake_pool(py, &engine_path, 2));
+        assert_eq!(workers, 2);
+        let pool = STOCKFISH_POOLS.lock().unwrap().get(&key).cloned();
+        assert_eq!(pool.expect("warmed pool").workers.len(), 2);
+
+        Python::attach(|py| shutdown_stockfish_pool(py, engine_path, 9, 2, None, 2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_pools_describes_warmed_pool() {
+        let tmp = tempfile::tempdir().unwrap();
+        let engine_path = write_handshake_engine(tmp.path());
+
+        Python::initialize();
+        Python::attach(|py| {
+            warm_fake_pool(py, &engine_path, 3);
+            let pools = list_stockfish_pools(py).unwrap().into_bound(py);
+            let pools = pools.cast::<PyList>().unwrap();
+            let entry = pools
+                .iter()
+                .find(|pool| {
+                    let path = pool.get_item("engine_path").unwrap();
+                    path.extract::<String>().unwrap() == engine_path
+                })
+                .expect("warmed pool listed");
+            assert_eq!(
+                entry.get_item("depth").unwrap().extract::<u32>().unwrap(),
+                9
+            );
+            assert_eq!(
+                entry
+                    .get_item("multi_pv")
+                    .unwrap()
+                    .extract::<u32>()
+                    .unwrap(),
+                2
+            );
+            assert!(entry.get_item("think_time_ms").unwrap().is_none());
+            assert_eq!(
+                entry
+                    .get_item("pool_size")
+                    .unwrap()
+                    .extract::<usize>()
+                    .unwrap(),
+                3
+            );
+            assert_eq!(
+                entry
+                    .get_item("workers")
+                    .unwrap()
+                    .extract::<usize>()
+                    .unwrap(),
+                3
+            );
+
+            shutdown_stockfish_pool(py, engine_path.clone(), 9, 2, None, 3);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_initialize_reports_engine_stderr() {
+        let tmp = tempfile::tempdir().unwrap();
+        let engine = write_fake_engine(
+            tmp.path(),
+            "echo 'info string starting' \n\
+             echo 'ERROR: network file nn-missing.nnue was not loaded' >&2\n\
+             exit 1\n",
+        );
+        let io = spawn_fake_engine(&engine, None);
+        let mut worker = StockfishWorker::with_io(Box::new(io));
+        let err = worker.initialize(&test_key()).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("Stockfish stderr"), "{message}");
+        assert!(
+            message.contains("nn-missing.nnue was not loaded"),
+            "{message}"
+        );
+    }
+
+    fn mock_worker(responses: Vec<&str>) -> StockfishWorker {
+        StockfishWorker::with_io(Box::new(MockIo::new(responses)))
+    }
+
+    #[test]
+    fn pool_respawns_terminated_worker_and_retries() {
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let spawn_count = spawned.clone();
+        let pool = StockfishPool::with_workers(
+            &test_key(),
+            vec![mock_worker(vec![])],
+            Box::new(move |_| {
+                spawn_count.fetch_add(1, Ordering::SeqCst);
+                Ok(mock_worker(vec![
+                    "info depth 9 nodes 2000 multipv 1 score cp 20 pv d2d4",
+                    "bestmove d2d4",
+                ]))
+            }),
+        );
+
+        let payload = pool.evaluate("fen", &[], &mut ignore_progress).unwrap();
+        assert_eq!(payload.depth, 9);
+        assert_eq!(spawned.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn rank_candidates_orders_by_resulting_eval_for_the_mover() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let mock = MockIo::new(vec![
+            // After 1. a4 Black is better by 30.
+            "info depth 10 multipv 1 score cp 30 nodes 1000 pv e7e5",
+            "bestmove e7e5",
+            // After 1. e4 Black is worse by 50.
+            "info depth 10 multipv 1 score cp -50 nodes 1000 pv c7c5",
+            "bestmove c7c5",
+        ]);
+        let writes_handle = mock.writes();
+        let pool = StockfishPool::with_workers(
+            &test_key(),
+            vec![StockfishWorker::with_io(Box::new(mock))],
+            Box::new(|_| Ok(mock_worker(vec![]))),
+        );
+
+        let ranked = pool
+            .rank_candidates(start, &["a2a4".to_string(), "e2e4".to_string()])
+            .unwrap();
+        let order: Vec<(&str, &str, Option<i32>)> = ranked
+            .iter()
+            .map(|c| (c.uci.as_str(), c.san.as_str(), c.cp))
+            .collect();
+        assert_eq!(
+            order,
+            vec![("e2e4", "e4", Some(50)), ("a2a4", "a4", Some(-30))]
+        );
+        assert_eq!(ranked[0].best_reply.as_deref(), Some("c7c5"));
+        let writes = writes_handle.lock().unwrap();
+        let positions: Vec<&String> = writes
+            .iter()
+            .filter(|cmd| cmd.starts_with("position fen"))
+            .collect();
+        assert_eq!(positions.len(), 2);
+    }
+
+    #[test]
+    fn pool_caps_respawn_retries() {
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let spawn_count = spawned.clone();
+        let pool = StockfishPool::with_workers(
+            &test_key(),
+            vec![mock_worker(vec![]), mock_worker(vec![])],
+            Box::new(move |_| {
+                spawn_count.fetch_add(1, Ordering::SeqCst);
+                Ok(mock_worker(vec![]))
+            }),
+        );
+
+        let err = pool.evaluate("fen", &[], &mut ignore_progress).unwrap_err();
+        assert!(err.to_string().contains("terminated unexpectedly"));
+        assert_eq!(spawned.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn pool_surfaces_original_error_when_respawn_fails() {
+        let pool = StockfishPool::with_workers(
+            &test_key(),
+            vec![mock_worker(vec![])],
+            Box::new(|_| {
+                Err(EngineError::Engine(
+                    "Unable to launch Stockfish".to_string(),
+                ))
+            }),
+        );
+
+        let err = pool.evaluate("fen", &[], &mut ignore_progress).unwrap_err();
+        assert!(err.to_string().contains("terminated unexpectedly"));
+    }
+
+    #[test]
+    fn evaluate_batch_skips_known_fens_without_engine_commands() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let mock = MockIo::new(vec![
+            "info depth 7 nodes 1000 multipv 1 score cp 30 pv e7e5",
+            "bestmove e7e5",
+        ]);
+        let writes_handle = mock.writes();
+        let pool = StockfishPool::with_workers(
+            &test_key(),
+            vec![StockfishWorker::with_io(Box::new(mock))],
+            Box::new(|_| Err(EngineError::Engine("no respawn in tests".to_string()))),
+        );
+        // Known FENs match by canonical form, so differing counters still hit.
+        let known = HashSet::from([start.replace(" 0 1", " 3 7")]);
+
+        let results = pool.evaluate_batch(&[start.to_string(), after_e4.to_string()], &known);
+        assert!(matches!(&results[0], BatchEntry::Cached(fen) if fen == start));
+        assert!(matches!(&results[1], BatchEntry::Evaluated(payload) if payload.depth == 7));
+        let writes = writes_handle.lock().unwrap();
+        let positions: Vec<&String> = writes
+            .iter()
+            .filter(|cmd| cmd.starts_with("position fen"))
+            .collect();
+        assert_eq!(positions, vec![&format!("position fen {after_e4}")]);
+    }
+
+    #[test]
+    fn evaluate_batch_spreads_work_and_isolates_bad_fens() {
+        let scripted = || {
+            let mut lines = Vec::new();
+            for _ in 0..3 {
+                lines.push("info depth 6 nodes 1000 multipv 1 score cp 12 pv e2e4");
+                lines.push("bestmove e2e4");
+            }
+            MockIo::new(lines)
+        };
+        let first = scripted();
+        let second = scripted();
+        let writes = [first.writes(), second.writes()];
+        let pool = StockfishPool::with_workers(
+            &PoolKey {
+                pool_size: 2,
+                ..test_key()
+            },
+            vec![
+                StockfishWorker::with_io(Box::new(first)),
+                StockfishWorker::with_io(Box::new(second)),
+            ],
+            Box::new(|_| Err(EngineError::Engine("no respawn in tests".to_string()))),
+        );
+        let fens: Vec<String> = vec![
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".into(),
+            "not a fen".into(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".into(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2".into(),
+        ];
+
+        let results = pool.evaluate_batch(&fens, &HashSet::new());
+        assert_eq!(results.len(), 4);
+        for (index, entry) in results.iter().enumerate() {
+            match entry {
+                BatchEntry::Evaluated(payload) => assert_eq!(payload.fen, fens[index]),
+                BatchEntry::Failed(fen, _) => assert_eq!(index, 1, "unexpected failure for {fen}"),
+                BatchEntry::Cached(_) => panic!("nothing is cached"),
+            }
+        }
+        assert!(matches!(&results[1], BatchEntry::Failed(fen, _) if fen == "not a fen"));
+        let searched: usize = writes
+            .iter()
+            .map(|handle| {
+                handle
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|cmd| cmd.starts_with("position fen"))
+                    .count()
+            })
+            .sum();
+        assert_eq!(searched, 3);
+    }
+
+    #[test]
+    fn payload_json_reports_pvs_from_whites_point_of_view() {
+        let pool = StockfishPool::with_workers(
+            &test_key(),
+            vec![mock_worker(vec![
+                "info depth 12 seldepth 16 multipv 1 score cp 35 nodes 8000 pv e7e5 g1f3",
+                "info depth 12 seldepth 16 multipv 2 score mate 3 nodes 8000 pv c7c5",
+                "bestmove e7e5 ponder g1f3",
+            ])],
+            Box::new(|_| Err(EngineError::Engine("no respawn in tests".to_string()))),
+        );
+        let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+
+        let json = payload_json(&pool, after_e4, true).unwrap();
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["fen"], after_e4);
+        assert_eq!(value["best_move"], "e7e5");
+        let pvs = value["pvs"].as_array().unwrap();
+        assert_eq!(pvs.len(), 2);
+        assert_eq!(pvs[0]["cp"], -35);
+        assert_eq!(pvs[0]["moves"], serde_json::json!(["e7e5", "g1f3"]));
+        assert_eq!(pvs[1]["cp"], serde_json::Value::Null);
+        assert_eq!(pvs[1]["mate"], -3);
+    }
 }
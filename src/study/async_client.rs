@@ -0,0 +1,145 @@
+use super::{
+    StudyChapterImport, StudyConfig, StudyError, import_form, parse_chapter_ids, resolve_study_id,
+    retry_delay,
+};
+use reqwest::StatusCode;
+use reqwest::{Client, RequestBuilder, Response};
+use std::time::Duration;
+
+/// Non-blocking counterpart of `LichessStudyClient` for use inside a tokio runtime.
+#[derive(Debug)]
+pub struct AsyncLichessStudyClient {
+    config: StudyConfig,
+    http: Client,
+}
+
+impl AsyncLichessStudyClient {
+    pub fn new(config: StudyConfig) -> Result<Self, StudyError> {
+        let mut builder = Client::builder().user_agent("rep-grow");
+        if let Some(secs) = config.request_timeout_secs {
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+        let client = builder.build().map_err(StudyError::Http)?;
+        Ok(Self {
+            config,
+            http: client,
+        })
+    }
+
+    /// Import one PGN, returning the ids of the chapters Lichess created.
+    pub async fn import_pgn(
+        &self,
+        payload: &StudyChapterImport,
+    ) -> Result<Vec<String>, StudyError> {
+        let study_id = resolve_study_id(&self.config, payload.study_id.as_deref())?;
+
+        let base = self.config.base_url.trim_end_matches('/');
+        let url = format!("{base}/api/study/{study_id}/import-pgn");
+        let form = import_form(&self.config, payload);
+
+        let response = self
+            .send_with_retry(|| {
+                self.http
+                    .post(&url)
+                    .bearer_auth(&self.config.token)
+                    .header(
+                        reqwest::header::CONTENT_TYPE,
+                        "application/x-www-form-urlencoded",
+                    )
+                    .form(&form)
+            })
+            .await?;
+        if !response.status().is_success() {
+            return Err(StudyError::HttpStatus(response.status()));
+        }
+        Ok(parse_chapter_ids(&response.text().await?))
+    }
+
+    /// Download every chapter of the study as PGN text.
+    pub async fn export_study_pgn(&self, study_id: Option<&str>) -> Result<String, StudyError> {
+        let study_id = resolve_study_id(&self.config, study_id)?;
+        let base = self.config.base_url.trim_end_matches('/');
+        let url = format!("{base}/api/study/{study_id}.pgn");
+        let response = self
+            .send_with_retry(|| self.http.get(&url).bearer_auth(&self.config.token))
+            .await?;
+        if !response.status().is_success() {
+            return Err(StudyError::HttpStatus(response.status()));
+        }
+        Ok(response.text().await?)
+    }
+
+    async fn send_with_retry<F>(&self, request: F) -> Result<Response, StudyError>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let mut attempt = 0;
+        loop {
+            let response = request().send().await?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS
+                || attempt >= self.config.max_retries
+            {
+                return Ok(response);
+            }
+            tokio::time::sleep(retry_delay(&self.config, response.headers(), attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::study::tests::test_config;
+    use httpmock::Method::{GET, POST};
+    use httpmock::MockServer;
+
+    #[tokio::test]
+    async fn async_import_pgn_sends_expected_request() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/api/study/ABCDEFGH/import-pgn")
+                    .header("authorization", "Bearer secret")
+                    .body_contains("pgn=1.+e4+e5")
+                    .body_contains("name=Async+Line");
+                then.status(200)
+                    .header("content-type", "application/json")
+                    .body(r#"{"chapters": [{"id": "chapB002"}]}"#);
+            })
+            .await;
+
+        let client = AsyncLichessStudyClient::new(test_config(server.base_url(), "ABCDEFGH"))
+            .expect("client");
+        let payload = StudyChapterImport {
+            study_id: None,
+            name: Some("Async Line".to_string()),
+            pgn: "1. e4 e5 *".to_string(),
+            orientation: None,
+        };
+        let chapter_ids = client.import_pgn(&payload).await.expect("import succeeds");
+        assert_eq!(chapter_ids, vec!["chapB002".to_string()]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn async_export_study_pgn_returns_body() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(GET).path("/api/study/ABCDEFGH.pgn");
+                then.status(200).body("1. d4 d5 *");
+            })
+            .await;
+
+        let client = AsyncLichessStudyClient::new(test_config(server.base_url(), "ABCDEFGH"))
+            .expect("client");
+        let pgn = client
+            .export_study_pgn(None)
+            .await
+            .expect("export succeeds");
+        assert_eq!(pgn, "1. d4 d5 *");
+        mock.assert_async().await;
+    }
+}
@@ -1,8 +1,21 @@
 use reqwest::StatusCode;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::HeaderMap;
 use serde::Deserialize;
+use shakmaty::{Chess, Color, Position};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::frequency::canonical_fen;
+use crate::pgn::PgnMove;
+
+#[cfg(feature = "async")]
+mod async_client;
+#[cfg(feature = "async")]
+pub use async_client::AsyncLichessStudyClient;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct StudyConfig {
@@ -11,12 +24,34 @@ pub struct StudyConfig {
     #[serde(default = "default_base_url")]
     pub base_url: String,
     pub default_orientation: Option<String>,
+    /// Retries after a `429 Too Many Requests` before giving up.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// First backoff delay when the response carries no `Retry-After`; doubled
+    /// on every further retry.
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+    /// Per-request timeout; `None` waits indefinitely.
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: Option<u64>,
 }
 
 fn default_base_url() -> String {
     "https://lichess.org".to_string()
 }
 
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    1000
+}
+
+fn default_request_timeout_secs() -> Option<u64> {
+    Some(30)
+}
+
 impl StudyConfig {
     pub fn from_path(path: impl AsRef<Path>) -> Result<Self, StudyError> {
         let text = fs::read_to_string(path)?;
@@ -39,6 +74,24 @@ pub struct StudyChapterImport {
     pub orientation: Option<String>,
 }
 
+const STUDY_VISIBILITIES: [&str; 3] = ["public", "unlisted", "private"];
+
+#[derive(Debug, Deserialize)]
+struct CreatedStudy {
+    id: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ImportResponse {
+    #[serde(default)]
+    chapters: Vec<ImportedChapter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportedChapter {
+    id: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct LichessStudyClient {
     config: StudyConfig,
@@ -47,54 +100,275 @@ pub struct LichessStudyClient {
 
 impl LichessStudyClient {
     pub fn new(config: StudyConfig) -> Result<Self, StudyError> {
-        let client = Client::builder()
-            .user_agent("rep-grow")
-            .build()
-            .map_err(StudyError::Http)?;
+        let mut builder = Client::builder().user_agent("rep-grow");
+        if let Some(secs) = config.request_timeout_secs {
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+        let client = builder.build().map_err(StudyError::Http)?;
         Ok(Self {
             config,
             http: client,
         })
     }
 
-    pub fn import_pgn(&self, payload: &StudyChapterImport) -> Result<(), StudyError> {
-        let study_id = payload
-            .study_id
-            .as_deref()
-            .unwrap_or(self.config.study_id.as_str());
-        if study_id.trim().is_empty() {
-            return Err(StudyError::MissingStudyId);
-        }
+    /// Import one PGN, returning the ids of the chapters Lichess created. A
+    /// response without a readable `chapters` array yields no ids.
+    pub fn import_pgn(&self, payload: &StudyChapterImport) -> Result<Vec<String>, StudyError> {
+        let study_id = resolve_study_id(&self.config, payload.study_id.as_deref())?;
 
         let base = self.config.base_url.trim_end_matches('/');
         let url = format!("{base}/api/study/{study_id}/import-pgn");
-        let mut form: Vec<(String, String)> = vec![("pgn".to_string(), payload.pgn.clone())];
-        if let Some(name) = &payload.name {
-            form.push(("name".to_string(), name.clone()));
-        }
-        if let Some(orientation) = payload
-            .orientation
-            .clone()
-            .or_else(|| self.config.default_orientation.clone())
-        {
-            form.push(("orientation".to_string(), orientation));
-        }
-
-        let response = self
-            .http
-            .post(url)
-            .bearer_auth(&self.config.token)
-            .header(
-                reqwest::header::CONTENT_TYPE,
-                "application/x-www-form-urlencoded",
-            )
-            .form(&form)
-            .send()?;
+        let form = import_form(&self.config, payload);
+
+        let response = self.send_with_retry(|| {
+            self.http
+                .post(&url)
+                .bearer_auth(&self.config.token)
+                .header(
+                    reqwest::header::CONTENT_TYPE,
+                    "application/x-www-form-urlencoded",
+                )
+                .form(&form)
+        })?;
+        if !response.status().is_success() {
+            return Err(StudyError::HttpStatus(response.status()));
+        }
+        Ok(parse_chapter_ids(&response.text()?))
+    }
+
+    /// Download every chapter of the study as PGN text.
+    pub fn export_study_pgn(&self, study_id: Option<&str>) -> Result<String, StudyError> {
+        let study_id = resolve_study_id(&self.config, study_id)?;
+        let base = self.config.base_url.trim_end_matches('/');
+        let url = format!("{base}/api/study/{study_id}.pgn");
+        let response =
+            self.send_with_retry(|| self.http.get(&url).bearer_auth(&self.config.token))?;
+        if !response.status().is_success() {
+            return Err(StudyError::HttpStatus(response.status()));
+        }
+        Ok(response.text()?)
+    }
+
+    /// Remove one chapter from the study.
+    pub fn delete_chapter(
+        &self,
+        study_id: Option<&str>,
+        chapter_id: &str,
+    ) -> Result<(), StudyError> {
+        let study_id = resolve_study_id(&self.config, study_id)?;
+        if chapter_id.trim().is_empty() {
+            return Err(StudyError::MissingChapterId);
+        }
+        let base = self.config.base_url.trim_end_matches('/');
+        let url = format!("{base}/api/study/{study_id}/{chapter_id}");
+        let response =
+            self.send_with_retry(|| self.http.delete(&url).bearer_auth(&self.config.token))?;
         if !response.status().is_success() {
             return Err(StudyError::HttpStatus(response.status()));
         }
         Ok(())
     }
+
+    /// Send the request built by `request`, retrying on `429 Too Many Requests`
+    /// after the `Retry-After` delay or an exponential backoff.
+    fn send_with_retry<F>(&self, request: F) -> Result<Response, StudyError>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let mut attempt = 0;
+        loop {
+            let response = request().send()?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS
+                || attempt >= self.config.max_retries
+            {
+                return Ok(response);
+            }
+            thread::sleep(retry_delay(&self.config, response.headers(), attempt));
+            attempt += 1;
+        }
+    }
+
+    /// Replace the moves of an existing chapter, returning the id(s) of the
+    /// chapter that now holds them.
+    ///
+    /// Lichess exposes no endpoint that overwrites a chapter's PGN; editing
+    /// moves in place is only possible through the socket-level node API. The
+    /// replacement is therefore imported as a new chapter first and the old
+    /// chapter deleted afterwards, so a failed import leaves the original
+    /// untouched. The new chapter gets a fresh id; pass the old chapter's
+    /// `name` (and `orientation`, if set) so it is otherwise unchanged.
+    pub fn update_chapter_pgn(
+        &self,
+        study_id: Option<&str>,
+        chapter_id: &str,
+        name: &str,
+        orientation: Option<&str>,
+        pgn: &str,
+    ) -> Result<Vec<String>, StudyError> {
+        let study_id = resolve_study_id(&self.config, study_id)?;
+        if chapter_id.trim().is_empty() {
+            return Err(StudyError::MissingChapterId);
+        }
+        let chapter_ids = self.import_pgn(&StudyChapterImport {
+            study_id: Some(study_id.to_string()),
+            name: Some(name.to_string()),
+            pgn: pgn.to_string(),
+            orientation: orientation.map(str::to_string),
+        })?;
+        self.delete_chapter(Some(study_id), chapter_id)?;
+        Ok(chapter_ids)
+    }
+
+    /// Create a new study and return its id. `visibility` must be one of
+    /// `public`, `unlisted` or `private`.
+    pub fn create_study(&self, name: &str, visibility: &str) -> Result<String, StudyError> {
+        if !STUDY_VISIBILITIES.contains(&visibility) {
+            return Err(StudyError::InvalidVisibility(visibility.to_string()));
+        }
+        let base = self.config.base_url.trim_end_matches('/');
+        let url = format!("{base}/api/study");
+        let form = [("name", name), ("visibility", visibility)];
+        let response = self.send_with_retry(|| {
+            self.http
+                .post(&url)
+                .bearer_auth(&self.config.token)
+                .form(&form)
+        })?;
+        if !response.status().is_success() {
+            return Err(StudyError::HttpStatus(response.status()));
+        }
+        let body = response.text()?;
+        serde_json::from_str::<CreatedStudy>(&body)
+            .ok()
+            .and_then(|created| created.id)
+            .filter(|id| !id.is_empty())
+            .ok_or(StudyError::UnexpectedResponse(body))
+    }
+
+    /// Import every payload in order, collecting one result per chapter.
+    pub fn import_pgn_batch(
+        &self,
+        payloads: &[StudyChapterImport],
+    ) -> Vec<Result<Vec<String>, StudyError>> {
+        self.import_pgn_batch_with_progress(payloads, |_, _, _| {})
+    }
+
+    /// Like `import_pgn_batch`, but calls `progress(index, total, result)` after each
+    /// chapter so callers can render progress and log per-chapter outcomes.
+    pub fn import_pgn_batch_with_progress<F>(
+        &self,
+        payloads: &[StudyChapterImport],
+        mut progress: F,
+    ) -> Vec<Result<Vec<String>, StudyError>>
+    where
+        F: FnMut(usize, usize, &Result<Vec<String>, StudyError>),
+    {
+        let total = payloads.len();
+        let mut results = Vec::with_capacity(total);
+        for (index, payload) in payloads.iter().enumerate() {
+            let result = self.import_pgn(payload);
+            progress(index, total, &result);
+            results.push(result);
+        }
+        results
+    }
+}
+
+/// The explicit study id, falling back to the configured one.
+fn resolve_study_id<'a>(
+    config: &'a StudyConfig,
+    study_id: Option<&'a str>,
+) -> Result<&'a str, StudyError> {
+    let study_id = study_id.unwrap_or(config.study_id.as_str());
+    if study_id.trim().is_empty() {
+        return Err(StudyError::MissingStudyId);
+    }
+    Ok(study_id)
+}
+
+fn import_form(config: &StudyConfig, payload: &StudyChapterImport) -> Vec<(String, String)> {
+    let mut form: Vec<(String, String)> = vec![("pgn".to_string(), payload.pgn.clone())];
+    if let Some(name) = &payload.name {
+        form.push(("name".to_string(), name.clone()));
+    }
+    if let Some(orientation) = payload
+        .orientation
+        .clone()
+        .or_else(|| config.default_orientation.clone())
+        .or_else(|| detect_orientation(&payload.pgn))
+    {
+        form.push(("orientation".to_string(), orientation));
+    }
+    form
+}
+
+/// Guess which side a repertoire PGN is built for. The player picks one move
+/// per position while the opponent's replies branch, so the side to move at
+/// the shallowest position with more than one move is the opponent. `None`
+/// when the PGN does not branch or cannot be read.
+pub fn detect_orientation(pgn: &str) -> Option<String> {
+    let games = crate::pgn::parse_games(pgn).ok()?;
+    let mut branches: HashMap<String, (usize, Color, HashSet<String>)> = HashMap::new();
+    for game in &games {
+        collect_branches(&game.moves, Chess::new(), 0, &mut branches)?;
+    }
+    let (_, opponent, _) = branches
+        .into_values()
+        .filter(|(_, _, moves)| moves.len() > 1)
+        .min_by_key(|(ply, _, _)| *ply)?;
+    let player = match opponent {
+        Color::White => "black",
+        Color::Black => "white",
+    };
+    Some(player.to_string())
+}
+
+fn collect_branches(
+    line: &[PgnMove],
+    mut position: Chess,
+    mut ply: usize,
+    branches: &mut HashMap<String, (usize, Color, HashSet<String>)>,
+) -> Option<()> {
+    for pgn_move in line {
+        for variation in &pgn_move.variations {
+            collect_branches(variation, position.clone(), ply, branches)?;
+        }
+        let mv = pgn_move.san.san.to_move(&position).ok()?;
+        let fen = canonical_fen(&position).ok()?;
+        branches
+            .entry(fen)
+            .or_insert_with(|| (ply, position.turn(), HashSet::new()))
+            .2
+            .insert(pgn_move.san.to_string());
+        position.play_unchecked(&mv);
+        ply += 1;
+    }
+    Some(())
+}
+
+fn parse_chapter_ids(body: &str) -> Vec<String> {
+    let imported: ImportResponse = serde_json::from_str(body).unwrap_or_default();
+    imported
+        .chapters
+        .into_iter()
+        .filter_map(|chapter| chapter.id)
+        .collect()
+}
+
+/// Delay before retrying a rate-limited request: `Retry-After` when present,
+/// otherwise exponential backoff from `retry_base_delay_ms`.
+fn retry_delay(config: &StudyConfig, headers: &HeaderMap, attempt: u32) -> Duration {
+    let retry_after = headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let backoff = Duration::from_millis(
+        config
+            .retry_base_delay_ms
+            .saturating_mul(1 << attempt.min(16)),
+    );
+    retry_after.unwrap_or(backoff)
 }
 
 #[derive(Debug)]
@@ -104,7 +378,44 @@ pub enum StudyError {
     Http(reqwest::Error),
     MissingToken,
     MissingStudyId,
+    MissingChapterId,
+    InvalidVisibility(String),
     HttpStatus(StatusCode),
+    UnexpectedResponse(String),
+}
+
+impl std::fmt::Display for StudyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StudyError::Io(err) => write!(f, "failed to read study config: {err}"),
+            StudyError::ParseToml(err) => write!(f, "invalid study config: {err}"),
+            StudyError::Http(err) => write!(f, "Lichess request failed: {err}"),
+            StudyError::MissingToken => write!(f, "study config has an empty token"),
+            StudyError::MissingStudyId => write!(f, "no study id configured or given"),
+            StudyError::MissingChapterId => write!(f, "chapter id must not be empty"),
+            StudyError::InvalidVisibility(visibility) => write!(
+                f,
+                "invalid study visibility '{visibility}' (expected public, unlisted or private)"
+            ),
+            StudyError::HttpStatus(status) => {
+                write!(f, "Lichess responded with HTTP status {status}")
+            }
+            StudyError::UnexpectedResponse(body) => {
+                write!(f, "unexpected response from Lichess: {body}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StudyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StudyError::Io(err) => Some(err),
+            StudyError::ParseToml(err) => Some(err),
+            StudyError::Http(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl From<std::io::Error> for StudyError {
@@ -128,11 +439,23 @@ impl From<reqwest::Error> for StudyError {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use httpmock::Method::POST;
+    use httpmock::Method::{DELETE, GET, POST};
     use httpmock::MockServer;
     use std::fs;
     use std::io::Write;
 
+    pub(super) fn test_config(base_url: String, study_id: &str) -> StudyConfig {
+        StudyConfig {
+            token: "secret".to_string(),
+            study_id: study_id.to_string(),
+            base_url,
+            default_orientation: None,
+            max_retries: default_max_retries(),
+            retry_base_delay_ms: 1,
+            request_timeout_secs: default_request_timeout_secs(),
+        }
+    }
+
     fn write_temp_config(dir: &std::path::Path, body: &str) -> std::path::PathBuf {
         let path = dir.join("study.toml");
         let mut file = fs::File::create(&path).expect("create config");
@@ -158,6 +481,36 @@ default_orientation = "black"
         assert_eq!(cfg.study_id, "MyStudy");
         assert_eq!(cfg.base_url, "https://example.com");
         assert_eq!(cfg.default_orientation.as_deref(), Some("black"));
+        assert_eq!(cfg.max_retries, 3);
+        assert_eq!(cfg.retry_base_delay_ms, 1000);
+        assert_eq!(cfg.request_timeout_secs, Some(30));
+    }
+
+    #[test]
+    fn study_errors_display_readable_messages() {
+        use std::error::Error;
+
+        assert_eq!(
+            StudyError::MissingToken.to_string(),
+            "study config has an empty token"
+        );
+        assert_eq!(
+            StudyError::HttpStatus(StatusCode::NOT_FOUND).to_string(),
+            "Lichess responded with HTTP status 404 Not Found"
+        );
+
+        // Bind and drop a listener so nothing is accepting on its port.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("free port");
+        let http_err = Client::new()
+            .get(format!("http://{addr}/"))
+            .send()
+            .expect_err("connection refused");
+        let err = StudyError::from(http_err);
+        assert!(err.to_string().starts_with("Lichess request failed: "));
+        assert!(err.source().is_some());
+        assert!(StudyError::MissingStudyId.source().is_none());
     }
 
     #[test]
@@ -167,9 +520,8 @@ default_orientation = "black"
         let study_id = "ABCDEFGH";
         let cfg = StudyConfig {
             token: token.to_string(),
-            study_id: study_id.to_string(),
-            base_url: server.base_url(),
             default_orientation: Some("white".to_string()),
+            ..test_config(server.base_url(), study_id)
         };
 
         let mock = server.mock(|when, then| {
@@ -179,7 +531,7 @@ default_orientation = "black"
                 .body_contains("pgn=1.+e4+e5+2.+Nf3");
             then.status(200)
                 .header("content-type", "application/json")
-                .body(r#"{"chapters": []}"#);
+                .body(r#"{"chapters": [{"id": "chapA001", "name": "Line A"}, {"name": "no id"}]}"#);
         });
 
         let client = LichessStudyClient::new(cfg).expect("client");
@@ -190,7 +542,371 @@ default_orientation = "black"
             orientation: None,
         };
 
-        client.import_pgn(&payload).expect("import succeeds");
+        let chapter_ids = client.import_pgn(&payload).expect("import succeeds");
+        assert_eq!(chapter_ids, vec!["chapA001".to_string()]);
+        mock.assert();
+    }
+
+    #[test]
+    fn import_pgn_detects_orientation_from_repertoire_branches() {
+        let server = MockServer::start();
+        let study_id = "ABCDEFGH";
+        let black_repertoire = "1. e4 (1. d4 Nf6 2. c4 e6) (1. c4 e5) 1... c5 2. Nf3 d6 *";
+
+        let detected = server.mock(|when, then| {
+            when.method(POST)
+                .path(format!("/api/study/{study_id}/import-pgn"))
+                .body_contains("name=Black")
+                .body_contains("orientation=black");
+            then.status(200)
+                .body(r#"{"chapters": [{"id": "chapB001"}]}"#);
+        });
+        let explicit = server.mock(|when, then| {
+            when.method(POST)
+                .path(format!("/api/study/{study_id}/import-pgn"))
+                .body_contains("name=Explicit")
+                .body_contains("orientation=white");
+            then.status(200)
+                .body(r#"{"chapters": [{"id": "chapW001"}]}"#);
+        });
+
+        let client =
+            LichessStudyClient::new(test_config(server.base_url(), study_id)).expect("client");
+        let chapter = |name: &str, orientation: Option<&str>| StudyChapterImport {
+            study_id: None,
+            name: Some(name.to_string()),
+            pgn: black_repertoire.to_string(),
+            orientation: orientation.map(str::to_string),
+        };
+        assert_eq!(
+            client.import_pgn(&chapter("Black", None)).expect("import"),
+            vec!["chapB001".to_string()]
+        );
+        assert_eq!(
+            client
+                .import_pgn(&chapter("Explicit", Some("white")))
+                .expect("import"),
+            vec!["chapW001".to_string()]
+        );
+        detected.assert();
+        explicit.assert();
+
+        assert_eq!(
+            detect_orientation("1. e4 e5 (1... c5 2. Nf3) 2. Nf3 *").as_deref(),
+            Some("white")
+        );
+        assert_eq!(detect_orientation("1. e4 e5 2. Nf3 *"), None);
+    }
+
+    /// Serve `responses` in order, one per connection, and report how many
+    /// requests arrived. httpmock cannot script a sequence of responses.
+    fn scripted_server(responses: Vec<String>) -> (String, thread::JoinHandle<usize>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let base_url = format!("http://{}", listener.local_addr().expect("addr"));
+        let handle = thread::spawn(move || {
+            let mut served = 0;
+            for response in responses {
+                let (mut stream, _) = listener.accept().expect("accept");
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let read = std::io::Read::read(&mut stream, &mut buf).expect("read");
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..read]);
+                }
+                stream.write_all(response.as_bytes()).expect("write");
+                served += 1;
+            }
+            served
+        });
+        (base_url, handle)
+    }
+
+    #[test]
+    fn import_pgn_retries_after_rate_limit() {
+        let too_many = |retry_after: &str| {
+            format!(
+                "HTTP/1.1 429 Too Many Requests\r\n{retry_after}content-length: 0\r\nconnection: close\r\n\r\n"
+            )
+        };
+        let ok = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}".to_string();
+        let (base_url, server) =
+            scripted_server(vec![too_many("retry-after: 0\r\n"), too_many(""), ok]);
+
+        let client = LichessStudyClient::new(test_config(base_url, "ABCDEFGH")).expect("client");
+        let payload = StudyChapterImport {
+            study_id: None,
+            name: None,
+            pgn: "1. e4 e5 *".to_string(),
+            orientation: None,
+        };
+        client
+            .import_pgn(&payload)
+            .expect("import succeeds after retries");
+        assert_eq!(server.join().expect("server"), 3);
+    }
+
+    #[test]
+    fn rate_limited_requests_give_up_after_max_retries() {
+        let too_many =
+            "HTTP/1.1 429 Too Many Requests\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+        let (base_url, server) = scripted_server(vec![too_many.to_string(); 2]);
+        let cfg = StudyConfig {
+            max_retries: 1,
+            ..test_config(base_url, "ABCDEFGH")
+        };
+
+        let client = LichessStudyClient::new(cfg).expect("client");
+        let result = client.export_study_pgn(None);
+        assert!(matches!(
+            result,
+            Err(StudyError::HttpStatus(StatusCode::TOO_MANY_REQUESTS))
+        ));
+        assert_eq!(server.join().expect("server"), 2);
+    }
+
+    #[test]
+    fn slow_responses_time_out_as_http_errors() {
+        let server = MockServer::start();
+        let slow = server.mock(|when, then| {
+            when.method(GET).path("/api/study/ABCDEFGH.pgn");
+            then.status(200).delay(Duration::from_secs(5)).body("*");
+        });
+        let cfg = StudyConfig {
+            request_timeout_secs: Some(1),
+            ..test_config(server.base_url(), "ABCDEFGH")
+        };
+
+        let client = LichessStudyClient::new(cfg).expect("client");
+        let started = std::time::Instant::now();
+        let result = client.export_study_pgn(None);
+        assert!(matches!(result, Err(StudyError::Http(ref err)) if err.is_timeout()));
+        assert!(started.elapsed() < Duration::from_secs(4));
+        slow.assert();
+    }
+
+    #[test]
+    fn export_study_pgn_returns_study_text() {
+        let server = MockServer::start();
+        let cfg = test_config(server.base_url(), "ABCDEFGH");
+        let pgn = "[Event \"Line A\"]\n\n1. e4 e5 *\n";
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/study/ABCDEFGH.pgn")
+                .header("authorization", "Bearer secret");
+            then.status(200)
+                .header("content-type", "application/x-chess-pgn")
+                .body(pgn);
+        });
+        let missing = server.mock(|when, then| {
+            when.method(GET).path("/api/study/MISSING1.pgn");
+            then.status(404);
+        });
+
+        let client = LichessStudyClient::new(cfg).expect("client");
+        assert_eq!(client.export_study_pgn(None).expect("export"), pgn);
+        mock.assert();
+        assert!(matches!(
+            client.export_study_pgn(Some("MISSING1")),
+            Err(StudyError::HttpStatus(StatusCode::NOT_FOUND))
+        ));
+        missing.assert();
+    }
+
+    #[test]
+    fn delete_chapter_sends_delete_and_maps_status() {
+        let server = MockServer::start();
+        let cfg = test_config(server.base_url(), "ABCDEFGH");
+        let deleted = server.mock(|when, then| {
+            when.method(DELETE)
+                .path("/api/study/ABCDEFGH/CHAPTER1")
+                .header("authorization", "Bearer secret");
+            then.status(204);
+        });
+        let missing = server.mock(|when, then| {
+            when.method(DELETE).path("/api/study/ABCDEFGH/GONE0000");
+            then.status(404);
+        });
+
+        let client = LichessStudyClient::new(cfg).expect("client");
+        client
+            .delete_chapter(None, "CHAPTER1")
+            .expect("delete succeeds");
+        deleted.assert();
+        assert!(matches!(
+            client.delete_chapter(None, "GONE0000"),
+            Err(StudyError::HttpStatus(StatusCode::NOT_FOUND))
+        ));
+        missing.assert();
+        assert!(matches!(
+            client.delete_chapter(None, "  "),
+            Err(StudyError::MissingChapterId)
+        ));
+    }
+
+    #[test]
+    fn update_chapter_pgn_imports_replacement_then_deletes_old_chapter() {
+        let server = MockServer::start();
+        let import = server.mock(|when, then| {
+            when.method(POST)
+                .path("/api/study/ABCDEFGH/import-pgn")
+                .header("authorization", "Bearer secret")
+                .body_contains("pgn=1.+d4+d5+2.+c4")
+                .body_contains("name=Queen%27s+Gambit")
+                .body_contains("orientation=black");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"chapters": [{"id": "NEWCHAP1"}]}"#);
+        });
+        let delete = server.mock(|when, then| {
+            when.method(DELETE)
+                .path("/api/study/ABCDEFGH/OLDCHAP1")
+                .header("authorization", "Bearer secret");
+            then.status(204);
+        });
+
+        let client =
+            LichessStudyClient::new(test_config(server.base_url(), "ABCDEFGH")).expect("client");
+        let chapter_ids = client
+            .update_chapter_pgn(
+                None,
+                "OLDCHAP1",
+                "Queen's Gambit",
+                Some("black"),
+                "1. d4 d5 2. c4 *",
+            )
+            .expect("update succeeds");
+        assert_eq!(chapter_ids, vec!["NEWCHAP1".to_string()]);
+        import.assert();
+        delete.assert();
+    }
+
+    #[test]
+    fn update_chapter_pgn_keeps_old_chapter_when_import_fails() {
+        let server = MockServer::start();
+        let import = server.mock(|when, then| {
+            when.method(POST).path("/api/study/ABCDEFGH/import-pgn");
+            then.status(400);
+        });
+        let delete = server.mock(|when, then| {
+            when.method(DELETE).path("/api/study/ABCDEFGH/OLDCHAP1");
+            then.status(204);
+        });
+
+        let client =
+            LichessStudyClient::new(test_config(server.base_url(), "ABCDEFGH")).expect("client");
+        assert!(matches!(
+            client.update_chapter_pgn(None, "OLDCHAP1", "Queen's Pawn", None, "1. d4 *"),
+            Err(StudyError::HttpStatus(StatusCode::BAD_REQUEST))
+        ));
+        import.assert();
+        delete.assert_hits(0);
+    }
+
+    #[test]
+    fn create_study_posts_form_and_returns_id() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/api/study")
+                .header("authorization", "Bearer secret")
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body_contains("name=Sicilian+Najdorf")
+                .body_contains("visibility=unlisted");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"id": "NEWSTUDY", "name": "Sicilian Najdorf"}"#);
+        });
+
+        let client =
+            LichessStudyClient::new(test_config(server.base_url(), "ABCDEFGH")).expect("client");
+        let study_id = client
+            .create_study("Sicilian Najdorf", "unlisted")
+            .expect("create succeeds");
+        assert_eq!(study_id, "NEWSTUDY");
         mock.assert();
+        assert!(matches!(
+            client.create_study("Sicilian Najdorf", "secret"),
+            Err(StudyError::InvalidVisibility(v)) if v == "secret"
+        ));
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn import_pgn_tolerates_missing_or_malformed_chapters() {
+        let server = MockServer::start();
+        let empty = server.mock(|when, then| {
+            when.method(POST).path("/api/study/EMPTY001/import-pgn");
+            then.status(200).body("{}");
+        });
+        let malformed = server.mock(|when, then| {
+            when.method(POST).path("/api/study/BROKEN01/import-pgn");
+            then.status(200).body(r#"{"chapters": "oops"}"#);
+        });
+
+        let client =
+            LichessStudyClient::new(test_config(server.base_url(), "EMPTY001")).expect("client");
+        let chapter = |study_id: &str| StudyChapterImport {
+            study_id: Some(study_id.to_string()),
+            name: None,
+            pgn: "1. e4 e5 *".to_string(),
+            orientation: None,
+        };
+        assert!(
+            client
+                .import_pgn(&chapter("EMPTY001"))
+                .expect("empty")
+                .is_empty()
+        );
+        assert!(
+            client
+                .import_pgn(&chapter("BROKEN01"))
+                .expect("malformed")
+                .is_empty()
+        );
+        empty.assert();
+        malformed.assert();
+    }
+
+    #[test]
+    fn import_pgn_batch_reports_progress_per_chapter() {
+        let server = MockServer::start();
+        let cfg = test_config(server.base_url(), "GOODSTDY");
+        let ok_mock = server.mock(|when, then| {
+            when.method(POST).path("/api/study/GOODSTDY/import-pgn");
+            then.status(200).body(r#"{"chapters": []}"#);
+        });
+        let missing_mock = server.mock(|when, then| {
+            when.method(POST).path("/api/study/MISSING1/import-pgn");
+            then.status(404);
+        });
+
+        let chapter = |study_id: Option<&str>| StudyChapterImport {
+            study_id: study_id.map(str::to_string),
+            name: None,
+            pgn: "1. e4 e5 *".to_string(),
+            orientation: None,
+        };
+        let payloads = vec![chapter(None), chapter(Some("MISSING1")), chapter(None)];
+
+        let client = LichessStudyClient::new(cfg).expect("client");
+        let mut calls: Vec<(usize, usize, bool)> = Vec::new();
+        let results = client.import_pgn_batch_with_progress(&payloads, |index, total, result| {
+            calls.push((index, total, result.is_ok()));
+        });
+
+        assert_eq!(calls, vec![(0, 3, true), (1, 3, false), (2, 3, true)]);
+        assert_eq!(
+            results.iter().map(Result::is_ok).collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+        assert!(matches!(
+            results[1],
+            Err(StudyError::HttpStatus(StatusCode::NOT_FOUND))
+        ));
+        ok_mock.assert_hits(2);
+        missing_mock.assert();
     }
 }
@@ -0,0 +1,281 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a connection waits for another worker's write to finish.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS positions (
+    fen TEXT PRIMARY KEY,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'leased')),
+    worker_id TEXT,
+    leased_at INTEGER,
+    ttl INTEGER
+);
+CREATE INDEX IF NOT EXISTS positions_status ON positions (status);
+";
+
+/// A shared queue of FENs awaiting analysis. A worker leases a FEN for a
+/// time-to-live, renews the lease while it works, and completes or releases
+/// it when done. A lease that is not renewed in time expires and the FEN goes
+/// back to the queue for the next worker.
+pub trait PositionWorkQueue {
+    /// Add `fen` to the queue; returns `false` if it is already queued or leased.
+    fn enqueue(&self, fen: &str) -> Result<bool, WorkQueueError>;
+
+    /// Lease the oldest available FEN to `worker_id`, or `None` when nothing is
+    /// pending.
+    fn lease(&self, worker_id: &str, ttl: Duration) -> Result<Option<String>, WorkQueueError>;
+
+    /// Give up a lease without completing it, making the FEN available again.
+    fn release(&self, fen: &str, worker_id: &str) -> Result<(), WorkQueueError>;
+
+    /// Extend a lease to `ttl` from now.
+    fn renew(&self, fen: &str, worker_id: &str, ttl: Duration) -> Result<(), WorkQueueError>;
+
+    /// Remove a leased FEN from the queue for good.
+    fn complete(&self, fen: &str, worker_id: &str) -> Result<(), WorkQueueError>;
+}
+
+#[derive(Debug)]
+pub enum WorkQueueError {
+    Database(rusqlite::Error),
+    /// The FEN is not leased to this worker, e.g. because the lease expired
+    /// and another worker took it.
+    LeaseLost {
+        fen: String,
+        worker_id: String,
+    },
+}
+
+impl std::fmt::Display for WorkQueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkQueueError::Database(err) => write!(f, "work queue database error: {err}"),
+            WorkQueueError::LeaseLost { fen, worker_id } => {
+                write!(f, "worker '{worker_id}' does not hold a lease on '{fen}'")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorkQueueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WorkQueueError::Database(err) => Some(err),
+            WorkQueueError::LeaseLost { .. } => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for WorkQueueError {
+    fn from(err: rusqlite::Error) -> Self {
+        WorkQueueError::Database(err)
+    }
+}
+
+/// `PositionWorkQueue` in a SQLite file. Each worker opens its own connection;
+/// leasing is a single `UPDATE`, so two workers never get the same FEN.
+pub struct SqliteWorkQueue {
+    conn: Connection,
+}
+
+impl SqliteWorkQueue {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, WorkQueueError> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Number of FENs waiting for a worker, expired leases included.
+    pub fn pending_count(&self) -> Result<u64, WorkQueueError> {
+        let count = self.conn.query_row(
+            "SELECT COUNT(*) FROM positions
+             WHERE status = 'pending' OR leased_at + ttl <= ?1",
+            params![now_millis()],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    fn expect_one_row(changed: usize, fen: &str, worker_id: &str) -> Result<(), WorkQueueError> {
+        if changed == 1 {
+            Ok(())
+        } else {
+            Err(WorkQueueError::LeaseLost {
+                fen: fen.to_string(),
+                worker_id: worker_id.to_string(),
+            })
+        }
+    }
+}
+
+impl PositionWorkQueue for SqliteWorkQueue {
+    fn enqueue(&self, fen: &str) -> Result<bool, WorkQueueError> {
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO positions (fen, status) VALUES (?1, 'pending')",
+            params![fen],
+        )?;
+        Ok(inserted == 1)
+    }
+
+    fn lease(&self, worker_id: &str, ttl: Duration) -> Result<Option<String>, WorkQueueError> {
+        let now = now_millis();
+        let fen = self
+            .conn
+            .query_row(
+                "UPDATE positions
+                 SET status = 'leased', worker_id = ?1, leased_at = ?2, ttl = ?3
+                 WHERE fen = (
+                     SELECT fen FROM positions
+                     WHERE status = 'pending' OR leased_at + ttl <= ?2
+                     ORDER BY rowid
+                     LIMIT 1
+                 )
+                 RETURNING fen",
+                params![worker_id, now, duration_millis(ttl)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(fen)
+    }
+
+    fn release(&self, fen: &str, worker_id: &str) -> Result<(), WorkQueueError> {
+        let changed = self.conn.execute(
+            "UPDATE positions
+             SET status = 'pending', worker_id = NULL, leased_at = NULL, ttl = NULL
+             WHERE fen = ?1 AND status = 'leased' AND worker_id = ?2",
+            params![fen, worker_id],
+        )?;
+        Self::expect_one_row(changed, fen, worker_id)
+    }
+
+    fn renew(&self, fen: &str, worker_id: &str, ttl: Duration) -> Result<(), WorkQueueError> {
+        let changed = self.conn.execute(
+            "UPDATE positions SET leased_at = ?3, ttl = ?4
+             WHERE fen = ?1 AND status = 'leased' AND worker_id = ?2",
+            params![fen, worker_id, now_millis(), duration_millis(ttl)],
+        )?;
+        Self::expect_one_row(changed, fen, worker_id)
+    }
+
+    fn complete(&self, fen: &str, worker_id: &str) -> Result<(), WorkQueueError> {
+        let changed = self.conn.execute(
+            "DELETE FROM positions WHERE fen = ?1 AND status = 'leased' AND worker_id = ?2",
+            params![fen, worker_id],
+        )?;
+        Self::expect_one_row(changed, fen, worker_id)
+    }
+}
+
+fn now_millis() -> i64 {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    duration_millis(elapsed)
+}
+
+fn duration_millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::thread;
+    use tempfile::tempdir;
+
+    const LONG_TTL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn concurrent_workers_never_lease_the_same_fen() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("queue.sqlite");
+        let queue = SqliteWorkQueue::open(&path).expect("open queue");
+        for index in 0..40 {
+            assert!(queue.enqueue(&format!("fen {index}")).expect("enqueue"));
+        }
+
+        let handles: Vec<_> = (0..4)
+            .map(|worker| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    let queue = SqliteWorkQueue::open(&path).expect("open queue");
+                    let worker_id = format!("worker-{worker}");
+                    let mut leased = Vec::new();
+                    while let Some(fen) = queue.lease(&worker_id, LONG_TTL).expect("lease") {
+                        leased.push(fen);
+                    }
+                    leased
+                })
+            })
+            .collect();
+        let leased: Vec<String> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("worker thread"))
+            .collect();
+
+        let unique: HashSet<&String> = leased.iter().collect();
+        assert_eq!(leased.len(), 40);
+        assert_eq!(unique.len(), 40);
+        assert_eq!(queue.pending_count().expect("count"), 0);
+    }
+
+    #[test]
+    fn expired_lease_is_requeued_for_another_worker() {
+        let dir = tempdir().expect("tempdir");
+        let queue = SqliteWorkQueue::open(dir.path().join("queue.sqlite")).expect("open queue");
+        queue.enqueue("fen a").expect("enqueue");
+
+        let first = queue.lease("worker-1", Duration::ZERO).expect("lease");
+        assert_eq!(first.as_deref(), Some("fen a"));
+        let second = queue.lease("worker-2", LONG_TTL).expect("lease");
+        assert_eq!(second.as_deref(), Some("fen a"));
+        assert_eq!(queue.lease("worker-3", LONG_TTL).expect("lease"), None);
+
+        let err = queue
+            .complete("fen a", "worker-1")
+            .expect_err("stale lease must not complete");
+        assert!(matches!(err, WorkQueueError::LeaseLost { .. }));
+        queue
+            .renew("fen a", "worker-2", LONG_TTL)
+            .expect("current holder renews");
+    }
+
+    #[test]
+    fn complete_removes_fen_and_release_requeues_it() {
+        let dir = tempdir().expect("tempdir");
+        let queue = SqliteWorkQueue::open(dir.path().join("queue.sqlite")).expect("open queue");
+        queue.enqueue("fen a").expect("enqueue");
+        queue.enqueue("fen b").expect("enqueue");
+        assert!(!queue.enqueue("fen a").expect("enqueue duplicate"));
+
+        let fen = queue.lease("worker-1", LONG_TTL).expect("lease");
+        assert_eq!(fen.as_deref(), Some("fen a"));
+        queue.release("fen a", "worker-1").expect("release");
+        assert_eq!(queue.pending_count().expect("count"), 2);
+
+        let fen = queue
+            .lease("worker-1", LONG_TTL)
+            .expect("lease")
+            .expect("fen");
+        queue.complete(&fen, "worker-1").expect("complete");
+        let fen = queue
+            .lease("worker-1", LONG_TTL)
+            .expect("lease")
+            .expect("fen");
+        queue.complete(&fen, "worker-1").expect("complete");
+
+        assert_eq!(queue.pending_count().expect("count"), 0);
+        assert_eq!(queue.lease("worker-1", LONG_TTL).expect("lease"), None);
+        let remaining: i64 = queue
+            .conn
+            .query_row("SELECT COUNT(*) FROM positions", [], |row| row.get(0))
+            .expect("count rows");
+        assert_eq!(remaining, 0);
+    }
+}
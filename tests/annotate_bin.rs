@@ -0,0 +1,130 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use _core::checkpoint::{Checkpoint, load_checkpoint, save_checkpoint};
+use _core::pgn::parse_games;
+use assert_cmd::Command;
+use tempfile::tempdir;
+
+/// A UCI stub that scores White-to-move positions at +34cp and has Black to
+/// move getting mated in two.
+fn write_stub_engine(dir: &Path) -> PathBuf {
+    let path = dir.join("stub_engine.sh");
+    let script = r#"#!/bin/sh
+side=w
+while read -r line; do
+  case "$line" in
+    uci) echo "id name stub"; echo "uciok" ;;
+    isready) echo "readyok" ;;
+    "position fen "*) set -- $line; side=$4 ;;
+    go*)
+      if [ "$side" = b ]; then
+        echo "info depth 5 multipv 1 score mate -2 nodes 10 pv e7e5"
+      else
+        echo "info depth 5 multipv 1 score cp 34 nodes 10 pv e2e4"
+      fi
+      echo "bestmove 0000" ;;
+    quit) exit 0 ;;
+  esac
+done
+"#;
+    fs::write(&path, script).expect("write stub engine");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("chmod stub engine");
+    path
+}
+
+#[test]
+fn annotate_cli_writes_eval_comments_after_each_move() {
+    let tmp = tempdir().expect("tempdir");
+    let engine = write_stub_engine(tmp.path());
+    let pgn_path = tmp.path().join("repertoire.pgn");
+    fs::write(
+        &pgn_path,
+        "[Event \"Club \\\"open\\\" night\"]\n[Result \"*\"]\n\n\
+         1. e4 $1 e5 {open game} (1... c5) 2. Nf3 Nc6 *\n",
+    )
+    .expect("write pgn");
+
+    #[allow(deprecated)]
+    let output = Command::cargo_bin("annotate")
+        .expect("annotate bin")
+        .args([
+            pgn_path.to_str().unwrap(),
+            "--engine",
+            engine.to_str().unwrap(),
+            "--depth",
+            "5",
+        ])
+        .output()
+        .expect("run annotate");
+    assert!(
+        output.status.success(),
+        "annotate exited with failure. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let annotated = String::from_utf8(output.stdout).expect("utf8 output");
+    assert_eq!(
+        annotated,
+        "[Event \"Club \\\"open\\\" night\"]\n[Result \"*\"]\n\n\
+         1. e4 $1 { [%eval #2] } 1... e5 { open game } { [%eval 0.34] } \
+         (1... c5 { [%eval 0.34] }) 2. Nf3 { [%eval #2] } 2... Nc6 { [%eval 0.34] } *\n"
+    );
+    let games = parse_games(&annotated).expect("annotated PGN parses");
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].headers[0].1, "Club \"open\" night");
+    assert_eq!(games[0].moves.len(), 4);
+    assert_eq!(games[0].moves[0].nags, vec![1]);
+    assert_eq!(
+        games[0].moves[1].comments,
+        vec!["open game", "[%eval 0.34]"]
+    );
+    assert_eq!(games[0].moves[1].variations[0].len(), 1);
+}
+
+#[test]
+fn annotate_cli_resumes_from_checkpoint() {
+    let tmp = tempdir().expect("tempdir");
+    let engine = write_stub_engine(tmp.path());
+    let pgn_path = tmp.path().join("repertoire.pgn");
+    fs::write(&pgn_path, "[Event \"?\"]\n[Result \"*\"]\n\n1. e4 e5 *\n").expect("write pgn");
+    let checkpoint_path = tmp.path().join("annotate.checkpoint.json");
+    let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+    let mut saved = Checkpoint::default();
+    saved
+        .evaluated
+        .insert(after_e4.to_string(), "[%eval 9.99]".to_string());
+    save_checkpoint(&checkpoint_path, &saved).expect("seed checkpoint");
+
+    #[allow(deprecated)]
+    let output = Command::cargo_bin("annotate")
+        .expect("annotate bin")
+        .args([
+            pgn_path.to_str().unwrap(),
+            "--engine",
+            engine.to_str().unwrap(),
+            "--checkpoint",
+            checkpoint_path.to_str().unwrap(),
+        ])
+        .output()
+        .expect("run annotate");
+    assert!(
+        output.status.success(),
+        "annotate exited with failure. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).expect("utf8 output"),
+        "[Event \"?\"]\n[Result \"*\"]\n\n1. e4 { [%eval 9.99] } 1... e5 { [%eval 0.34] } *\n"
+    );
+    let progress: Checkpoint<String> = load_checkpoint(&checkpoint_path)
+        .expect("read checkpoint")
+        .expect("checkpoint saved");
+    assert_eq!(progress.cursor, 1);
+    assert_eq!(progress.evaluated.len(), 2);
+    assert_eq!(progress.evaluated[after_e4], "[%eval 9.99]");
+}
@@ -0,0 +1,70 @@
+use std::fs;
+
+use assert_cmd::Command;
+use tempfile::tempdir;
+
+const REPERTOIRE: &str = "[Event \"Rated Blitz game\"]\n[Result \"*\"]\n\n\
+                          1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 3. Bb5 *\n\n\
+                          [Event \"Casual Rapid game\"]\n[Result \"*\"]\n\n1. d4 d5 2. c4 *\n";
+
+fn run_export(side: &str) -> Vec<Vec<String>> {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("repertoire.pgn");
+    fs::write(&pgn_path, REPERTOIRE).expect("write pgn");
+
+    #[allow(deprecated)]
+    let output = Command::cargo_bin("export_anki")
+        .expect("export_anki bin")
+        .args([pgn_path.to_str().unwrap(), "--side", side])
+        .output()
+        .expect("run export_anki");
+    assert!(
+        output.status.success(),
+        "export_anki exited with failure. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .expect("utf8 output")
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.split('\t').map(str::to_string).collect())
+        .collect()
+}
+
+#[test]
+fn export_anki_writes_one_card_per_white_decision() {
+    let cards = run_export("white");
+
+    // Start, 1. e4 c5, 1. e4 e5, 2... Nc6 and 1. d4 d5, in first-seen order.
+    assert_eq!(cards.len(), 5);
+    assert!(cards.iter().all(|card| card.len() == 3));
+    assert_eq!(
+        cards[0],
+        vec![
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "e4 / d4",
+            "",
+        ]
+    );
+    assert_eq!(
+        cards[1],
+        vec![
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1",
+            "Nf3",
+            "1. e4 c5",
+        ]
+    );
+    assert_eq!(cards[3][1], "Bb5");
+    assert_eq!(cards[3][2], "1. e4 e5 2. Nf3 Nc6");
+}
+
+#[test]
+fn export_anki_writes_one_card_per_black_decision() {
+    let cards = run_export("black");
+
+    // 1. e4, 2. Nf3 and 1. d4.
+    assert_eq!(cards.len(), 3);
+    assert_eq!(cards[0][1], "e5 / c5");
+    assert_eq!(cards[0][2], "1. e4");
+    assert_eq!(cards[2][1], "d5");
+}
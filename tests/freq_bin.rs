@@ -1,7 +1,10 @@
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 
 use assert_cmd::Command;
+use flate2::Compression;
+use flate2::write::GzEncoder;
 use serde_json::Value;
 use tempfile::tempdir;
 
@@ -57,3 +60,639 @@ fn freq_cli_outputs_expected_json() {
         .any(|m| m["san"] == "e4");
     assert!(has_e4, "expected to see e4 in any ranking entry");
 }
+
+fn run_freq(args: &[&str]) -> Value {
+    #[allow(deprecated)]
+    let output = Command::cargo_bin("freq")
+        .expect("freq bin")
+        .args(args)
+        .output()
+        .expect("run freq");
+
+    assert!(
+        output.status.success(),
+        "freq exited with failure. stdout: {} stderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).expect("json output")
+}
+
+fn start_position_frequency(payload: &Value, san: &str) -> u64 {
+    let start_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    payload["rankings"][start_fen]
+        .as_array()
+        .expect("start position moves")
+        .iter()
+        .find(|m| m["san"] == san)
+        .and_then(|m| m["frequency"].as_u64())
+        .expect("move frequency")
+}
+
+#[test]
+fn freq_cli_collapses_repeated_positions() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("shuffle.pgn");
+    fs::write(
+        &pgn_path,
+        "[Result \"*\"]\n\n1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 5. e4 *\n",
+    )
+    .expect("write pgn");
+    let path = pgn_path.to_str().unwrap();
+
+    let raw = run_freq(&[path, "--side", "white", "--indent", "0"]);
+    assert_eq!(start_position_frequency(&raw, "Nf3"), 2);
+
+    let collapsed = run_freq(&[
+        path,
+        "--side",
+        "white",
+        "--indent",
+        "0",
+        "--collapse-repetitions",
+    ]);
+    assert_eq!(start_position_frequency(&collapsed, "Nf3"), 1);
+    let start_moves =
+        collapsed["rankings"]["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"]
+            .as_array()
+            .expect("start position moves");
+    assert_eq!(start_moves.iter().filter(|m| m["san"] == "Nf3").count(), 1);
+}
+
+#[test]
+fn freq_cli_keys_moves_by_requested_notation() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("freq_input.pgn");
+    write_sample_pgn(&pgn_path);
+    let path = pgn_path.to_str().unwrap();
+
+    for (notation, expected_key) in [("san", "e4"), ("uci", "e2e4")] {
+        let payload = run_freq(&[path, "--side", "white", "--key", notation]);
+        assert_eq!(payload["key"], notation);
+        let start_moves =
+            payload["rankings"]["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"]
+                .as_array()
+                .expect("start position moves");
+        assert_eq!(start_moves[0]["key"], expected_key);
+        assert_eq!(start_moves[0]["san"], "e4");
+        assert_eq!(start_moves[0]["uci"], "e2e4");
+    }
+}
+
+#[test]
+fn freq_cli_sorted_output_is_byte_identical_across_runs() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("freq_input.pgn");
+    write_sample_pgn(&pgn_path);
+    let run = || {
+        #[allow(deprecated)]
+        let output = Command::cargo_bin("freq")
+            .expect("freq bin")
+            .args([pgn_path.to_str().unwrap(), "--side", "black", "--sorted"])
+            .output()
+            .expect("run freq");
+        assert!(output.status.success());
+        output.stdout
+    };
+
+    let first = run();
+    assert_eq!(first, run());
+    let payload: Value = serde_json::from_slice(&first).expect("json output");
+    assert!(payload.get("generated_at").is_none());
+    let fens: Vec<&str> = payload["rankings"]
+        .as_array()
+        .expect("rankings array")
+        .iter()
+        .map(|entry| entry["fen"].as_str().expect("fen"))
+        .collect();
+    assert_eq!(fens.len(), 3);
+    assert!(fens.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn freq_cli_skips_multi_word_comments() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("commented.pgn");
+    fs::write(
+        &pgn_path,
+        "[Event \"?\"]\n[Result \"*\"]\n\n\
+         1. e4 {the main move, see {nested} notes} e5 2. Nf3{develop} Nc6\n\
+         3. Bb5 } a6 {unterminated comment Qxf7 *\n",
+    )
+    .expect("write pgn");
+
+    let payload = run_freq(&[pgn_path.to_str().unwrap(), "--side", "white", "--sorted"]);
+    let mut sans: Vec<&str> = payload["rankings"]
+        .as_array()
+        .expect("rankings array")
+        .iter()
+        .flat_map(|entry| entry["moves"].as_array().expect("moves"))
+        .map(|m| m["san"].as_str().expect("san"))
+        .collect();
+    sans.sort();
+    assert_eq!(sans, vec!["Bb5", "Nf3", "e4"]);
+}
+
+#[test]
+fn freq_cli_counts_moves_inside_variations() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("branching.pgn");
+    fs::write(
+        &pgn_path,
+        "[Result \"*\"]\n\n\
+         1. e4 e5 (1... c5 2. Nf3 (2. c3 d5) d6) 2. Nf3 (2. Bc4 Nf6) Nc6 3. Bb5 *\n",
+    )
+    .expect("write pgn");
+
+    let payload = run_freq(&[pgn_path.to_str().unwrap(), "--side", "white"]);
+    let sans_at = |fen: &str| -> Vec<String> {
+        let mut sans: Vec<String> = payload["rankings"][fen]
+            .as_array()
+            .expect("position moves")
+            .iter()
+            .map(|m| m["san"].as_str().expect("san").to_string())
+            .collect();
+        sans.sort();
+        sans
+    };
+    assert_eq!(
+        sans_at("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1"),
+        vec!["Bc4", "Nf3"]
+    );
+    assert_eq!(
+        sans_at("rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1"),
+        vec!["Nf3", "c3"]
+    );
+    assert_eq!(
+        sans_at("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1"),
+        vec!["Bb5"]
+    );
+}
+
+#[test]
+fn freq_cli_aggregates_every_game_in_the_file() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("two_games.pgn");
+    fs::write(
+        &pgn_path,
+        "[Event \"Rated Blitz game\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n\n\
+         [Event \"two\"]\n[Result \"*\"]\n\n1. d4 d5 2. Nf3 *\n\n\
+         [Event \"three\"]\n\n1. Nf3 d5\n\n\
+         [Event \"four\"]\n\n1. e4 c5\n",
+    )
+    .expect("write pgn");
+
+    let payload = run_freq(&[pgn_path.to_str().unwrap(), "--side", "white"]);
+    assert_eq!(start_position_frequency(&payload, "e4"), 2);
+    assert_eq!(start_position_frequency(&payload, "d4"), 1);
+    assert_eq!(start_position_frequency(&payload, "Nf3"), 3);
+    assert_eq!(payload["total_nodes"], 5);
+}
+
+#[test]
+fn freq_cli_writes_csv_rows() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("freq_input.pgn");
+    write_sample_pgn(&pgn_path);
+
+    #[allow(deprecated)]
+    let output = Command::cargo_bin("freq")
+        .expect("freq bin")
+        .args([
+            pgn_path.to_str().unwrap(),
+            "--side",
+            "white",
+            "--format",
+            "csv",
+        ])
+        .output()
+        .expect("run freq");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("fen,uci,san,frequency"));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows.iter().all(|row| row.len() == 4));
+    assert!(rows.windows(2).all(|pair| pair[0][0] <= pair[1][0]));
+    let e4 = rows.iter().find(|row| row[2] == "e4").expect("e4 row");
+    assert_eq!(
+        e4[0],
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    );
+    assert_eq!(e4[1], "e2e4");
+    assert_eq!(e4[3], "1");
+}
+
+#[test]
+fn freq_cli_count_only_matches_full_run() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("freq_input.pgn");
+    fs::write(
+        &pgn_path,
+        "1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 *\n\n1. d4 d5 *\n",
+    )
+    .expect("write pgn");
+    let pgn = pgn_path.to_str().unwrap();
+
+    let full = run_freq(&[pgn, "--side", "white"]);
+    let counts = run_freq(&[pgn, "--side", "white", "--count-only"]);
+
+    assert!(counts.get("rankings").is_none());
+    assert_eq!(counts["games"], 2);
+    assert_eq!(counts["total_moves"], 8);
+    assert_eq!(counts["player_nodes"], full["total_nodes"]);
+}
+
+#[test]
+fn freq_cli_reads_pgn_from_stdin() {
+    #[allow(deprecated)]
+    let output = Command::cargo_bin("freq")
+        .expect("freq bin")
+        .args(["-", "--side", "white"])
+        .write_stdin("1. e4 e5 2. Nf3 *\n")
+        .output()
+        .expect("run freq");
+    assert!(
+        output.status.success(),
+        "freq exited with failure. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let payload: Value =
+        serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).expect("json output");
+    assert_eq!(start_position_frequency(&payload, "e4"), 1);
+    assert_eq!(payload["total_nodes"], 2);
+}
+
+#[test]
+fn freq_cli_weights_frequencies_by_game_result() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("results.pgn");
+    fs::write(
+        &pgn_path,
+        "[Result \"1-0\"]\n\n1. e4 e5 1-0\n\n\
+         [Result \"1-0\"]\n\n1. e4 c5 1-0\n\n\
+         [Result \"0-1\"]\n\n1. d4 d5 0-1\n\n\
+         [Result \"1/2-1/2\"]\n\n1. c4 e5 1/2-1/2\n",
+    )
+    .expect("write pgn");
+    let pgn = pgn_path.to_str().unwrap();
+
+    let raw = run_freq(&[pgn, "--side", "white"]);
+    assert_eq!(start_position_frequency(&raw, "e4"), 2);
+    assert_eq!(start_position_frequency(&raw, "d4"), 1);
+    assert_eq!(start_position_frequency(&raw, "c4"), 1);
+
+    let weighted = run_freq(&[pgn, "--side", "white", "--weight-by-result"]);
+    assert_eq!(start_position_frequency(&weighted, "e4"), 6);
+    assert_eq!(start_position_frequency(&weighted, "d4"), 0);
+    assert_eq!(start_position_frequency(&weighted, "c4"), 1);
+
+    let as_black = run_freq(&[
+        pgn,
+        "--side",
+        "black",
+        "--weight-by-result",
+        "--result-weights",
+        "5,2,1",
+    ]);
+    let after_d4 = "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1";
+    let d5 = as_black["rankings"][after_d4]
+        .as_array()
+        .expect("after d4")
+        .iter()
+        .find(|m| m["san"] == "d5")
+        .expect("d5");
+    assert_eq!(d5["frequency"], 5);
+}
+
+#[test]
+fn freq_cli_reports_legal_move_completeness() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("completeness.pgn");
+    fs::write(&pgn_path, "1. e4 e5 *\n\n1. d4 d5 *\n\n1. e4 c5 *\n").expect("write pgn");
+    let pgn = pgn_path.to_str().unwrap();
+
+    let plain = run_freq(&[pgn, "--side", "white"]);
+    assert!(plain.get("completeness").is_none());
+
+    let payload = run_freq(&[pgn, "--side", "white", "--with-completeness"]);
+    let start_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let start = &payload["completeness"][start_fen];
+    assert_eq!(start["legal_move_count"], 20);
+    assert_eq!(start["covered_count"], 2);
+    assert_eq!(
+        payload["rankings"][start_fen]
+            .as_array()
+            .expect("start")
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn freq_cli_completeness_counts_moves_before_top_n() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("completeness.pgn");
+    fs::write(&pgn_path, "1. e4 e5 *\n\n1. d4 d5 *\n\n1. e4 c5 *\n").expect("write pgn");
+    let pgn = pgn_path.to_str().unwrap();
+
+    let payload = run_freq(&[
+        pgn,
+        "--side",
+        "white",
+        "--with-completeness",
+        "--top-n",
+        "1",
+    ]);
+    let start_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let moves = payload["rankings"][start_fen].as_array().expect("start");
+    assert_eq!(moves.len(), 1);
+    assert_eq!(moves[0]["san"], "e4");
+    assert_eq!(payload["completeness"][start_fen]["covered_count"], 2);
+}
+
+#[test]
+fn freq_cli_reads_gzipped_pgn() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("freq_input.pgn");
+    write_sample_pgn(&pgn_path);
+    let gz_path = tmp.path().join("freq_input.pgn.gz");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&fs::read(&pgn_path).expect("read pgn"))
+        .expect("gzip pgn");
+    fs::write(&gz_path, encoder.finish().expect("finish gzip")).expect("write gz");
+    // Same bytes without the extension exercise magic-byte detection.
+    let sniffed_path = tmp.path().join("freq_input_compressed");
+    fs::copy(&gz_path, &sniffed_path).expect("copy gz");
+
+    let plain = run_freq(&[pgn_path.to_str().unwrap(), "--side", "white"]);
+    let gzipped = run_freq(&[gz_path.to_str().unwrap(), "--side", "white"]);
+    let sniffed = run_freq(&[sniffed_path.to_str().unwrap(), "--side", "white"]);
+
+    assert_eq!(gzipped["rankings"], plain["rankings"]);
+    assert_eq!(sniffed["rankings"], plain["rankings"]);
+    assert_eq!(gzipped["total_nodes"], plain["total_nodes"]);
+}
+
+#[test]
+fn freq_cli_attaches_nags_to_ranked_moves() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("nags.pgn");
+    fs::write(
+        &pgn_path,
+        "1. e4 $1 e5 2. Nf3 $1 $14 Nc6 *\n\n1. e4 $3 c5 2. Nf3 *\n",
+    )
+    .expect("write pgn");
+
+    let payload = run_freq(&[pgn_path.to_str().unwrap(), "--side", "white"]);
+    let start_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let e4 = &payload["rankings"][start_fen][0];
+    assert_eq!(e4["san"], "e4");
+    assert_eq!(e4["nags"], serde_json::json!([1, 3]));
+
+    let nf3_nags: Vec<&Value> = payload["rankings"]
+        .as_object()
+        .expect("rankings map")
+        .values()
+        .flat_map(|moves| moves.as_array().expect("moves"))
+        .filter(|m| m["san"] == "Nf3")
+        .map(|m| &m["nags"])
+        .collect();
+    assert_eq!(nf3_nags.len(), 2);
+    assert!(nf3_nags.contains(&&serde_json::json!([1, 14])));
+    assert!(nf3_nags.iter().any(|nags| nags.is_null()));
+}
+
+#[test]
+fn freq_cli_top_n_caps_moves_per_position() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("wide.pgn");
+    fs::write(
+        &pgn_path,
+        "1. e4 e5 *\n\n1. e4 c5 *\n\n1. d4 d5 *\n\n1. c4 e5 *\n\n1. e4 e6 *\n\n1. d4 Nf6 *\n",
+    )
+    .expect("write pgn");
+    let pgn = pgn_path.to_str().unwrap();
+
+    let capped = run_freq(&[pgn, "--side", "white", "--top-n", "2"]);
+    let rankings = capped["rankings"].as_object().expect("rankings map");
+    assert!(
+        rankings
+            .values()
+            .all(|moves| moves.as_array().unwrap().len() <= 2)
+    );
+    let start_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let top: Vec<&Value> = rankings[start_fen]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| &m["san"])
+        .collect();
+    assert_eq!(top, vec!["e4", "d4"]);
+
+    let full = run_freq(&[pgn, "--side", "white"]);
+    assert_eq!(full["rankings"][start_fen].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn freq_cli_min_frequency_drops_rare_sidelines() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("rare.pgn");
+    fs::write(
+        &pgn_path,
+        "1. e4 e5 2. Nf3 *\n\n1. e4 e5 2. Nf3 *\n\n1. e4 c5 2. Nc3 *\n",
+    )
+    .expect("write pgn");
+    let pgn = pgn_path.to_str().unwrap();
+
+    let full = run_freq(&[pgn, "--side", "white"]);
+    let filtered = run_freq(&[pgn, "--side", "white", "--min-frequency", "2"]);
+
+    let sicilian = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1";
+    assert!(full["rankings"].get(sicilian).is_some());
+    assert!(filtered["rankings"].get(sicilian).is_none());
+    assert_eq!(start_position_frequency(&filtered, "e4"), 3);
+    let rankings = filtered["rankings"].as_object().expect("rankings map");
+    assert!(
+        rankings
+            .values()
+            .flat_map(|moves| moves.as_array().unwrap())
+            .all(|m| m["frequency"].as_u64() >= Some(2))
+    );
+    assert_eq!(
+        filtered["total_nodes"].as_u64(),
+        Some(rankings.len() as u64)
+    );
+    assert_eq!(filtered["total_nodes"], 2);
+}
+
+#[test]
+fn freq_cli_default_output_orders_fens_deterministically() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("orders.pgn");
+    fs::write(
+        &pgn_path,
+        "1. e4 e5 2. Nf3 Nc6 *\n\n1. d4 d5 2. c4 e6 *\n\n1. c4 e5 2. Nc3 *\n",
+    )
+    .expect("write pgn");
+    let run = |extra: &[&str]| {
+        #[allow(deprecated)]
+        let output = Command::cargo_bin("freq")
+            .expect("freq bin")
+            .args([
+                pgn_path.to_str().unwrap(),
+                "--side",
+                "white",
+                "--indent",
+                "0",
+            ])
+            .args(extra)
+            .output()
+            .expect("run freq");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).expect("utf8 output")
+    };
+
+    let first = run(&[]);
+    assert_eq!(first, run(&[]));
+    let payload: Value = serde_json::from_str(&first).expect("json");
+    assert!(payload.get("generated_at").is_none());
+    let stamped: Value = serde_json::from_str(&run(&["--timestamp"])).expect("json");
+    assert!(stamped["generated_at"].is_string());
+    // serde_json's map sorts keys on parse, so check the order in the raw text.
+    let offsets: Vec<usize> = payload["rankings"]
+        .as_object()
+        .expect("rankings map")
+        .keys()
+        .map(|fen| first.find(&format!("\"{fen}\":")).expect("fen key"))
+        .collect();
+    assert!(offsets.len() > 3);
+    assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn freq_cli_keeps_mainline_after_sidelines_glued_to_moves() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("glued.pgn");
+    fs::write(
+        &pgn_path,
+        "[Result \"*\"]\n\n1. e4 (1. d4 d5) 1... e5 2.Nf3 (2.Bc4) Nc6 (2...d6 3. d4) 3.Bb5 *\n",
+    )
+    .expect("write pgn");
+
+    let payload = run_freq(&[pgn_path.to_str().unwrap(), "--side", "white"]);
+    let rankings = payload["rankings"].as_object().expect("rankings");
+    let after_nc6 = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1";
+    assert_eq!(rankings[after_nc6][0]["san"], "Bb5");
+    let after_d6 = "rnbqkbnr/ppp2ppp/3p4/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1";
+    assert_eq!(rankings[after_d6][0]["san"], "d4");
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let mut first_moves: Vec<&str> = rankings[start]
+        .as_array()
+        .expect("start moves")
+        .iter()
+        .map(|m| m["san"].as_str().expect("san"))
+        .collect();
+    first_moves.sort();
+    assert_eq!(first_moves, vec!["d4", "e4"]);
+}
+
+#[test]
+fn freq_cli_ranks_both_sides_separately() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("freq_input.pgn");
+    fs::write(&pgn_path, "1. e4 e5 (1... c5) 2. Nf3 Nc6 *\n\n1. d4 d5 *\n").expect("write pgn");
+    let pgn = pgn_path.to_str().unwrap();
+
+    let both = run_freq(&[pgn, "--side", "both"]);
+    let white = run_freq(&[pgn, "--side", "white"]);
+    let black = run_freq(&[pgn, "--side", "black"]);
+
+    assert_eq!(both["side"], "both");
+    assert!(both.get("rankings").is_none());
+    assert_eq!(both["white"]["rankings"], white["rankings"]);
+    assert_eq!(both["white"]["total_nodes"], white["total_nodes"]);
+    assert_eq!(both["black"]["rankings"], black["rankings"]);
+    assert_eq!(both["black"]["total_nodes"], black["total_nodes"]);
+    assert_eq!(start_position_frequency(&both["white"], "d4"), 1);
+    let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+    let black_replies: Vec<&str> = both["black"]["rankings"][after_e4]
+        .as_array()
+        .expect("black moves after e4")
+        .iter()
+        .map(|m| m["san"].as_str().unwrap())
+        .collect();
+    assert_eq!(black_replies, vec!["c5", "e5"]);
+
+    let counts = run_freq(&[pgn, "--side", "both", "--count-only"]);
+    let total = white["total_nodes"].as_u64().unwrap() + black["total_nodes"].as_u64().unwrap();
+    assert_eq!(counts["player_nodes"], total);
+}
+
+#[test]
+fn freq_cli_reports_where_an_illegal_move_is() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("freq_input.pgn");
+    fs::write(
+        &pgn_path,
+        "1. e4 e5 2. Nf3 Nc6 *\n\n1. d4 d5 2. Ke3 Nf6 *\n",
+    )
+    .expect("write pgn");
+
+    for extra in [None, Some("--count-only")] {
+        #[allow(deprecated)]
+        let output = Command::cargo_bin("freq")
+            .expect("freq bin")
+            .arg(pgn_path.to_str().unwrap())
+            .args(extra)
+            .output()
+            .expect("run freq");
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("Illegal move 2. Ke3 at ply 3"),
+            "stderr: {stderr}"
+        );
+        assert!(
+            stderr.contains("rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 2"),
+            "stderr: {stderr}"
+        );
+    }
+}
+
+#[test]
+fn freq_cli_max_ply_ignores_deeper_moves() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("freq_input.pgn");
+    fs::write(
+        &pgn_path,
+        "1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 3. Bb5 a6 *\n",
+    )
+    .expect("write pgn");
+    let pgn = pgn_path.to_str().unwrap();
+
+    let full = run_freq(&[pgn, "--side", "white", "--max-ply", "0"]);
+    let limited = run_freq(&[pgn, "--side", "white", "--max-ply", "2"]);
+
+    assert_eq!(full["total_nodes"], 5);
+    assert_eq!(limited["total_nodes"], 3);
+    let sans: Vec<&str> = limited["rankings"]
+        .as_object()
+        .expect("rankings map")
+        .values()
+        .flat_map(|moves| moves.as_array().expect("moves"))
+        .map(|m| m["san"].as_str().unwrap())
+        .collect();
+    assert_eq!(sans, vec!["e4"]);
+    let after_e5 = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(limited["rankings"][after_e5], Value::Array(Vec::new()));
+
+    let counts = run_freq(&[pgn, "--side", "white", "--max-ply", "2", "--count-only"]);
+    assert_eq!(counts["total_moves"], 3);
+    assert_eq!(counts["player_nodes"], limited["total_nodes"]);
+}
@@ -0,0 +1,53 @@
+use std::fs;
+
+use assert_cmd::Command;
+use tempfile::tempdir;
+
+fn run_prune(pgn: &str, args: &[&str]) -> String {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("repertoire.pgn");
+    fs::write(&pgn_path, pgn).expect("write pgn");
+
+    #[allow(deprecated)]
+    let output = Command::cargo_bin("prune")
+        .expect("prune bin")
+        .arg(pgn_path.to_str().unwrap())
+        .args(args)
+        .output()
+        .expect("run prune");
+    assert!(
+        output.status.success(),
+        "prune exited with failure. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("utf8 output")
+}
+
+#[test]
+fn prune_cli_drops_rare_sidelines_and_their_subtrees() {
+    let pgn = "1. e4 e5 2. Nf3 Nc6 (2... d6 3. d4 exd4 4. Nxd4) 3. Bb5 *\n\n\
+               1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 *\n\n\
+               1. e4 c5 (1... e6 2. d3) 2. Nc3 Nc6 3. Bb5 *\n";
+
+    let pruned = run_prune(pgn, &["--side", "white", "--min-frequency", "2"]);
+
+    assert_eq!(
+        pruned,
+        "[Result \"*\"]\n\n1. e4 e5 2. Nf3 Nc6 (2... d6) 3. Bb5 *\n\n\
+         [Result \"*\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 *\n\n\
+         [Result \"*\"]\n\n1. e4 c5 (1... e6) *\n"
+    );
+    assert!(
+        !pruned.contains("Nxd4"),
+        "subtree after a pruned move stays"
+    );
+}
+
+#[test]
+fn prune_cli_never_prunes_a_game_to_nothing() {
+    let pruned = run_prune(
+        "1. a4 e5 2. h4 *\n",
+        &["--side", "white", "--min-frequency", "5"],
+    );
+    assert_eq!(pruned, "[Result \"*\"]\n\n1. a4 e5 *\n");
+}
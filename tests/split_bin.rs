@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::Path;
+
+use assert_cmd::Command;
+use serde_json::Value;
+use tempfile::tempdir;
+
+fn write_sample_pgn(path: &Path) {
+    let pgn = r#"[Event "?"]
+[Result "*"]
+
+1. e4 e5 (1... c5 2. Nf3 d6) 2. Nf3 Nc6 3. Bb5 a6 *
+
+[Event "?"]
+[Result "*"]
+
+1. d4 d5 2. c4 e6 3. Nc3 Nf6 *
+"#;
+    fs::write(path, pgn).expect("write pgn");
+}
+
+fn run_split(input: &Path, args: &[&str]) -> String {
+    #[allow(deprecated)]
+    let output = Command::cargo_bin("split")
+        .expect("split bin")
+        .arg(input.to_str().unwrap())
+        .args(args)
+        .output()
+        .expect("run split");
+    assert!(
+        output.status.success(),
+        "split exited with failure. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("utf8 output")
+}
+
+#[test]
+fn split_cli_writes_one_pgn_per_chapter() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("repertoire.pgn");
+    write_sample_pgn(&pgn_path);
+    let out_dir = tmp.path().join("chapters");
+
+    let stdout = run_split(
+        &pgn_path,
+        &[
+            "--max-moves",
+            "6",
+            "--output-dir",
+            out_dir.to_str().unwrap(),
+        ],
+    );
+
+    assert!(stdout.contains("Wrote 3 chapters"), "stdout: {stdout}");
+    let mut files: Vec<String> = fs::read_dir(&out_dir)
+        .expect("read output dir")
+        .map(|entry| entry.expect("entry").file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        vec!["chapter_001.pgn", "chapter_002.pgn", "chapter_003.pgn"]
+    );
+    let chapters: Vec<String> = files
+        .iter()
+        .map(|name| fs::read_to_string(out_dir.join(name)).expect("read chapter"))
+        .collect();
+    assert_eq!(
+        chapters,
+        vec![
+            "[Result \"*\"]\n\n1. d4 d5 2. c4 e6 3. Nc3 Nf6 *\n",
+            "[Result \"*\"]\n\n1. e4 c5 2. Nf3 d6 *\n",
+            "[Result \"*\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 *\n",
+        ]
+    );
+}
+
+#[test]
+fn split_cli_keeps_small_repertoire_in_one_chapter() {
+    let tmp = tempdir().expect("tempdir");
+    let pgn_path = tmp.path().join("repertoire.pgn");
+    write_sample_pgn(&pgn_path);
+
+    let stdout = run_split(&pgn_path, &["--format", "json"]);
+
+    let manifest: Value = serde_json::from_str(&stdout).expect("manifest json");
+    let chapters = manifest.as_array().expect("manifest array");
+    assert_eq!(chapters.len(), 1);
+    assert_eq!(chapters[0]["prefix"], Value::Array(Vec::new()));
+    assert_eq!(chapters[0]["move_count"], 15);
+    assert_eq!(
+        chapters[0]["pgn"],
+        "[Result \"*\"]\n\n1. e4 (1. d4 d5 2. c4 e6 3. Nc3 Nf6) 1... e5 \
+         (1... c5 2. Nf3 d6) 2. Nf3 Nc6 3. Bb5 a6 *\n"
+    );
+}
+
+#[test]
+fn split_cli_reads_json_node_list_from_root_fen() {
+    let tmp = tempdir().expect("tempdir");
+    let root = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+    let e5 = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1";
+    let c5 = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1";
+    let nodes = serde_json::json!([
+        {"fen": root, "children": [{"uci": "e7e5", "fen": e5}, {"uci": "c7c5", "fen": c5}]},
+    ]);
+    let nodes_path = tmp.path().join("nodes.json");
+    fs::write(&nodes_path, nodes.to_string()).expect("write nodes");
+
+    let stdout = run_split(
+        &nodes_path,
+        &["--format", "json", "--max-moves", "1", "--root-fen", root],
+    );
+
+    let manifest: Value = serde_json::from_str(&stdout).expect("manifest json");
+    let chapters = manifest.as_array().expect("manifest array");
+    assert_eq!(chapters.len(), 2);
+    assert_eq!(chapters[0]["prefix"], serde_json::json!(["c7c5"]));
+    assert_eq!(
+        chapters[0]["pgn"],
+        format!("[Result \"*\"]\n[FEN \"{root}\"]\n[SetUp \"1\"]\n\n1... c5 *\n")
+    );
+    assert_eq!(chapters[1]["prefix"], serde_json::json!(["e7e5"]));
+}
+
+#[test]
+fn split_cli_canonicalizes_json_node_fens() {
+    let tmp = tempdir().expect("tempdir");
+    // As written by python-chess: en passant square and real move counters.
+    let root = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+    let e5 = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2";
+    let c5 = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2";
+    let nodes = serde_json::json!([
+        {"fen": root, "children": [{"uci": "e7e5", "fen": e5}, {"uci": "c7c5", "fen": c5}]},
+    ]);
+    let nodes_path = tmp.path().join("nodes.json");
+    fs::write(&nodes_path, nodes.to_string()).expect("write nodes");
+
+    let stdout = run_split(&nodes_path, &["--format", "json", "--root-fen", root]);
+
+    let manifest: Value = serde_json::from_str(&stdout).expect("manifest json");
+    let chapters = manifest.as_array().expect("manifest array");
+    assert_eq!(chapters.len(), 1);
+    assert_eq!(chapters[0]["move_count"], 2);
+    assert_eq!(
+        chapters[0]["pgn"],
+        "[Result \"*\"]\n[FEN \"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\"]\n\
+         [SetUp \"1\"]\n\n1... e5 (1... c5) *\n"
+    );
+}